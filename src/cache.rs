@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+
+use crate::common::content_hash;
+
+/// The directory that compiled object files are kept in between runs: `ratio` in the user's
+/// cache directory, or in the temporary directory when there is none.
+pub fn dir() -> PathBuf {
+    if let Some(cache_home) = std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(cache_home).join("ratio");
+    }
+    match std::env::var_os("HOME").filter(|dir| !dir.is_empty()) {
+        Some(home) => PathBuf::from(home).join(".cache").join("ratio"),
+        None => std::env::temp_dir().join("ratio-cache"),
+    }
+}
+
+/// Removes every cached object file.
+pub fn clean() -> std::io::Result<()> {
+    match std::fs::remove_dir_all(dir()) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Everything that went into compiling an object file, so that a cached object is only reused
+/// for exactly the same source and options.
+#[derive(Clone)]
+pub struct Key {
+    parts: Vec<String>,
+}
+
+impl Key {
+    /// A key for objects from this build of the compiler, to which the source text and options
+    /// are added. The version alone does not change while the compiler is worked on, so the
+    /// executable's modification time is part of the key as well.
+    pub fn for_compiler() -> Self {
+        let built = std::env::current_exe()
+            .and_then(std::fs::metadata)
+            .and_then(|metadata| metadata.modified())
+            .map(|time| format!("{:?}", time))
+            .unwrap_or_default();
+        Key {
+            parts: vec![env!("CARGO_PKG_VERSION").to_string(), built],
+        }
+    }
+
+    /// Adds a source file's text, or an option that changes the generated code.
+    pub fn with(mut self, part: &str) -> Self {
+        self.parts.push(part.to_string());
+        self
+    }
+
+    /// Where the object file for this key is kept, whether or not it exists yet. `None` when the
+    /// cache directory cannot be created, in which case nothing is cached.
+    pub fn object_path(&self) -> Option<PathBuf> {
+        let dir = dir();
+        std::fs::create_dir_all(&dir).ok()?;
+        // Each part is prefixed with its length, so that no two lists of parts hash the same text.
+        let text: String = self
+            .parts
+            .iter()
+            .map(|part| format!("{}:{}", part.len(), part))
+            .collect();
+        let hash = content_hash(&text);
+        Some(dir.join(format!("{:016x}.o", hash)))
+    }
+}
@@ -2,7 +2,12 @@ use std::error::Error;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
-#[derive(Debug, Copy, Clone)]
+use serde::Serialize;
+
+use crate::diagnostics::{self, ErrorCode, Label, Note, NoteKind};
+use crate::lint::Lint;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct Position {
     pub line: usize,
     pub column: usize,
@@ -14,7 +19,46 @@ impl Position {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// One of the source files being compiled, by its position on the command line.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct FileId(pub usize);
+
+/// A region of a source file, from `start` up to but not including `end`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Span {
+    pub file: FileId,
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(file: FileId, start: Position, end: Position) -> Self {
+        Self { file, start, end }
+    }
+
+    /// An empty span at a single position.
+    pub fn at(file: FileId, position: Position) -> Self {
+        Self::new(file, position, position)
+    }
+
+    /// The span from the start of `self` to the end of `other`, which must be in the same file.
+    pub fn to(self, other: Span) -> Self {
+        Self::new(self.file, self.start, other.end)
+    }
+}
+
+/// Formats as `line:column-line:column`, without the file.
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.start.line, self.start.column, self.end.line, self.end.column
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
 pub enum Keyword {
     Fn,
     Extern,
@@ -22,21 +66,87 @@ pub enum Keyword {
     Return,
     Int,
     Bool,
+    Str,
     True,
     False,
 
     If,
     Else,
     While,
+    Noreturn,
+    Pub,
+    Inline,
+    Noinline,
+    Const,
+    Import,
+}
+
+impl Keyword {
+    /// The keyword as it is written in source.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Keyword::Fn => "fn",
+            Keyword::Extern => "extern",
+            Keyword::Var => "var",
+            Keyword::Return => "return",
+            Keyword::Int => "int",
+            Keyword::Bool => "bool",
+            Keyword::Str => "str",
+            Keyword::True => "true",
+            Keyword::False => "false",
+            Keyword::If => "if",
+            Keyword::Else => "else",
+            Keyword::While => "while",
+            Keyword::Noreturn => "noreturn",
+            Keyword::Pub => "pub",
+            Keyword::Inline => "inline",
+            Keyword::Noinline => "noinline",
+            Keyword::Const => "const",
+            Keyword::Import => "import",
+        }
+    }
+}
+
+impl Display for Keyword {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "keyword '{}'", self.as_str())
+    }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+/// Whether calls to a function should be inlined, as requested by `inline` or `noinline`.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub enum Inline {
+    /// Left to the optimizer.
+    Default,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
 pub enum Type {
     Int,
     Bool,
+    Str,
+    /// The result of calling a function that returns nothing. Such a call still returns, unlike
+    /// a noreturn one; it is only allowed as a statement, which discards its placeholder value.
+    Void,
+}
+
+/// What every Ratio function returns. There is no syntax yet for declaring another return type.
+pub const FUNCTION_RETURN_TYPE: Type = Type::Int;
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::Str => write!(f, "str"),
+            Type::Void => write!(f, "void"),
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
 pub enum Builtin {
     // I should find a better way to track builtin functions.
     // Probably something that is implicitly imported at the top of the code file.
@@ -45,7 +155,51 @@ pub enum Builtin {
     Input,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// How a builtin may be called. `max_args` of `None` means any number of trailing arguments.
+pub struct BuiltinSignature {
+    pub min_args: usize,
+    pub max_args: Option<usize>,
+    pub accepted_types: &'static [Type],
+    pub return_type: Type,
+}
+
+impl Builtin {
+    /// Every builtin; the lexer, the semantic checks and codegen all go through this table.
+    pub const ALL: [Builtin; 2] = [Builtin::Print, Builtin::Input];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::Print => "print",
+            Builtin::Input => "input",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        Builtin::ALL
+            .into_iter()
+            .find(|builtin| builtin.name() == name)
+    }
+
+    pub fn signature(&self) -> BuiltinSignature {
+        match self {
+            // `print(value)`, or printf-style `print(format, args...)`.
+            Builtin::Print => BuiltinSignature {
+                min_args: 1,
+                max_args: None,
+                accepted_types: &[Type::Int, Type::Bool, Type::Str],
+                return_type: Type::Void,
+            },
+            Builtin::Input => BuiltinSignature {
+                min_args: 0,
+                max_args: Some(0),
+                accepted_types: &[],
+                return_type: Type::Str,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Token {
     Keyword(Keyword),
     Builtin(Builtin),
@@ -67,102 +221,435 @@ pub enum Token {
     StringLiteral(String),
     NumberLiteral(i64),
     Identifier(String),
+    #[allow(clippy::upper_case_acronyms)]
     EOF,
     LeftBrace,
     RightBrace,
     Semicolon,
 }
 
-#[derive(Debug)]
+impl Token {
+    /// Whether the token begins a top-level item, which is where the parser resumes after an
+    /// error.
+    pub fn starts_item(&self) -> bool {
+        matches!(
+            self,
+            Token::Keyword(
+                Keyword::Fn
+                    | Keyword::Extern
+                    | Keyword::Pub
+                    | Keyword::Inline
+                    | Keyword::Noinline
+                    | Keyword::Const
+                    | Keyword::Import
+            )
+        )
+    }
+}
+
+/// Describes a token the way a user would refer to it, for use in parse errors.
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Token::Keyword(keyword) => write!(f, "{}", keyword),
+            Token::Builtin(builtin) => write!(f, "builtin '{}'", builtin.name()),
+            Token::LeftParen => write!(f, "'('"),
+            Token::RightParen => write!(f, "')'"),
+            Token::Colon => write!(f, "':'"),
+            Token::Comma => write!(f, "','"),
+            Token::Equals => write!(f, "'='"),
+            Token::Plus => write!(f, "'+'"),
+            Token::Minus => write!(f, "'-'"),
+            Token::Asterisk => write!(f, "'*'"),
+            Token::Slash => write!(f, "'/'"),
+            Token::Equality => write!(f, "'=='"),
+            Token::NotEqual => write!(f, "'!='"),
+            Token::LessThan => write!(f, "'<'"),
+            Token::LessThanOrEqual => write!(f, "'<='"),
+            Token::GreaterThan => write!(f, "'>'"),
+            Token::GreaterThanOrEqual => write!(f, "'>='"),
+            Token::StringLiteral(_) => write!(f, "a string"),
+            Token::NumberLiteral(_) => write!(f, "a number"),
+            Token::Identifier(_) => write!(f, "an identifier"),
+            Token::EOF => write!(f, "end of file"),
+            Token::LeftBrace => write!(f, "'{{'"),
+            Token::RightBrace => write!(f, "'}}'"),
+            Token::Semicolon => write!(f, "';'"),
+        }
+    }
+}
+
+/// Serializes, for `--dump-ast=json`, as an object with a field for each kind of item. Enums
+/// with fields are objects whose `kind` is the variant name; those without are strings. The
+/// `file` of a span is the index of its source file on the command line, counting the constants
+/// of `--define` as a file before the others.
+#[derive(Debug, Default, Serialize)]
 pub struct Program {
+    pub imports: Vec<Import>,
     pub functions: Vec<Stmt>,
     pub externs: Vec<ExternFunction>,
+    pub consts: Vec<ConstDecl>,
+}
+
+impl Program {
+    /// Every top-level item, in the order they appear in the source files.
+    pub fn items(&self) -> Vec<Item<'_>> {
+        let mut items: Vec<Item> = Vec::new();
+        items.extend(self.imports.iter().map(Item::Import));
+        items.extend(self.functions.iter().map(Item::Function));
+        items.extend(self.externs.iter().map(Item::Extern));
+        items.extend(self.consts.iter().map(Item::Const));
+        items.sort_by_key(|item| item.span());
+        items
+    }
+
+    /// Adds the items of another source file.
+    pub fn merge(&mut self, other: Program) {
+        self.imports.extend(other.imports);
+        self.functions.extend(other.functions);
+        self.externs.extend(other.externs);
+        self.consts.extend(other.consts);
+    }
+}
+
+/// A top-level item of a program.
+#[derive(Clone, Copy)]
+pub enum Item<'a> {
+    Import(&'a Import),
+    /// Always a `Stmt::Function`.
+    Function(&'a Stmt),
+    Extern(&'a ExternFunction),
+    Const(&'a ConstDecl),
+}
+
+impl Item<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            Item::Import(import) => import.span,
+            Item::Function(function) => function.span(),
+            Item::Extern(ext) => ext.span,
+            Item::Const(constant) => constant.span,
+        }
+    }
+}
+
+/// A top-level `import "path";`, which adds the items of another source file to the program. The
+/// path is relative to the directory of the file that imports it.
+#[derive(Debug, Serialize)]
+pub struct Import {
+    pub path: String,
+    pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+/// A top-level `const NAME: type = value;`, whose value is computed at compile time.
+#[derive(Debug, Serialize)]
+pub struct ConstDecl {
+    pub name: String,
+    pub ty: Type,
+    pub value: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct ExternFunction {
     pub name: String,
     pub args: Vec<(String, Type)>, // (name, type)
-    pub return_type: String,
+    pub return_type: Type,
+    /// Declared with `noreturn` in place of a return type.
+    pub noreturn: bool,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+/// C library functions that never return. They are treated as `noreturn` even when declared
+/// without it, so that `extern fn exit(code: int);` keeps working as expected.
+const IMPLICITLY_NORETURN: [&str; 3] = ["exit", "_exit", "abort"];
+
+/// A C library function that codegen declares itself, because a builtin is implemented with it.
+pub struct RuntimeFunction {
+    pub name: &'static str,
+    pub builtin: Builtin,
+    /// The parameters every call passes. A variadic function may be declared with more.
+    pub params: &'static [Type],
+    pub variadic: bool,
+    /// What an extern for this function may declare it to return. printf returns a C `int`, which
+    /// an extern can ignore or declare as `int`, in which case calls sign-extend it to 64 bits.
+    pub return_types: &'static [Type],
+}
+
+pub const RUNTIME_FUNCTIONS: [RuntimeFunction; 1] = [RuntimeFunction {
+    name: "printf",
+    builtin: Builtin::Print,
+    params: &[Type::Str],
+    variadic: true,
+    return_types: &[Type::Void, Type::Int],
+}];
+
+impl RuntimeFunction {
+    /// Whether `ext` can share this function's declaration, so that calls through either one
+    /// behave the same.
+    pub fn accepts(&self, ext: &ExternFunction) -> bool {
+        let arg_count_ok = if self.variadic {
+            ext.args.len() >= self.params.len()
+        } else {
+            ext.args.len() == self.params.len()
+        };
+        arg_count_ok
+            && self
+                .params
+                .iter()
+                .zip(&ext.args)
+                .all(|(param, (_, arg))| param == arg)
+            && self.return_types.contains(&ext.return_type)
+            && !ext.is_noreturn()
+    }
+}
+
+/// The 64-bit FNV-1a hash of `value`, which backends name string constants after. Unlike the
+/// standard library's hasher, it is specified, so it gives the same result from every build of
+/// the compiler.
+pub fn content_hash(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl ExternFunction {
+    /// Whether calls to this function never return to the caller.
+    pub fn is_noreturn(&self) -> bool {
+        self.noreturn || IMPLICITLY_NORETURN.contains(&self.name.as_str())
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind")]
 pub enum Stmt {
     Function {
         name: String,
         args: Vec<(String, Type)>,
         body: Vec<Stmt>,
-        return_expr: Option<Expr>,
+        /// Declared with `pub`, so that code outside the program can call it.
+        public: bool,
+        inline: Inline,
+        span: Span,
+    },
+    Return {
+        value: Expr,
+        span: Span,
     },
-    Return(Expr),
-    ExternFunction(ExternFunction),
     VariableDecl {
         name: String,
-        type_name: String,
+        var_type: Type,
         value: Expr,
+        span: Span,
     },
     Assignment {
         name: String,
         value: Expr,
+        span: Span,
     },
 
     IfStatement {
         condition: Expr,
         body: Vec<Stmt>,
         else_body: Option<Vec<Stmt>>,
+        span: Span,
     },
     While {
         condition: Expr,
         body: Vec<Stmt>,
+        span: Span,
+    },
+    #[allow(clippy::enum_variant_names)]
+    ExprStmt {
+        expr: Expr,
+        span: Span,
     },
-    ExprStmt(Expr),
 }
 
-#[derive(Debug)]
+impl Stmt {
+    /// The source covered by the statement. For a function this is its signature.
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Function { span, .. }
+            | Stmt::Return { span, .. }
+            | Stmt::VariableDecl { span, .. }
+            | Stmt::Assignment { span, .. }
+            | Stmt::IfStatement { span, .. }
+            | Stmt::While { span, .. }
+            | Stmt::ExprStmt { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind")]
 pub enum Expr {
     Call {
         callee: String,
         args: Vec<Expr>,
+        span: Span,
+    },
+    Variable {
+        name: String,
+        span: Span,
+    },
+    StringLiteral {
+        value: String,
+        span: Span,
+    },
+    IntegerLiteral {
+        value: i64,
+        span: Span,
+    },
+    BooleanLiteral {
+        value: bool,
+        span: Span,
     },
-    Variable(String),
-    StringLiteral(String),
-    IntegerLiteral(i64),
-    BooleanLiteral(bool),
     BinaryOperator {
         operator: String,
         left: Box<Expr>,
         right: Box<Expr>,
+        span: Span,
     },
     BooleanComparison {
         lvalue: Box<Expr>,
         operator: Token,
+        /// Where the operator is, for errors about the comparison as a whole.
+        #[serde(skip)]
+        operator_span: Span,
         rvalue: Box<Expr>,
+        span: Span,
     },
 }
 
+impl Expr {
+    /// The source covered by the expression, including the operands of an operator.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Call { span, .. }
+            | Expr::Variable { span, .. }
+            | Expr::StringLiteral { span, .. }
+            | Expr::IntegerLiteral { span, .. }
+            | Expr::BooleanLiteral { span, .. }
+            | Expr::BinaryOperator { span, .. }
+            | Expr::BooleanComparison { span, .. } => *span,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CompileError {
     pub message: String,
-    pub position: Position,
+    /// Where the error is; this is the location that is reported first.
+    pub span: Span,
+    pub code: Option<ErrorCode>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<Note>,
 }
 
 impl CompileError {
-    pub fn new(message: impl Into<String>, position: Position) -> Self {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
         Self {
             message: message.into(),
-            position,
+            span,
+            code: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
         }
     }
+
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, message: impl Into<String>) -> Self {
+        self.notes.push(Note {
+            kind: NoteKind::Note,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_help(mut self, message: impl Into<String>) -> Self {
+        self.notes.push(Note {
+            kind: NoteKind::Help,
+            message: message.into(),
+        });
+        self
+    }
 }
 
 impl Display for CompileError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Error")?;
+        if let Some(code) = self.code {
+            write!(f, "[{}]", code)?;
+        }
         write!(
             f,
-            "Error at {}:{}\n{}",
-            self.position.line, self.position.column, self.message,
-        )
+            " at {}:{}\n{}",
+            self.span.start.line, self.span.start.column, self.message,
+        )?;
+        diagnostics::write_details(f, &self.labels, &self.notes)
     }
 }
 
 impl Error for CompileError {}
+
+#[derive(Debug)]
+pub struct CompileWarning {
+    /// The lint the warning belongs to, which decides whether and how it is reported.
+    pub lint: Lint,
+    pub message: String,
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub notes: Vec<Note>,
+}
+
+impl CompileWarning {
+    pub fn new(lint: Lint, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            lint,
+            message: message.into(),
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, message: impl Into<String>) -> Self {
+        self.notes.push(Note {
+            kind: NoteKind::Note,
+            message: message.into(),
+        });
+        self
+    }
+}
+
+impl Display for CompileWarning {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Warning[{}] at {}:{}\n{}",
+            self.lint, self.span.start.line, self.span.start.column, self.message,
+        )?;
+        diagnostics::write_details(f, &self.labels, &self.notes)
+    }
+}
@@ -0,0 +1,167 @@
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{AbiParam, InstBuilder, Value, types};
+use cranelift_module::{DataId, FuncId, Module};
+
+use super::FunctionCodegen;
+use crate::common::{Builtin, Token, Type};
+use crate::hir::{Callee, ConstValue, Expr, ExprKind};
+use crate::semantics::format;
+
+impl FunctionCodegen<'_> {
+    pub fn gen_expr(&mut self, expr: &Expr) -> Value {
+        match &expr.kind {
+            ExprKind::Call { callee, args } => match callee {
+                Callee::Builtin(Builtin::Print) if args.len() > 1 => {
+                    let format = match &args[0].kind {
+                        ExprKind::StringLiteral { value } => {
+                            let data = self
+                                .codegen
+                                .intern_string(&format::to_printf_format(value), "fmt");
+                            self.string_address(data)
+                        }
+                        _ => self.gen_expr(&args[0]),
+                    };
+                    let mut printf_args = vec![format];
+                    for arg in &args[1..] {
+                        printf_args.push(self.gen_expr(arg));
+                    }
+                    let printf = self.codegen.printf;
+                    self.call_variadic(printf, &printf_args);
+                    if !matches!(args[0].kind, ExprKind::StringLiteral { .. }) {
+                        let newline = self.codegen.intern_string("\n", "newline");
+                        let newline = self.string_address(newline);
+                        self.call_variadic(printf, &[newline]);
+                    }
+                    self.builder.ins().iconst(types::I64, 0)
+                }
+                Callee::Builtin(Builtin::Print) => {
+                    let arg = &args[0];
+                    let val = self.gen_expr(arg);
+                    let fmt = match arg.ty {
+                        Type::Str => self.codegen.fmt_str,
+                        _ => self.codegen.fmt_int,
+                    };
+                    let fmt = self.string_address(fmt);
+                    let printf = self.codegen.printf;
+                    self.call_variadic(printf, &[fmt, val]);
+                    self.builder.ins().iconst(types::I64, 0)
+                }
+                Callee::Builtin(Builtin::Input) => {
+                    unreachable!("calls to `input` are rejected before code generation")
+                }
+                Callee::Function { name, .. } | Callee::Extern { name, .. } => {
+                    let mut arg_vals = Vec::with_capacity(args.len());
+                    for arg in args {
+                        arg_vals.push(self.gen_expr(arg));
+                    }
+                    let fn_sig = &self.codegen.function_table[name];
+                    let (id, returns_value) = (fn_sig.id, fn_sig.returns_value);
+                    if fn_sig.variadic {
+                        let result = self.call_variadic(id, &arg_vals);
+                        // printf, which an extern can declare as returning int, returns a 32-bit
+                        // C int.
+                        return if returns_value {
+                            self.builder.ins().sextend(types::I64, result)
+                        } else {
+                            self.builder.ins().iconst(types::I64, 0)
+                        };
+                    }
+                    let callee = self
+                        .codegen
+                        .module
+                        .declare_func_in_func(id, self.builder.func);
+                    let call = self.builder.ins().call(callee, &arg_vals);
+                    if returns_value {
+                        self.builder.inst_results(call)[0]
+                    } else {
+                        self.builder.ins().iconst(types::I64, 0)
+                    }
+                }
+            },
+            ExprKind::Variable { var } => self.builder.use_var(self.variables[var.0]),
+            ExprKind::Const { id } => match &self.codegen.consts[id.0] {
+                ConstValue::Int(value) => self.builder.ins().iconst(types::I64, *value),
+                ConstValue::Bool(value) => self.builder.ins().iconst(types::I64, *value as i64),
+                ConstValue::Str(value) => {
+                    let value = value.clone();
+                    let data = self.codegen.intern_string(&value, "str");
+                    self.string_address(data)
+                }
+            },
+            ExprKind::StringLiteral { value: s } => {
+                let data = self.codegen.intern_string(s, "str");
+                self.string_address(data)
+            }
+            ExprKind::IntegerLiteral { value: n } => self.builder.ins().iconst(types::I64, *n),
+            ExprKind::BooleanLiteral { value: b } => {
+                self.builder.ins().iconst(types::I64, *b as i64)
+            }
+            ExprKind::BinaryOperator {
+                operator,
+                left,
+                right,
+            } => {
+                let l = self.gen_expr(left);
+                let r = self.gen_expr(right);
+                match operator.as_str() {
+                    "+" => self.builder.ins().iadd(l, r),
+                    "-" => self.builder.ins().isub(l, r),
+                    "*" => self.builder.ins().imul(l, r),
+                    "/" => self.builder.ins().sdiv(l, r),
+                    _ => self.builder.ins().iconst(types::I64, 0),
+                }
+            }
+            ExprKind::BooleanComparison {
+                lvalue,
+                operator,
+                rvalue,
+            } => {
+                let l = self.gen_expr(lvalue);
+                let r = self.gen_expr(rvalue);
+                let cond = match operator {
+                    Token::Equality => IntCC::Equal,
+                    Token::NotEqual => IntCC::NotEqual,
+                    Token::LessThan => IntCC::SignedLessThan,
+                    Token::LessThanOrEqual => IntCC::SignedLessThanOrEqual,
+                    Token::GreaterThan => IntCC::SignedGreaterThan,
+                    Token::GreaterThanOrEqual => IntCC::SignedGreaterThanOrEqual,
+                    _ => IntCC::Equal,
+                };
+                // `icmp` produces an `i8`, but bools are 64-bit integers everywhere else.
+                let flag = self.builder.ins().icmp(cond, l, r);
+                self.builder.ins().uextend(types::I64, flag)
+            }
+        }
+    }
+
+    /// The address of a string constant.
+    fn string_address(&mut self, data: DataId) -> Value {
+        let global = self
+            .codegen
+            .module
+            .declare_data_in_func(data, self.builder.func);
+        let pointer_type = self.codegen.pointer_type;
+        self.builder.ins().global_value(pointer_type, global)
+    }
+
+    /// Calls a C variadic function such as printf, returning its 32-bit result. Cranelift
+    /// signatures cannot be variadic, so the call goes through a signature made for these
+    /// particular arguments.
+    fn call_variadic(&mut self, function: FuncId, args: &[Value]) -> Value {
+        let mut signature = self.codegen.module.make_signature();
+        for arg in args {
+            let ty = self.builder.func.dfg.value_type(*arg);
+            signature.params.push(AbiParam::new(ty));
+        }
+        signature.returns.push(AbiParam::new(types::I32));
+        let signature = self.builder.import_signature(signature);
+        let callee = self
+            .codegen
+            .module
+            .declare_func_in_func(function, self.builder.func);
+        let pointer_type = self.codegen.pointer_type;
+        let address = self.builder.ins().func_addr(pointer_type, callee);
+        let call = self.builder.ins().call_indirect(signature, address, args);
+        self.builder.inst_results(call)[0]
+    }
+}
@@ -0,0 +1,339 @@
+use cranelift_codegen::ir::{self, AbiParam, InstBuilder, types};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_module::{DataDescription, DataId, FuncId, Linkage, Module, default_libcall_names};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+use std::collections::HashMap;
+
+use crate::common::{RUNTIME_FUNCTIONS, Type, content_hash};
+use crate::hir::units::{Binding, Unit};
+use crate::hir::{self, ConstValue, USER_MAIN_SYMBOL};
+use crate::ice;
+
+mod expr;
+mod stmt;
+
+/// How to generate code, from the command line options that this backend supports.
+pub struct Options {
+    /// Cranelift's `opt_level` setting: `none`, `speed` or `speed_and_size`.
+    pub opt_level: &'static str,
+    pub pic: bool,
+    /// Whether to emit the C main, which an object linked into a program that has a C main of
+    /// its own must leave out.
+    pub c_main: bool,
+}
+
+pub struct FnSig {
+    id: FuncId,
+    returns_value: bool,
+    /// Declared by the compiler with only its fixed parameters, such as printf. Calls go through
+    /// a signature built from the arguments of each call, since Cranelift has no variadic
+    /// signatures.
+    variadic: bool,
+}
+
+/// The state shared by every function of the object file being generated.
+pub struct Codegen {
+    module: ObjectModule,
+    pointer_type: ir::Type,
+    /// Every function that can be called, by its Ratio name.
+    function_table: HashMap<String, FnSig>,
+    printf: FuncId,
+    /// The formats for printing a single value.
+    fmt_int: DataId,
+    fmt_str: DataId,
+    /// String constants by their contents, so that a repeated literal is only stored once.
+    string_literals: HashMap<String, DataId>,
+    /// The value of each constant, indexed by `ConstId`.
+    consts: Vec<ConstValue>,
+}
+
+/// Compiles the functions of `unit` for the host into the contents of an object file, which
+/// refers to the program's other functions for the linker to resolve. An error means the host
+/// is not a target Cranelift supports.
+pub fn generate_object(
+    program: &hir::Program,
+    unit: &Unit,
+    options: &Options,
+) -> Result<Vec<u8>, String> {
+    let mut flags = settings::builder();
+    flags
+        .set("opt_level", options.opt_level)
+        .expect("valid opt_level");
+    flags
+        .set("is_pic", if options.pic { "true" } else { "false" })
+        .expect("valid is_pic");
+    let isa = cranelift_native::builder()
+        .map_err(|message| {
+            format!(
+                "the cranelift backend cannot target this machine: {}",
+                message
+            )
+        })?
+        .finish(settings::Flags::new(flags))
+        .map_err(|error| {
+            format!(
+                "the cranelift backend cannot target this machine: {}",
+                error
+            )
+        })?;
+    let builder = ObjectBuilder::new(isa, "main", default_libcall_names()).map_err(|error| {
+        format!(
+            "the cranelift backend cannot target this machine: {}",
+            error
+        )
+    })?;
+
+    let mut codegen = Codegen::new(ObjectModule::new(builder), program);
+    codegen.declare_functions(program, unit);
+    let mut builder_context = FunctionBuilderContext::new();
+    let defined = program
+        .functions
+        .iter()
+        .filter(|func| unit.binding(func) != Binding::Imported);
+    for func in defined.clone() {
+        ice::set_context(format!(
+            "compiling function `{}` at {}:{}",
+            func.name, func.span.start.line, func.span.start.column
+        ));
+        codegen.gen_function(func, &mut builder_context);
+    }
+    ice::clear_context();
+    if options.c_main && defined.clone().any(|func| func.name == program.entry) {
+        let user_main = codegen.function_table[&program.entry].id;
+        codegen.emit_c_main(user_main, &mut builder_context);
+    }
+
+    let product = codegen.module.finish();
+    Ok(product.emit().expect("emit object file"))
+}
+
+impl Codegen {
+    fn new(mut module: ObjectModule, program: &hir::Program) -> Self {
+        let pointer_type = module.target_config().pointer_type();
+        let mut printf_signature = module.make_signature();
+        printf_signature.params.push(AbiParam::new(pointer_type));
+        printf_signature.returns.push(AbiParam::new(types::I32));
+        let printf = module
+            .declare_function("printf", Linkage::Import, &printf_signature)
+            .expect("declare printf");
+
+        let mut codegen = Codegen {
+            module,
+            pointer_type,
+            function_table: HashMap::new(),
+            printf,
+            fmt_int: DataId::from_u32(0),
+            fmt_str: DataId::from_u32(0),
+            string_literals: HashMap::new(),
+            consts: program
+                .consts
+                .iter()
+                .map(|constant| constant.value.clone())
+                .collect(),
+        };
+        codegen.fmt_int = codegen.define_string("%lld\n", "fmt_int".to_string());
+        codegen.fmt_str = codegen.define_string("%s\n", "fmt_str".to_string());
+        codegen
+    }
+
+    /// Declares every extern and function, so that calls can refer to functions defined later in
+    /// the file or in another object.
+    fn declare_functions(&mut self, program: &hir::Program, unit: &Unit) {
+        for ext in &program.externs {
+            // An extern for a function the compiler declared for a builtin shares that
+            // declaration. Semantic analysis has checked that the two are compatible.
+            if RUNTIME_FUNCTIONS
+                .iter()
+                .any(|runtime| runtime.name == ext.name)
+            {
+                self.function_table.insert(
+                    ext.name.clone(),
+                    FnSig {
+                        id: self.printf,
+                        returns_value: ext.signature.return_type != Type::Void,
+                        variadic: true,
+                    },
+                );
+                continue;
+            }
+            let mut signature = self.module.make_signature();
+            for param in &ext.signature.params {
+                signature
+                    .params
+                    .push(AbiParam::new(self.value_type(*param)));
+            }
+            let returns_value = ext.signature.return_type != Type::Void;
+            if returns_value {
+                let return_type = self.value_type(ext.signature.return_type);
+                signature.returns.push(AbiParam::new(return_type));
+            }
+            let id = self
+                .module
+                .declare_function(&ext.name, Linkage::Import, &signature)
+                .expect("declare extern");
+            self.function_table.insert(
+                ext.name.clone(),
+                FnSig {
+                    id,
+                    returns_value,
+                    variadic: false,
+                },
+            );
+        }
+
+        for func in &program.functions {
+            let signature = self.function_signature(func);
+            let symbol = if func.name == "main" {
+                USER_MAIN_SYMBOL
+            } else {
+                func.name.as_str()
+            };
+            let linkage = match unit.binding(func) {
+                Binding::Local => Linkage::Local,
+                Binding::Exported => Linkage::Export,
+                Binding::Imported => Linkage::Import,
+            };
+            let id = self
+                .module
+                .declare_function(symbol, linkage, &signature)
+                .expect("declare function");
+            self.function_table.insert(
+                func.name.clone(),
+                FnSig {
+                    id,
+                    returns_value: true,
+                    variadic: false,
+                },
+            );
+        }
+    }
+
+    fn function_signature(&self, func: &hir::Function) -> ir::Signature {
+        let mut signature = self.module.make_signature();
+        for param in &func.signature.params {
+            signature
+                .params
+                .push(AbiParam::new(self.value_type(*param)));
+        }
+        let return_type = self.value_type(func.signature.return_type);
+        signature.returns.push(AbiParam::new(return_type));
+        signature
+    }
+
+    fn gen_function(&mut self, func: &hir::Function, builder_context: &mut FunctionBuilderContext) {
+        let id = self.function_table[&func.name].id;
+        let mut context = self.module.make_context();
+        context.func.signature = self.function_signature(func);
+        let mut builder = FunctionBuilder::new(&mut context.func, builder_context);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+
+        // Locals are Cranelift variables, which the frontend turns into SSA values. Each starts
+        // out as zero, so that every path defines it before use.
+        let mut variables = Vec::with_capacity(func.locals.len());
+        for (i, local) in func.locals.iter().enumerate() {
+            let variable = Variable::from_u32(i as u32);
+            let ty = self.value_type(local.ty);
+            builder.declare_var(variable, ty);
+            let zero = builder.ins().iconst(ty, 0);
+            builder.def_var(variable, zero);
+            variables.push(variable);
+        }
+        let params = builder.block_params(entry).to_vec();
+        for (param, value) in func.params.iter().zip(params) {
+            builder.def_var(variables[param.0], value);
+        }
+
+        let mut function = FunctionCodegen {
+            codegen: self,
+            builder,
+            variables,
+            terminated: false,
+        };
+        function.gen_block(&func.body);
+        if !function.terminated {
+            let return_type = function.codegen.value_type(func.signature.return_type);
+            let zero = function.builder.ins().iconst(return_type, 0);
+            function.builder.ins().return_(&[zero]);
+        }
+        function.builder.seal_all_blocks();
+        function.builder.finalize();
+
+        self.module
+            .define_function(id, &mut context)
+            .expect("define function");
+    }
+
+    /// Emits `int main(void)`, which calls the entry point and returns its result as the exit
+    /// status.
+    fn emit_c_main(&mut self, user_main: FuncId, builder_context: &mut FunctionBuilderContext) {
+        let mut signature = self.module.make_signature();
+        signature.returns.push(AbiParam::new(types::I32));
+        let id = self
+            .module
+            .declare_function("main", Linkage::Export, &signature)
+            .expect("declare main");
+        let mut context = self.module.make_context();
+        context.func.signature = signature;
+        let mut builder = FunctionBuilder::new(&mut context.func, builder_context);
+        let entry = builder.create_block();
+        builder.switch_to_block(entry);
+        let callee = self.module.declare_func_in_func(user_main, builder.func);
+        let call = builder.ins().call(callee, &[]);
+        let result = builder.inst_results(call)[0];
+        let status = builder.ins().ireduce(types::I32, result);
+        builder.ins().return_(&[status]);
+        builder.seal_all_blocks();
+        builder.finalize();
+        self.module
+            .define_function(id, &mut context)
+            .expect("define main");
+    }
+
+    /// The data object holding a string constant with the given contents, reusing an earlier one
+    /// if possible. It is named after a hash of the contents, like the LLVM backend's.
+    fn intern_string(&mut self, value: &str, name: &str) -> DataId {
+        if let Some(id) = self.string_literals.get(value) {
+            return *id;
+        }
+        let id = self.define_string(value, format!("{}.{:016x}", name, content_hash(value)));
+        self.string_literals.insert(value.to_string(), id);
+        id
+    }
+
+    /// Adds a local, null-terminated, read-only string to the object file.
+    fn define_string(&mut self, value: &str, name: String) -> DataId {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        let mut data = DataDescription::new();
+        data.define(bytes.into_boxed_slice());
+        let id = self
+            .module
+            .declare_data(&name, Linkage::Local, false, false)
+            .expect("declare string");
+        self.module.define_data(id, &data).expect("define string");
+        id
+    }
+
+    /// The Cranelift type that holds a Ratio value. Bools are 64-bit integers, as in the LLVM
+    /// backend.
+    fn value_type(&self, t: Type) -> ir::Type {
+        match t {
+            Type::Int | Type::Bool => types::I64,
+            Type::Str => self.pointer_type,
+            Type::Void => unreachable!("void values are never stored"),
+        }
+    }
+}
+
+/// The state of emitting one function.
+pub struct FunctionCodegen<'a> {
+    codegen: &'a mut Codegen,
+    builder: FunctionBuilder<'a>,
+    /// The variable of each local, indexed by `VarId`.
+    variables: Vec<Variable>,
+    /// Whether the current block already ends in a terminator, such as a `return`.
+    terminated: bool,
+}
@@ -0,0 +1,101 @@
+use cranelift_codegen::ir::{InstBuilder, TrapCode};
+
+use super::FunctionCodegen;
+use crate::hir::{ExprKind, Stmt};
+
+impl FunctionCodegen<'_> {
+    pub fn gen_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Return { value, .. } => {
+                let val = self.gen_expr(value);
+                self.builder.ins().return_(&[val]);
+                self.terminated = true;
+            }
+            Stmt::VariableDecl { var, value, .. } | Stmt::Assignment { var, value, .. } => {
+                let val = self.gen_expr(value);
+                self.builder.def_var(self.variables[var.0], val);
+            }
+            Stmt::ExprStmt { expr, .. } => {
+                self.gen_expr(expr);
+                if let ExprKind::Call { callee, .. } = &expr.kind
+                    && callee.is_noreturn()
+                {
+                    self.builder.ins().trap(TrapCode::unwrap_user(1));
+                    self.terminated = true;
+                }
+            }
+            Stmt::IfStatement {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                // A branch that ended in a return has nowhere to go, so the merge block is only
+                // added to the function when at least one branch falls through to it.
+                let merge_block = self.builder.create_block();
+
+                let cond_val = self.gen_expr(condition);
+                self.builder
+                    .ins()
+                    .brif(cond_val, then_block, &[], else_block, &[]);
+
+                self.builder.switch_to_block(then_block);
+                self.gen_block(body);
+                let then_falls_through = !self.terminated;
+                if then_falls_through {
+                    self.builder.ins().jump(merge_block, &[]);
+                }
+
+                self.builder.switch_to_block(else_block);
+                self.terminated = false;
+                if let Some(else_body) = else_body {
+                    self.gen_block(else_body);
+                }
+                if !self.terminated {
+                    self.builder.ins().jump(merge_block, &[]);
+                }
+
+                if then_falls_through || !self.terminated {
+                    self.builder.switch_to_block(merge_block);
+                    self.terminated = false;
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                let cond_block = self.builder.create_block();
+                let body_block = self.builder.create_block();
+                let after_block = self.builder.create_block();
+
+                self.builder.ins().jump(cond_block, &[]);
+                self.builder.switch_to_block(cond_block);
+                let cond_val = self.gen_expr(condition);
+                self.builder
+                    .ins()
+                    .brif(cond_val, body_block, &[], after_block, &[]);
+
+                self.builder.switch_to_block(body_block);
+                self.gen_block(body);
+                if !self.terminated {
+                    self.builder.ins().jump(cond_block, &[]);
+                }
+
+                self.builder.switch_to_block(after_block);
+                self.terminated = false;
+            }
+        }
+    }
+
+    /// Emits the statements of a block, stopping once one of them ends the current block, since
+    /// nothing after a `return` can execute.
+    pub fn gen_block(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.gen_stmt(stmt);
+            if self.terminated {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,815 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::common::{CompileError, CompileWarning, Span};
+
+/// A stable identifier for a kind of error, such as `R0003`. Codes are never reused, so scripts
+/// and searches can rely on them rather than on the wording of a message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorCode {
+    UnexpectedToken,
+    UnclosedDelimiter,
+    UndeclaredVariable,
+    UnknownFunction,
+    MismatchedTypes,
+    WrongArgumentCount,
+    MismatchedArgument,
+    InvalidOperands,
+    InvalidFormat,
+    MissingMain,
+    MainWithParameters,
+    ConflictingDeclarations,
+    DuplicateDefinition,
+    MisusedAssignment,
+    ChainedComparison,
+    UnknownCharacter,
+    LiteralOutOfRange,
+    MissingSemicolon,
+    ReturnAfterBody,
+    NonConstantExpression,
+    ConstantEvaluation,
+    AssignmentToConstant,
+    ImportCycle,
+    UnresolvedImport,
+}
+
+impl ErrorCode {
+    /// Every error code, in numerical order.
+    pub const ALL: [ErrorCode; 24] = [
+        ErrorCode::UnexpectedToken,
+        ErrorCode::UnclosedDelimiter,
+        ErrorCode::UndeclaredVariable,
+        ErrorCode::UnknownFunction,
+        ErrorCode::MismatchedTypes,
+        ErrorCode::WrongArgumentCount,
+        ErrorCode::MismatchedArgument,
+        ErrorCode::InvalidOperands,
+        ErrorCode::InvalidFormat,
+        ErrorCode::MissingMain,
+        ErrorCode::MainWithParameters,
+        ErrorCode::ConflictingDeclarations,
+        ErrorCode::DuplicateDefinition,
+        ErrorCode::MisusedAssignment,
+        ErrorCode::ChainedComparison,
+        ErrorCode::UnknownCharacter,
+        ErrorCode::LiteralOutOfRange,
+        ErrorCode::MissingSemicolon,
+        ErrorCode::ReturnAfterBody,
+        ErrorCode::NonConstantExpression,
+        ErrorCode::ConstantEvaluation,
+        ErrorCode::AssignmentToConstant,
+        ErrorCode::ImportCycle,
+        ErrorCode::UnresolvedImport,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedToken => "R0001",
+            ErrorCode::UnclosedDelimiter => "R0002",
+            ErrorCode::UndeclaredVariable => "R0003",
+            ErrorCode::UnknownFunction => "R0004",
+            ErrorCode::MismatchedTypes => "R0005",
+            ErrorCode::WrongArgumentCount => "R0006",
+            ErrorCode::MismatchedArgument => "R0007",
+            ErrorCode::InvalidOperands => "R0008",
+            ErrorCode::InvalidFormat => "R0009",
+            ErrorCode::MissingMain => "R0010",
+            ErrorCode::MainWithParameters => "R0011",
+            ErrorCode::ConflictingDeclarations => "R0012",
+            ErrorCode::DuplicateDefinition => "R0013",
+            ErrorCode::MisusedAssignment => "R0014",
+            ErrorCode::ChainedComparison => "R0015",
+            ErrorCode::UnknownCharacter => "R0016",
+            ErrorCode::LiteralOutOfRange => "R0017",
+            ErrorCode::MissingSemicolon => "R0018",
+            ErrorCode::ReturnAfterBody => "R0019",
+            ErrorCode::NonConstantExpression => "R0020",
+            ErrorCode::ConstantEvaluation => "R0021",
+            ErrorCode::AssignmentToConstant => "R0022",
+            ErrorCode::ImportCycle => "R0023",
+            ErrorCode::UnresolvedImport => "R0024",
+        }
+    }
+
+    /// Looks up a code as written by the user; `r0003` and `R0003` are the same code.
+    pub fn from_code(code: &str) -> Option<ErrorCode> {
+        ErrorCode::ALL
+            .into_iter()
+            .find(|error| error.code().eq_ignore_ascii_case(code))
+    }
+
+    /// The longer description printed by `--explain`.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedToken => {
+                "The parser found a token that cannot appear at this point in the program.
+
+Erroneous code example:
+
+    fn main() {
+        var x: int = ;
+    }
+
+The initializer of `x` is missing, so the parser found ';' where it expected an
+expression. The message says what the parser was expecting; supplying it fixes the
+error:
+
+    fn main() {
+        var x: int = 1;
+    }"
+            }
+            ErrorCode::UnclosedDelimiter => {
+                "A '{' or '(' was opened but never closed, or a '}' or ')' was found with
+nothing to close.
+
+Erroneous code example:
+
+    extern {
+        fn abs(x: int) int;
+
+The error points at the delimiter that is never closed. Close it:
+
+    extern {
+        fn abs(x: int) int;
+    }
+
+When a closer is unmatched instead, the error points at the stray closer; remove
+it, or add the opener it was meant to close."
+            }
+            ErrorCode::UndeclaredVariable => {
+                "A variable was used before it was declared, or was never declared at all.
+
+Erroneous code example:
+
+    fn main() {
+        print(x);
+    }
+
+Declare the variable with `var` before using it:
+
+    fn main() {
+        var x: int = 1;
+        print(x);
+    }
+
+A variable cannot be used in its own initializer, so `var x: int = x;` is also
+an error."
+            }
+            ErrorCode::UnknownFunction => {
+                "A call names a function that is neither defined in the program, declared with
+`extern`, nor a builtin.
+
+Erroneous code example:
+
+    fn main() {
+        greet();
+    }
+
+Define the function, or declare it as an extern if it comes from C:
+
+    fn greet() {
+        print(\"hello\");
+    }
+
+    fn main() {
+        greet();
+    }"
+            }
+            ErrorCode::MismatchedTypes => {
+                "A variable was given a value of a different type than the one it was declared
+with.
+
+Erroneous code example:
+
+    fn main() {
+        var x: int = \"one\";
+    }
+
+Either change the declared type or the value:
+
+    fn main() {
+        var x: str = \"one\";
+    }
+
+The same applies to assignments: a variable keeps its declared type for its
+whole lifetime."
+            }
+            ErrorCode::WrongArgumentCount => {
+                "A function was called with more or fewer arguments than it takes.
+
+Erroneous code example:
+
+    fn add(a: int, b: int) {
+        print(a + b);
+    }
+
+    fn main() {
+        add(1);
+    }
+
+Pass exactly one argument for each parameter:
+
+    fn main() {
+        add(1, 2);
+    }"
+            }
+            ErrorCode::MismatchedArgument => {
+                "An argument has a different type than the parameter it is passed to.
+
+Erroneous code example:
+
+    fn square(n: int) {
+        print(n * n);
+    }
+
+    fn main() {
+        square(\"4\");
+    }
+
+Pass a value of the parameter's type:
+
+    fn main() {
+        square(4);
+    }"
+            }
+            ErrorCode::InvalidOperands => {
+                "An arithmetic or comparison operator was applied to values it does not support.
+
+Erroneous code example:
+
+    fn main() {
+        var x: int = 1 + true;
+    }
+
+Arithmetic operators take two ints. Comparisons take two ints; `==` and `!=`
+also take two bools. Strings cannot be added or compared yet."
+            }
+            ErrorCode::InvalidFormat => {
+                "A printf-style `print` call has a format string that does not match its
+arguments.
+
+Erroneous code example:
+
+    fn main() {
+        print(\"%d and %d\", 1);
+    }
+
+Each `%d` takes an int or bool and each `%s` takes a str. Write `%%` for a
+literal percent sign. Pass one argument per conversion:
+
+    fn main() {
+        print(\"%d and %d\", 1, 2);
+    }"
+            }
+            ErrorCode::MissingMain => {
+                "The program has no `main` function, so there is nothing to run.
+
+Erroneous code example:
+
+    fn greet() {
+        print(\"hello\");
+    }
+
+Add a `main` function; it is where execution starts:
+
+    fn main() {
+        print(\"hello\");
+    }
+
+When another function is named with `--entry`, execution starts there instead,
+and that function must exist."
+            }
+            ErrorCode::MainWithParameters => {
+                "`main` was declared with parameters.
+
+Erroneous code example:
+
+    fn main(argc: int) {
+    }
+
+`main` is called by the C runtime, which passes no arguments to it. Remove the
+parameters:
+
+    fn main() {
+    }
+
+The same goes for a function named with `--entry`."
+            }
+            ErrorCode::ConflictingDeclarations => {
+                "The same name was declared in two incompatible ways.
+
+Erroneous code example:
+
+    extern fn abs(x: int) int;
+    extern fn abs(x: int, y: int) int;
+
+An extern may be declared more than once, but every declaration must have the
+same signature. A function defined in the program also cannot share its name
+with an extern, and a constant cannot share its name with either.
+
+Some C functions, such as `printf` for `print`, are declared by the compiler
+itself. An extern for one of them must be compatible with that declaration:
+
+    extern fn printf(format: str, value: int);"
+            }
+            ErrorCode::DuplicateDefinition => {
+                "Two functions or two constants were defined with the same name.
+
+Erroneous code example:
+
+    fn greet() {
+        print(\"hello\");
+    }
+
+    fn greet() {
+        print(\"hi\");
+    }
+
+Every call must know which function it refers to, so function names must be
+unique. The same goes for constants. Rename or remove one of the definitions."
+            }
+            ErrorCode::MisusedAssignment => {
+                "'=' and '==' were mixed up.
+
+Erroneous code example:
+
+    fn main() {
+        var x: int = 5;
+        if x = 5 {
+            print(x);
+        }
+    }
+
+'=' assigns a value and can only be used as a statement of its own, while '=='
+compares two values. A condition needs a comparison:
+
+    if x == 5 {
+
+The reverse mistake is also reported: a declaration such as `var x: int == 5;`
+must use '=' to give the variable its value."
+            }
+            ErrorCode::ChainedComparison => {
+                "Two comparisons were chained together.
+
+Erroneous code example:
+
+    fn main() {
+        var x: int = 5;
+        if 0 < x < 10 {
+            print(x);
+        }
+    }
+
+Unlike in mathematics, this would compare `0` with the result of `x < 10`
+rather than checking that `x` is between the two, so it is rejected. Compare
+each pair separately, for example with nested conditions:
+
+    if 0 < x {
+        if x < 10 {
+            print(x);
+        }
+    }"
+            }
+            ErrorCode::UnknownCharacter => {
+                "The source contains a character that is not part of the language.
+
+Erroneous code example:
+
+    fn main() {
+        var price: int = $5;
+    }
+
+Outside of string literals, a program may only contain letters, digits,
+underscores, whitespace and the operators and punctuation of the language.
+Remove the character, or move it into a string if it is meant to be printed.
+
+There is no '!' operator on its own; '!' is only valid as part of '!='."
+            }
+            ErrorCode::LiteralOutOfRange => {
+                "An integer literal is too large to fit in an `int`.
+
+Erroneous code example:
+
+    fn main() {
+        var big: int = 99999999999999999999;
+    }
+
+An `int` is a signed 64-bit integer, so the largest value a literal can have is
+9223372036854775807. Use a smaller value."
+            }
+            ErrorCode::MissingSemicolon => {
+                "A statement is not followed by a ';'.
+
+Erroneous code example:
+
+    fn main() {
+        var x: int = 5
+        print(x);
+    }
+
+Variable declarations, assignments, `return` and expression statements such as
+calls must each end with a ';':
+
+    var x: int = 5;
+
+Statements that end with a block, such as `if` and `while`, need no ';'."
+            }
+            ErrorCode::ReturnAfterBody => {
+                "A `return` was written after the closing '}' of a function.
+
+Erroneous code example:
+
+    fn five() {
+        var x: int = 5;
+    } return x;
+
+Older versions of Ratio returned a value this way. A `return` is now an
+ordinary statement inside the function body:
+
+    fn five() {
+        var x: int = 5;
+        return x;
+    }
+
+A function that reaches the end of its body without a `return` returns 0."
+            }
+            ErrorCode::NonConstantExpression => {
+                "The value of a constant cannot be computed at compile time.
+
+Erroneous code example:
+
+    const LIMIT: int = read_limit();
+
+A constant's value is computed by the compiler, so it may only use literals,
+arithmetic, comparisons and constants declared before it:
+
+    const SECONDS_PER_DAY: int = 60 * 60 * 24;
+    const SECONDS_PER_WEEK: int = SECONDS_PER_DAY * 7;
+
+Use a variable for a value that is only known when the program runs."
+            }
+            ErrorCode::ConstantEvaluation => {
+                "Computing the value of a constant failed.
+
+Erroneous code example:
+
+    const SLOTS: int = 10 / 0;
+
+The arithmetic in a constant is carried out by the compiler, and an operation
+that would fail at runtime, such as dividing by zero or overflowing the range
+of `int`, is reported as an error instead."
+            }
+            ErrorCode::AssignmentToConstant => {
+                "A value was assigned to a constant.
+
+Erroneous code example:
+
+    const LIMIT: int = 10;
+
+    fn main() {
+        LIMIT = 20;
+    }
+
+A constant always has the value it was declared with. Declare a variable to
+hold a value that changes:
+
+    var limit: int = LIMIT;
+    limit = 20;"
+            }
+            ErrorCode::ImportCycle => {
+                "A file imports itself, directly or through the files it imports.
+
+Erroneous code example:
+
+    // a.ratio
+    import \"b.ratio\";
+
+    // b.ratio
+    import \"a.ratio\";
+
+Every name is visible in every file of a program, so a file never needs to import
+a file that imports it. Move what both files use into a third file that each of
+them imports, or drop one of the imports:
+
+    // a.ratio
+    import \"b.ratio\";
+
+    // b.ratio
+    import \"shared.ratio\";"
+            }
+            ErrorCode::UnresolvedImport => {
+                "An imported file could not be read.
+
+Erroneous code example:
+
+    import \"utils.ratio\";
+
+The path of an import is relative to the directory of the file it is written in,
+not to the directory the compiler is run from. Check that the file exists there:
+
+    import \"lib/utils.ratio\";"
+            }
+        }
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// A secondary location attached to a diagnostic, such as where something was first declared.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NoteKind {
+    /// Additional context about the problem.
+    Note,
+    /// A suggestion for fixing the problem.
+    Help,
+}
+
+impl Display for NoteKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            NoteKind::Note => write!(f, "note"),
+            NoteKind::Help => write!(f, "help"),
+        }
+    }
+}
+
+/// A free-form line printed after a diagnostic's labels.
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub kind: NoteKind,
+    pub message: String,
+}
+
+/// Writes the labels and then the notes of a diagnostic, one per line, after its message.
+pub fn write_details(f: &mut Formatter, labels: &[Label], notes: &[Note]) -> fmt::Result {
+    for label in labels {
+        write!(
+            f,
+            "\n --> {}:{}: {}",
+            label.span.start.line, label.span.start.column, label.message
+        )?;
+    }
+    for note in notes {
+        write!(f, "\n{}: {}", note.kind, note.message)?;
+    }
+    Ok(())
+}
+
+/// The candidate closest to `name`, if any is close enough to be a plausible typo.
+pub fn closest_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    // Short names allow one edit; longer ones allow roughly one edit per three characters.
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+const BOLD: &str = "\x1b[1m";
+const RED_BOLD: &str = "\x1b[1;31m";
+const YELLOW_BOLD: &str = "\x1b[1;33m";
+const BLUE_BOLD: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn style(&self) -> &'static str {
+        match self {
+            Severity::Error => RED_BOLD,
+            Severity::Warning => YELLOW_BOLD,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A source file being compiled, which diagnostics quote from.
+pub struct SourceFile {
+    /// The path it was read from, as diagnostics show it.
+    pub name: String,
+    pub text: String,
+}
+
+/// Renders diagnostics for the terminal, quoting the source line under each span.
+pub struct Renderer<'a> {
+    /// The files being compiled, indexed by `FileId`.
+    files: &'a [SourceFile],
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(files: &'a [SourceFile], color: bool) -> Self {
+        Self { files, color }
+    }
+
+    pub fn error(&self, error: &CompileError) -> String {
+        self.render(
+            Severity::Error,
+            error.code,
+            &error.message,
+            error.span,
+            &error.labels,
+            &error.notes,
+        )
+    }
+
+    /// Renders a warning as `severity`, which is `Error` for a lint made an error with `-W`. The
+    /// message ends with the option that controls it, such as `[-Wdead-code]`.
+    pub fn warning(&self, warning: &CompileWarning, severity: Severity) -> String {
+        let option = match severity {
+            Severity::Error => format!("-Werror={}", warning.lint),
+            Severity::Warning => format!("-W{}", warning.lint),
+        };
+        self.render(
+            severity,
+            None,
+            &format!("{} [{}]", warning.message, option),
+            warning.span,
+            &warning.labels,
+            &warning.notes,
+        )
+    }
+
+    /// The first line of a diagnostic, such as `error[R0003]: use of undeclared variable`.
+    pub fn header(&self, severity: Severity, code: Option<ErrorCode>, message: &str) -> String {
+        let prefix = match code {
+            Some(code) => format!("{}[{}]", severity, code),
+            None => severity.to_string(),
+        };
+        format!(
+            "{}{}",
+            self.paint(severity.style(), &prefix),
+            self.paint(BOLD, &format!(": {}", message))
+        )
+    }
+
+    fn render(
+        &self,
+        severity: Severity,
+        code: Option<ErrorCode>,
+        message: &str,
+        span: Span,
+        labels: &[Label],
+        notes: &[Note],
+    ) -> String {
+        let mut out = self.header(severity, code, message);
+        // Every snippet shares one gutter, wide enough for the largest line number shown.
+        let width = std::iter::once(span)
+            .chain(labels.iter().map(|label| label.span))
+            .map(|span| span.start.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let pad = " ".repeat(width);
+        out.push_str(&format!(
+            "\n{}{} {}",
+            pad,
+            self.paint(BLUE_BOLD, "-->"),
+            self.location(span)
+        ));
+        self.push_snippet(&mut out, width, span, '^', severity.style(), None);
+        for label in labels {
+            // A label in another file, such as an earlier definition, says which file it is in.
+            if label.span.file != span.file {
+                out.push_str(&format!(
+                    "\n{}{} {}",
+                    pad,
+                    self.paint(BLUE_BOLD, ":::"),
+                    self.location(label.span)
+                ));
+            }
+            self.push_snippet(
+                &mut out,
+                width,
+                label.span,
+                '-',
+                BLUE_BOLD,
+                Some(&label.message),
+            );
+        }
+        for note in notes {
+            out.push_str(&format!(
+                "\n{} {} {}: {}",
+                pad,
+                self.paint(BLUE_BOLD, "="),
+                self.paint(BOLD, &note.kind.to_string()),
+                note.message
+            ));
+        }
+        out
+    }
+
+    /// Quotes the line a span starts on and underlines the span with `marker`. A span that
+    /// continues onto later lines is underlined to the end of its first line.
+    fn push_snippet(
+        &self,
+        out: &mut String,
+        width: usize,
+        span: Span,
+        marker: char,
+        style: &str,
+        label: Option<&str>,
+    ) {
+        // Spans outside the source, such as the end of an empty file, have no line to quote.
+        let Some(line) = span.start.line.checked_sub(1).and_then(|index| {
+            let file = self.files.get(span.file.0)?;
+            file.text.lines().nth(index)
+        }) else {
+            return;
+        };
+        let pad = " ".repeat(width);
+        let gutter = self.paint(BLUE_BOLD, "|");
+        out.push_str(&format!("\n{} {}", pad, gutter));
+        out.push_str(&format!(
+            "\n{} {} {}",
+            self.paint(BLUE_BOLD, &format!("{:>width$}", span.start.line)),
+            gutter,
+            line
+        ));
+
+        // Tabs are kept in the indentation so that the underline lines up with the source.
+        let indent: String = line
+            .chars()
+            .take(span.start.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let end_column = if span.end.line == span.start.line {
+            span.end.column
+        } else {
+            line.chars().count() + 1
+        };
+        let mut underline = marker
+            .to_string()
+            .repeat(end_column.saturating_sub(span.start.column).max(1));
+        if let Some(label) = label {
+            underline.push(' ');
+            underline.push_str(label);
+        }
+        out.push_str(&format!(
+            "\n{} {} {}{}",
+            pad,
+            gutter,
+            indent,
+            self.paint(style, &underline)
+        ));
+    }
+
+    /// The `path:line:column` where `span` starts.
+    fn location(&self, span: Span) -> String {
+        let name = self
+            .files
+            .get(span.file.0)
+            .map_or("", |file| file.name.as_str());
+        format!("{}:{}:{}", name, span.start.line, span.start.column)
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt::Write;
+
+use crate::common::{Expr, Inline, Item, Program, Stmt, Type};
+
+const INDENT: &str = "  ";
+
+/// Formats the program as an indented tree, one node per line with its span at the end. Items
+/// appear in source order, and the format only changes when the syntax tree does, so the output
+/// can be compared against a saved copy.
+pub fn print_program(program: &Program) -> String {
+    let mut out = String::new();
+    for item in program.items() {
+        match item {
+            Item::Import(import) => {
+                writeln!(out, "import {:?} [{}]", import.path, import.span).unwrap();
+            }
+            Item::Function(function) => print_stmt(&mut out, function, 0),
+            Item::Extern(ext) => {
+                write!(out, "extern fn {}(", ext.name).unwrap();
+                print_params(&mut out, &ext.args);
+                out.push(')');
+                if ext.noreturn {
+                    out.push_str(" noreturn");
+                } else if ext.return_type != Type::Void {
+                    write!(out, " {}", ext.return_type).unwrap();
+                }
+                writeln!(out, " [{}]", ext.span).unwrap();
+            }
+            Item::Const(constant) => {
+                writeln!(
+                    out,
+                    "const {}: {} [{}]",
+                    constant.name, constant.ty, constant.span
+                )
+                .unwrap();
+                print_expr(&mut out, &constant.value, 1);
+            }
+        }
+    }
+    out
+}
+
+fn print_stmt(out: &mut String, stmt: &Stmt, depth: usize) {
+    let indent = INDENT.repeat(depth);
+    match stmt {
+        Stmt::Function {
+            name,
+            args,
+            body,
+            public,
+            inline,
+            span,
+        } => {
+            out.push_str(&indent);
+            if *public {
+                out.push_str("pub ");
+            }
+            match inline {
+                Inline::Default => {}
+                Inline::Always => out.push_str("inline "),
+                Inline::Never => out.push_str("noinline "),
+            }
+            write!(out, "fn {}(", name).unwrap();
+            print_params(out, args);
+            writeln!(out, ") [{}]", span).unwrap();
+            print_block(out, body, depth + 1);
+        }
+        Stmt::Return { value, span } => {
+            writeln!(out, "{}Return [{}]", indent, span).unwrap();
+            print_expr(out, value, depth + 1);
+        }
+        Stmt::VariableDecl {
+            name,
+            var_type,
+            value,
+            span,
+        } => {
+            writeln!(out, "{}Var {}: {} [{}]", indent, name, var_type, span).unwrap();
+            print_expr(out, value, depth + 1);
+        }
+        Stmt::Assignment { name, value, span } => {
+            writeln!(out, "{}Assign {} [{}]", indent, name, span).unwrap();
+            print_expr(out, value, depth + 1);
+        }
+        Stmt::IfStatement {
+            condition,
+            body,
+            else_body,
+            span,
+        } => {
+            writeln!(out, "{}If [{}]", indent, span).unwrap();
+            print_expr(out, condition, depth + 1);
+            writeln!(out, "{}{}Then", indent, INDENT).unwrap();
+            print_block(out, body, depth + 2);
+            if let Some(else_body) = else_body {
+                writeln!(out, "{}{}Else", indent, INDENT).unwrap();
+                print_block(out, else_body, depth + 2);
+            }
+        }
+        Stmt::While {
+            condition,
+            body,
+            span,
+        } => {
+            writeln!(out, "{}While [{}]", indent, span).unwrap();
+            print_expr(out, condition, depth + 1);
+            writeln!(out, "{}{}Do", indent, INDENT).unwrap();
+            print_block(out, body, depth + 2);
+        }
+        Stmt::ExprStmt { expr, span } => {
+            writeln!(out, "{}Expr [{}]", indent, span).unwrap();
+            print_expr(out, expr, depth + 1);
+        }
+    }
+}
+
+fn print_block(out: &mut String, stmts: &[Stmt], depth: usize) {
+    for stmt in stmts {
+        print_stmt(out, stmt, depth);
+    }
+}
+
+fn print_expr(out: &mut String, expr: &Expr, depth: usize) {
+    let indent = INDENT.repeat(depth);
+    match expr {
+        Expr::Call { callee, args, span } => {
+            writeln!(out, "{}Call {} [{}]", indent, callee, span).unwrap();
+            for arg in args {
+                print_expr(out, arg, depth + 1);
+            }
+        }
+        Expr::Variable { name, span } => {
+            writeln!(out, "{}Variable {} [{}]", indent, name, span).unwrap();
+        }
+        // Debug formatting quotes the string and escapes anything unprintable.
+        Expr::StringLiteral { value, span } => {
+            writeln!(out, "{}String {:?} [{}]", indent, value, span).unwrap();
+        }
+        Expr::IntegerLiteral { value, span } => {
+            writeln!(out, "{}Int {} [{}]", indent, value, span).unwrap();
+        }
+        Expr::BooleanLiteral { value, span } => {
+            writeln!(out, "{}Bool {} [{}]", indent, value, span).unwrap();
+        }
+        Expr::BinaryOperator {
+            operator,
+            left,
+            right,
+            span,
+        } => {
+            writeln!(out, "{}Binary '{}' [{}]", indent, operator, span).unwrap();
+            print_expr(out, left, depth + 1);
+            print_expr(out, right, depth + 1);
+        }
+        Expr::BooleanComparison {
+            lvalue,
+            operator,
+            rvalue,
+            span,
+            ..
+        } => {
+            // Token's Display already quotes the operator.
+            writeln!(out, "{}Compare {} [{}]", indent, operator, span).unwrap();
+            print_expr(out, lvalue, depth + 1);
+            print_expr(out, rvalue, depth + 1);
+        }
+    }
+}
+
+fn print_params(out: &mut String, params: &[(String, Type)]) {
+    for (i, (name, ty)) in params.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write!(out, "{}: {}", name, ty).unwrap();
+    }
+}
@@ -0,0 +1,8 @@
+use crate::common::Program;
+
+/// Formats the program as a JSON document. The schema comes from the `Serialize` derives on the
+/// syntax tree, and keeps every span and literal value, so tools can map nodes back to the
+/// source exactly.
+pub fn print_program(program: &Program) -> String {
+    serde_json::to_string_pretty(program).expect("the syntax tree serializes to JSON")
+}
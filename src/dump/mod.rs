@@ -0,0 +1,3 @@
+pub mod ast;
+pub mod json;
+pub mod tokens;
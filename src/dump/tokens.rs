@@ -0,0 +1,13 @@
+use std::fmt::Write;
+
+use crate::common::{Span, Token};
+
+/// Formats the token stream one token per line, as its span followed by the token, such as
+/// `3:5-3:9  Identifier("count")`.
+pub fn print_tokens(tokens: &[(Token, Span)]) -> String {
+    let mut out = String::new();
+    for (token, span) in tokens {
+        writeln!(out, "{}  {:?}", span, token).unwrap();
+    }
+    out
+}
@@ -1,11 +1,120 @@
-use std::fs::File;
 use std::io::Read;
 
-pub fn read_file(filepath: &std::path::PathBuf) -> std::io::Result<String> {
-    std::fs::read_to_string(filepath)
+/// Reads a text file. The error says in plain words why the file cannot be read: that it does
+/// not exist, may not be read or is a directory, or where its text stops being valid UTF-8.
+pub fn read_file(filepath: &std::path::Path) -> std::io::Result<String> {
+    // Opening a directory succeeds on some systems and is denied on others, so check first.
+    if filepath.is_dir() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::IsADirectory,
+            "it is a directory",
+        ));
+    }
+    let bytes = std::fs::read(filepath).map_err(describe_error)?;
+    utf8(bytes)
 }
 
-pub fn write_file(filepath: &str, contents: &str) -> std::io::Result<()> {
+/// Reads a source file, or standard input when the path is `-`.
+pub fn read_source(filepath: &std::path::Path) -> std::io::Result<String> {
+    if !is_stdin(filepath) {
+        return read_file(filepath);
+    }
+    let mut contents = Vec::new();
+    std::io::stdin().read_to_end(&mut contents)?;
+    utf8(contents)
+}
+
+fn utf8(bytes: Vec<u8>) -> std::io::Result<String> {
+    String::from_utf8(bytes).map_err(|error| {
+        let offset = error.utf8_error().valid_up_to();
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("it is not valid UTF-8 from byte {}", offset),
+        )
+    })
+}
+
+/// Replaces the operating system's message for the usual reasons a file cannot be opened, which
+/// ends in an error number, with a plain one.
+fn describe_error(error: std::io::Error) -> std::io::Error {
+    let message = match error.kind() {
+        std::io::ErrorKind::NotFound => "no such file exists",
+        std::io::ErrorKind::PermissionDenied => "permission to read it is denied",
+        _ => return error,
+    };
+    std::io::Error::new(error.kind(), message)
+}
+
+/// The name diagnostics give a source file: `<stdin>` for `-`, and the path otherwise.
+pub fn source_name(filepath: &std::path::Path) -> String {
+    if is_stdin(filepath) {
+        "<stdin>".to_string()
+    } else {
+        filepath.display().to_string()
+    }
+}
+
+pub fn is_stdin(filepath: &std::path::Path) -> bool {
+    filepath.as_os_str() == "-"
+}
+
+/// The temporary files and directories that still exist, so that they can be removed before
+/// exiting early.
+static TEMPORARIES: std::sync::Mutex<Vec<std::path::PathBuf>> = std::sync::Mutex::new(Vec::new());
+
+/// A temporary file or directory, which is removed when the guard is dropped or, since exiting
+/// skips destructors, by `remove_temporaries` before exiting on an error. Nothing needs to exist
+/// at the path yet.
+pub struct TempPath {
+    path: std::path::PathBuf,
+}
+
+impl TempPath {
+    pub fn new(path: std::path::PathBuf) -> Self {
+        TEMPORARIES.lock().unwrap().push(path.clone());
+        TempPath { path }
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    /// Keeps the file by renaming it to `destination`, replacing any file there in one step.
+    pub fn persist(self, destination: &std::path::Path) -> std::io::Result<()> {
+        std::fs::rename(&self.path, destination)
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        remove_path(&self.path);
+        TEMPORARIES
+            .lock()
+            .unwrap()
+            .retain(|path| *path != self.path);
+    }
+}
+
+/// Removes every temporary file and directory that is still guarded. Called before exiting on an
+/// error, after which the guards' destructors never run.
+pub fn remove_temporaries() {
+    // A panic while the list was locked leaves it poisoned, but its contents are still usable.
+    let temporaries = TEMPORARIES
+        .lock()
+        .unwrap_or_else(|error| error.into_inner());
+    for path in temporaries.iter() {
+        remove_path(path);
+    }
+}
+
+fn remove_path(path: &std::path::Path) {
+    let _ = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+}
+
+pub fn write_file(filepath: &std::path::Path, contents: &str) -> std::io::Result<()> {
     std::fs::write(filepath, contents)
-        .map_err(|e| std::io::Error::new(e.kind(), format!("Failed to write to file: {}", e)))
 }
@@ -0,0 +1,271 @@
+use std::fmt::Write;
+
+use crate::common::{Expr, ExternFunction, Inline, Item, Program, Stmt, Token, Type};
+
+const INDENT: &str = "    ";
+
+/// Prints the program back as source in the canonical style: four-space indentation, one
+/// statement per line, spaces around operators and `{` on the line that opens the block.
+/// Functions are separated by a blank line, while runs of imports, externs or constants stay
+/// together.
+///
+/// The output parses to the same syntax tree, apart from spans, and formatting it again changes
+/// nothing. Expressions need no parentheses for that, since the language has none: an operator's
+/// right operand is the rest of the expression, so writing the operands in order is enough.
+pub fn format_program(program: &Program) -> String {
+    let mut out = String::new();
+    let mut previous: Option<Item> = None;
+    for item in program.items() {
+        let grouped = matches!(
+            (previous, item),
+            (Some(Item::Import(_)), Item::Import(_))
+                | (Some(Item::Extern(_)), Item::Extern(_))
+                | (Some(Item::Const(_)), Item::Const(_))
+        );
+        if previous.is_some() && !grouped {
+            out.push('\n');
+        }
+        match item {
+            Item::Import(import) => writeln!(out, "import \"{}\";", import.path).unwrap(),
+            Item::Function(function) => format_stmt(&mut out, function, 0),
+            Item::Extern(ext) => format_extern(&mut out, ext, 0),
+            Item::Const(constant) => {
+                writeln!(
+                    out,
+                    "const {}: {} = {};",
+                    constant.name,
+                    constant.ty,
+                    format_expr(&constant.value)
+                )
+                .unwrap();
+            }
+        }
+        previous = Some(item);
+    }
+    out
+}
+
+fn format_stmt(out: &mut String, stmt: &Stmt, depth: usize) {
+    let indent = INDENT.repeat(depth);
+    match stmt {
+        Stmt::Function {
+            name,
+            args,
+            body,
+            public,
+            inline,
+            ..
+        } => {
+            out.push_str(&indent);
+            if *public {
+                out.push_str("pub ");
+            }
+            match inline {
+                Inline::Default => {}
+                Inline::Always => out.push_str("inline "),
+                Inline::Never => out.push_str("noinline "),
+            }
+            write!(out, "fn {}({}) ", name, format_params(args)).unwrap();
+            format_block(out, body, depth);
+            out.push('\n');
+        }
+        Stmt::Return { value, .. } => {
+            writeln!(out, "{}return {};", indent, format_expr(value)).unwrap();
+        }
+        Stmt::VariableDecl {
+            name,
+            var_type,
+            value,
+            ..
+        } => {
+            writeln!(
+                out,
+                "{}var {}: {} = {};",
+                indent,
+                name,
+                var_type,
+                format_expr(value)
+            )
+            .unwrap();
+        }
+        Stmt::Assignment { name, value, .. } => {
+            writeln!(out, "{}{} = {};", indent, name, format_expr(value)).unwrap();
+        }
+        Stmt::IfStatement { .. } => {
+            out.push_str(&indent);
+            format_if(out, stmt, depth);
+            out.push('\n');
+        }
+        Stmt::While {
+            condition, body, ..
+        } => {
+            write!(out, "{}while {} ", indent, format_expr(condition)).unwrap();
+            format_block(out, body, depth);
+            out.push('\n');
+        }
+        Stmt::ExprStmt { expr, .. } => {
+            writeln!(out, "{}{};", indent, format_expr(expr)).unwrap();
+        }
+    }
+}
+
+/// Writes an if statement from `if` to its final `}`. An else branch holding only another if
+/// statement is written as `else if`, which is how the parser reads one back.
+fn format_if(out: &mut String, stmt: &Stmt, depth: usize) {
+    let Stmt::IfStatement {
+        condition,
+        body,
+        else_body,
+        ..
+    } = stmt
+    else {
+        unreachable!("format_if is only called with if statements");
+    };
+    write!(out, "if {} ", format_expr(condition)).unwrap();
+    format_block(out, body, depth);
+    match else_body.as_deref() {
+        None => {}
+        Some([nested @ Stmt::IfStatement { .. }]) => {
+            out.push_str(" else ");
+            format_if(out, nested, depth);
+        }
+        Some(else_body) => {
+            out.push_str(" else ");
+            format_block(out, else_body, depth);
+        }
+    }
+}
+
+/// Writes a block from `{` to `}`, with its statements indented one level deeper than `depth`.
+fn format_block(out: &mut String, stmts: &[Stmt], depth: usize) {
+    if stmts.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+    out.push_str("{\n");
+    for stmt in stmts {
+        format_stmt(out, stmt, depth + 1);
+    }
+    write!(out, "{}}}", INDENT.repeat(depth)).unwrap();
+}
+
+fn format_extern(out: &mut String, ext: &ExternFunction, depth: usize) {
+    write!(
+        out,
+        "{}extern fn {}({})",
+        INDENT.repeat(depth),
+        ext.name,
+        format_params(&ext.args)
+    )
+    .unwrap();
+    if ext.noreturn {
+        out.push_str(" noreturn");
+    } else if ext.return_type != Type::Void {
+        write!(out, " {}", ext.return_type).unwrap();
+    }
+    out.push_str(";\n");
+}
+
+fn format_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Call { callee, args, .. } => {
+            let args: Vec<String> = args.iter().map(format_expr).collect();
+            format!("{}({})", callee, args.join(", "))
+        }
+        Expr::Variable { name, .. } => name.clone(),
+        // Strings have no escapes, so the contents are written as they are.
+        Expr::StringLiteral { value, .. } => format!("\"{}\"", value),
+        Expr::IntegerLiteral { value, .. } => value.to_string(),
+        Expr::BooleanLiteral { value, .. } => value.to_string(),
+        Expr::BinaryOperator {
+            operator,
+            left,
+            right,
+            ..
+        } => format!("{} {} {}", format_expr(left), operator, format_expr(right)),
+        Expr::BooleanComparison {
+            lvalue,
+            operator,
+            rvalue,
+            ..
+        } => format!(
+            "{} {} {}",
+            format_expr(lvalue),
+            comparison_operator(operator),
+            format_expr(rvalue)
+        ),
+    }
+}
+
+fn comparison_operator(token: &Token) -> &'static str {
+    match token {
+        Token::Equality => "==",
+        Token::NotEqual => "!=",
+        Token::LessThan => "<",
+        Token::LessThanOrEqual => "<=",
+        Token::GreaterThan => ">",
+        Token::GreaterThanOrEqual => ">=",
+        _ => unreachable!("comparisons only hold comparison operators"),
+    }
+}
+
+fn format_params(params: &[(String, Type)]) -> String {
+    let params: Vec<String> = params
+        .iter()
+        .map(|(name, ty)| format!("{}: {}", name, ty))
+        .collect();
+    params.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_program;
+    use crate::common::{FileId, Program};
+    use crate::dump::ast::print_program;
+    use crate::loader;
+
+    /// The programs kept in the repository, which between them use every kind of item,
+    /// statement and expression.
+    const SAMPLES: [(&str, &str); 3] = [
+        ("fib.ratio", include_str!("../fib.ratio")),
+        ("input.ratio", include_str!("../input.ratio")),
+        ("ast.ratio", include_str!("../tests/snapshots/ast.ratio")),
+    ];
+
+    fn parse(name: &str, source: &str) -> Program {
+        loader::lex(source, FileId(0))
+            .and_then(|tokens| loader::parse(tokens, FileId(0)))
+            .unwrap_or_else(|errors| panic!("{} does not parse: {:?}", name, errors))
+    }
+
+    /// The syntax tree as the AST dump shows it, without the span that ends each line.
+    fn tree(program: &Program) -> String {
+        print_program(program)
+            .lines()
+            .map(|line| match line.rfind(" [") {
+                Some(start) if line.ends_with(']') => &line[..start],
+                _ => line,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn formatting_keeps_the_syntax_tree() {
+        for (name, source) in SAMPLES {
+            let program = parse(name, source);
+            let formatted = format_program(&program);
+            let reparsed = parse(&format!("formatted {}", name), &formatted);
+            assert_eq!(tree(&reparsed), tree(&program), "{}:\n{}", name, formatted);
+        }
+    }
+
+    #[test]
+    fn formatting_twice_changes_nothing() {
+        for (name, source) in SAMPLES {
+            let once = format_program(&parse(name, source));
+            let twice = format_program(&parse(&format!("formatted {}", name), &once));
+            assert_eq!(twice, once, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt::Write;
+
+use crate::common::Type;
+use crate::hir::units::{Binding, Unit};
+use crate::hir::{Function, Program};
+
+/// Writes a C header declaring the functions the program exports, so that C code can call them.
+/// Only the functions exported from its object files are declared: `pub` ones and an entry point
+/// other than `main`. `guard` names the include guard.
+///
+/// Ratio's int and bool are both passed as 64-bit integers and str as a pointer to a
+/// NUL-terminated string, so they become `int64_t` and `const char *`.
+pub fn c_header(program: &Program, guard: &str) -> String {
+    let mut out = String::new();
+    writeln!(out, "/* Generated by ratio. Do not edit. */").unwrap();
+    writeln!(out, "#ifndef {}", guard).unwrap();
+    writeln!(out, "#define {}", guard).unwrap();
+    out.push_str("\n#include <stdint.h>\n");
+    out.push_str("\n#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+    for function in &program.functions {
+        if Unit::whole().binding(function) == Binding::Exported {
+            writeln!(out, "{};", prototype(function)).unwrap();
+        }
+    }
+    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n");
+    writeln!(out, "\n#endif /* {} */", guard).unwrap();
+    out
+}
+
+/// The include guard for a header written to a file named `file_name`, such as `RATIO_MATH_H` for
+/// `math.h`.
+pub fn guard_name(file_name: &str) -> String {
+    let stem = file_name.strip_suffix(".h").unwrap_or(file_name);
+    let stem: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("RATIO_{}_H", stem)
+}
+
+fn prototype(function: &Function) -> String {
+    let params: Vec<String> = function
+        .params
+        .iter()
+        .map(|param| {
+            let local = &function.locals[param.0];
+            declaration(local.ty, &local.name)
+        })
+        .collect();
+    let params = if params.is_empty() {
+        "void".to_string()
+    } else {
+        params.join(", ")
+    };
+    let declarator = format!("{}({})", function.name, params);
+    declaration(function.signature.return_type, &declarator)
+}
+
+/// Declares `declarator` with the C type for `ty`. A pointer's `*` is written against the name,
+/// as in `const char *name`.
+fn declaration(ty: Type, declarator: &str) -> String {
+    let c_type = c_type(ty);
+    if c_type.ends_with('*') {
+        format!("{}{}", c_type, declarator)
+    } else {
+        format!("{} {}", c_type, declarator)
+    }
+}
+
+fn c_type(ty: Type) -> &'static str {
+    match ty {
+        Type::Int | Type::Bool => "int64_t",
+        Type::Str => "const char *",
+        Type::Void => "void",
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use crate::{
+    common::{CompileError, Expr, Token},
+    diagnostics::ErrorCode,
+    hir::ConstValue,
+    semantics::conditions::compare,
+};
+
+/// Computes the value of a constant's initializer at compile time. `consts` holds the constants
+/// declared before it, which are the only names it may use. Problems are pushed to `errors`,
+/// except type errors, which semantic analysis reports; those just give `None`.
+pub fn evaluate(
+    expr: &Expr,
+    consts: &HashMap<&str, ConstValue>,
+    errors: &mut Vec<CompileError>,
+) -> Option<ConstValue> {
+    match expr {
+        Expr::IntegerLiteral { value, .. } => Some(ConstValue::Int(*value)),
+        Expr::BooleanLiteral { value, .. } => Some(ConstValue::Bool(*value)),
+        Expr::StringLiteral { value, .. } => Some(ConstValue::Str(value.clone())),
+        Expr::Variable { name, span } => {
+            let value = consts.get(name.as_str()).cloned();
+            if value.is_none() {
+                errors.push(
+                    CompileError::new(format!("`{}` is not a constant", name), *span)
+                        .with_code(ErrorCode::NonConstantExpression)
+                        .with_help("a constant can only use the constants declared before it"),
+                );
+            }
+            value
+        }
+        Expr::Call { callee, span, .. } => {
+            errors.push(
+                CompileError::new(format!("cannot call `{}` in a constant", callee), *span)
+                    .with_code(ErrorCode::NonConstantExpression)
+                    .with_note("the value of a constant is computed at compile time"),
+            );
+            None
+        }
+        Expr::BinaryOperator {
+            operator,
+            left,
+            right,
+            span,
+        } => {
+            let left = evaluate(left, consts, errors);
+            let right = evaluate(right, consts, errors);
+            let (Some(ConstValue::Int(left)), Some(ConstValue::Int(right))) = (left, right) else {
+                return None;
+            };
+            let result = match operator.as_str() {
+                "+" => left.checked_add(right),
+                "-" => left.checked_sub(right),
+                "*" => left.checked_mul(right),
+                "/" if right == 0 => {
+                    errors.push(
+                        CompileError::new("division by zero in a constant", *span)
+                            .with_code(ErrorCode::ConstantEvaluation),
+                    );
+                    return None;
+                }
+                "/" => left.checked_div(right),
+                _ => return None,
+            };
+            if result.is_none() {
+                errors.push(
+                    CompileError::new(
+                        format!("`{} {} {}` overflows int", left, operator, right),
+                        *span,
+                    )
+                    .with_code(ErrorCode::ConstantEvaluation)
+                    .with_note(format!(
+                        "an int ranges from {} to {}",
+                        i64::MIN,
+                        i64::MAX
+                    )),
+                );
+            }
+            result.map(ConstValue::Int)
+        }
+        Expr::BooleanComparison {
+            lvalue,
+            operator,
+            rvalue,
+            ..
+        } => {
+            let left = evaluate(lvalue, consts, errors);
+            let right = evaluate(rvalue, consts, errors);
+            match (left, right) {
+                (Some(ConstValue::Int(left)), Some(ConstValue::Int(right))) => {
+                    Some(ConstValue::Bool(compare(operator, &left, &right)))
+                }
+                (Some(ConstValue::Bool(left)), Some(ConstValue::Bool(right)))
+                    if matches!(operator, Token::Equality | Token::NotEqual) =>
+                {
+                    Some(ConstValue::Bool(compare(operator, &left, &right)))
+                }
+                _ => None,
+            }
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+
+use crate::{
+    common::{self, Builtin, CompileError, FUNCTION_RETURN_TYPE, Span, Type},
+    diagnostics::{ErrorCode, closest_match},
+    hir::{
+        Callee, Const, ConstId, ConstValue, Expr, ExprKind, Extern, Function, Local, Program,
+        Signature, Stmt, VarId, const_eval,
+    },
+};
+
+/// Lowers a parsed program into HIR, resolving every variable and callee. Names that cannot be
+/// resolved are reported as errors; type errors are left to semantic analysis, so lowering
+/// records whatever types the program has without judging them. `entry` is the function
+/// execution starts in.
+pub fn lower_program(program: &common::Program, entry: &str) -> Result<Program, Vec<CompileError>> {
+    let mut callees = HashMap::new();
+    let mut externs = Vec::new();
+    for ext in &program.externs {
+        if callees.contains_key(ext.name.as_str()) {
+            // Mismatched redeclarations were rejected during semantic analysis.
+            continue;
+        }
+        let signature = Signature {
+            params: ext.args.iter().map(|(_, t)| *t).collect(),
+            return_type: ext.return_type,
+            noreturn: ext.is_noreturn(),
+        };
+        callees.insert(
+            ext.name.as_str(),
+            Callee::Extern {
+                name: ext.name.clone(),
+                signature: signature.clone(),
+            },
+        );
+        externs.push(Extern {
+            name: ext.name.clone(),
+            signature,
+            span: ext.span,
+        });
+    }
+    for func in &program.functions {
+        if let common::Stmt::Function { name, args, .. } = func {
+            callees.insert(
+                name.as_str(),
+                Callee::Function {
+                    name: name.clone(),
+                    signature: function_signature(args),
+                },
+            );
+        }
+    }
+
+    let mut errors = Vec::new();
+    let mut consts = Vec::new();
+    let mut const_values = HashMap::new();
+    let mut const_decls = HashMap::new();
+    for decl in &program.consts {
+        if const_decls.contains_key(decl.name.as_str()) {
+            // Duplicate constants were rejected during semantic analysis.
+            continue;
+        }
+        // An initializer of the wrong type was also reported there; the declared type is what the
+        // rest of the program relies on.
+        let value = const_eval::evaluate(&decl.value, &const_values, &mut errors)
+            .filter(|value| value.ty() == decl.ty)
+            .unwrap_or_else(|| match decl.ty {
+                Type::Bool => ConstValue::Bool(false),
+                Type::Str => ConstValue::Str(String::new()),
+                _ => ConstValue::Int(0),
+            });
+        const_values.insert(decl.name.as_str(), value.clone());
+        const_decls.insert(decl.name.as_str(), (ConstId(consts.len()), decl));
+        consts.push(Const {
+            name: decl.name.clone(),
+            value,
+        });
+    }
+
+    let mut functions = Vec::new();
+    for func in &program.functions {
+        if let common::Stmt::Function {
+            name,
+            args,
+            body,
+            public,
+            #[cfg(feature = "llvm")]
+            inline,
+            span,
+            ..
+        } = func
+        {
+            let mut lowerer = FunctionLowerer {
+                callees: &callees,
+                consts: &const_decls,
+                locals: Vec::new(),
+                scope: HashMap::new(),
+                errors: &mut errors,
+            };
+            let params = args
+                .iter()
+                .map(|(arg_name, t)| lowerer.declare(arg_name, *t))
+                .collect();
+            let body = lowerer.lower_block(body);
+            functions.push(Function {
+                name: name.clone(),
+                signature: function_signature(args),
+                params,
+                locals: lowerer.locals,
+                body,
+                // A host program calls an entry point other than `main` by its name.
+                public: *public || (name == entry && entry != "main"),
+                #[cfg(feature = "llvm")]
+                inline: *inline,
+                span: *span,
+            });
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(Program {
+            functions,
+            externs,
+            consts,
+            entry: entry.to_string(),
+        })
+    } else {
+        Err(errors)
+    }
+}
+
+fn function_signature(args: &[(String, Type)]) -> Signature {
+    Signature {
+        params: args.iter().map(|(_, t)| *t).collect(),
+        return_type: FUNCTION_RETURN_TYPE,
+        noreturn: false,
+    }
+}
+
+struct FunctionLowerer<'a> {
+    callees: &'a HashMap<&'a str, Callee>,
+    consts: &'a HashMap<&'a str, (ConstId, &'a common::ConstDecl)>,
+    locals: Vec<Local>,
+    // Variables are function-scoped, matching the semantic checks, so a declaration stays
+    // visible after the block it appears in.
+    scope: HashMap<String, VarId>,
+    errors: &'a mut Vec<CompileError>,
+}
+
+impl<'a> FunctionLowerer<'a> {
+    fn declare(&mut self, name: &str, ty: Type) -> VarId {
+        let var = VarId(self.locals.len());
+        self.locals.push(Local {
+            name: name.to_string(),
+            ty,
+        });
+        self.scope.insert(name.to_string(), var);
+        var
+    }
+
+    fn resolve(&mut self, name: &str, span: Span) -> Option<VarId> {
+        let var = self.scope.get(name).copied();
+        if var.is_none() {
+            let mut error =
+                CompileError::new(format!("use of undeclared variable `{}`", name), span)
+                    .with_code(ErrorCode::UndeclaredVariable);
+            let candidates = self
+                .scope
+                .keys()
+                .map(String::as_str)
+                .chain(self.consts.keys().copied());
+            if let Some(similar) = closest_match(name, candidates) {
+                error = error.with_help(format!(
+                    "a variable with a similar name exists: `{}`",
+                    similar
+                ));
+            }
+            self.errors.push(error);
+        }
+        var
+    }
+
+    /// The constant `name` refers to, unless a variable of the same name shadows it.
+    fn constant(&self, name: &str) -> Option<(ConstId, &'a common::ConstDecl)> {
+        if self.scope.contains_key(name) {
+            return None;
+        }
+        self.consts.get(name).copied()
+    }
+
+    fn lower_block(&mut self, stmts: &[common::Stmt]) -> Vec<Stmt> {
+        stmts
+            .iter()
+            .filter_map(|stmt| self.lower_stmt(stmt))
+            .collect()
+    }
+
+    fn lower_stmt(&mut self, stmt: &common::Stmt) -> Option<Stmt> {
+        let span = stmt.span();
+        match stmt {
+            // Nested functions and externs are not part of the grammar.
+            common::Stmt::Function { .. } => None,
+            common::Stmt::Return { value, .. } => Some(Stmt::Return {
+                value: self.lower_expr(value),
+                span,
+            }),
+            common::Stmt::VariableDecl {
+                name,
+                var_type,
+                value,
+                ..
+            } => {
+                // The initializer is lowered first so that it cannot refer to the new variable.
+                let value = self.lower_expr(value);
+                let var = self.declare(name, *var_type);
+                Some(Stmt::VariableDecl { var, value, span })
+            }
+            common::Stmt::Assignment { name, value, .. } => {
+                let value = self.lower_expr(value);
+                if let Some((_, decl)) = self.constant(name) {
+                    self.errors.push(
+                        CompileError::new(format!("cannot assign to constant `{}`", name), span)
+                            .with_code(ErrorCode::AssignmentToConstant)
+                            .with_label(decl.span, "declared as a constant here")
+                            .with_help(
+                                "declare a variable with `var` to hold a value that changes",
+                            ),
+                    );
+                    return None;
+                }
+                let var = self.resolve(name, span)?;
+                Some(Stmt::Assignment { var, value, span })
+            }
+            common::Stmt::IfStatement {
+                condition,
+                body,
+                else_body,
+                ..
+            } => Some(Stmt::IfStatement {
+                condition: self.lower_expr(condition),
+                body: self.lower_block(body),
+                else_body: else_body.as_ref().map(|stmts| self.lower_block(stmts)),
+                span,
+            }),
+            common::Stmt::While {
+                condition, body, ..
+            } => Some(Stmt::While {
+                condition: self.lower_expr(condition),
+                body: self.lower_block(body),
+                span,
+            }),
+            common::Stmt::ExprStmt { expr, .. } => Some(Stmt::ExprStmt {
+                expr: self.lower_expr(expr),
+                span,
+            }),
+        }
+    }
+
+    /// Lowers an expression. Unresolved names are reported and replaced by an int zero, so that
+    /// lowering can carry on and find further errors.
+    fn lower_expr(&mut self, expr: &common::Expr) -> Expr {
+        let span = expr.span();
+        let (kind, ty) = match expr {
+            common::Expr::IntegerLiteral { value, .. } => {
+                (ExprKind::IntegerLiteral { value: *value }, Type::Int)
+            }
+            common::Expr::BooleanLiteral { value, .. } => {
+                (ExprKind::BooleanLiteral { value: *value }, Type::Bool)
+            }
+            common::Expr::StringLiteral { value, .. } => (
+                ExprKind::StringLiteral {
+                    value: value.clone(),
+                },
+                Type::Str,
+            ),
+            common::Expr::Variable { name, .. } => match self.constant(name) {
+                Some((id, decl)) => (ExprKind::Const { id }, decl.ty),
+                None => match self.resolve(name, span) {
+                    Some(var) => (ExprKind::Variable { var }, self.locals[var.0].ty),
+                    None => (ExprKind::IntegerLiteral { value: 0 }, Type::Int),
+                },
+            },
+            common::Expr::Call { callee, args, .. } => {
+                let args = args.iter().map(|arg| self.lower_expr(arg)).collect();
+                let resolved = match Builtin::from_name(callee) {
+                    Some(builtin) => Some(Callee::Builtin(builtin)),
+                    None => self.callees.get(callee.as_str()).cloned(),
+                };
+                match resolved {
+                    Some(resolved) => {
+                        let ty = match &resolved {
+                            Callee::Builtin(builtin) => builtin.signature().return_type,
+                            Callee::Function { signature, .. }
+                            | Callee::Extern { signature, .. } => signature.return_type,
+                        };
+                        (
+                            ExprKind::Call {
+                                callee: resolved,
+                                args,
+                            },
+                            ty,
+                        )
+                    }
+                    None => {
+                        let mut error =
+                            CompileError::new(format!("cannot find function `{}`", callee), span)
+                                .with_code(ErrorCode::UnknownFunction);
+                        let candidates = self
+                            .callees
+                            .keys()
+                            .copied()
+                            .chain(Builtin::ALL.iter().map(|builtin| builtin.name()));
+                        if let Some(similar) = closest_match(callee, candidates) {
+                            error = error.with_help(format!(
+                                "a function with a similar name exists: `{}`",
+                                similar
+                            ));
+                        }
+                        self.errors.push(error);
+                        (ExprKind::IntegerLiteral { value: 0 }, Type::Int)
+                    }
+                }
+            }
+            common::Expr::BinaryOperator {
+                operator,
+                left,
+                right,
+                ..
+            } => (
+                ExprKind::BinaryOperator {
+                    operator: operator.clone(),
+                    left: Box::new(self.lower_expr(left)),
+                    right: Box::new(self.lower_expr(right)),
+                },
+                Type::Int,
+            ),
+            common::Expr::BooleanComparison {
+                lvalue,
+                operator,
+                rvalue,
+                ..
+            } => (
+                ExprKind::BooleanComparison {
+                    lvalue: Box::new(self.lower_expr(lvalue)),
+                    operator: operator.clone(),
+                    rvalue: Box::new(self.lower_expr(rvalue)),
+                },
+                Type::Bool,
+            ),
+        };
+        Expr { kind, ty }
+    }
+}
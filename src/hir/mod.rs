@@ -0,0 +1,268 @@
+#[cfg(feature = "llvm")]
+use crate::common::Inline;
+use crate::common::{Builtin, Span, Token, Type};
+
+pub mod const_eval;
+pub mod lower;
+pub mod units;
+
+/// The symbol the user's `main` is emitted under, so that a C ABI `main` can wrap it.
+pub const USER_MAIN_SYMBOL: &str = "__ratio_main";
+
+/// A program after name resolution, in the form codegen consumes. Every expression carries its
+/// type, every variable is a `VarId`, and every call knows what it calls.
+#[derive(Debug)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    pub externs: Vec<Extern>,
+    /// Indexed by `ConstId`.
+    pub consts: Vec<Const>,
+    /// The function the C main calls: `main`, unless `--entry` names another. The C main goes
+    /// in the object that defines it.
+    pub entry: String,
+}
+
+impl Program {
+    /// The first statement that calls `builtin`, if any does.
+    pub fn first_call_to(&self, builtin: Builtin) -> Option<Span> {
+        self.functions
+            .iter()
+            .find_map(|function| first_call_in_block(&function.body, builtin))
+    }
+}
+
+fn first_call_in_block(stmts: &[Stmt], builtin: Builtin) -> Option<Span> {
+    stmts.iter().find_map(|stmt| match stmt {
+        Stmt::Return { value, .. }
+        | Stmt::VariableDecl { value, .. }
+        | Stmt::Assignment { value, .. }
+        | Stmt::ExprStmt { expr: value, .. } => calls(value, builtin).then(|| stmt.span()),
+        Stmt::IfStatement {
+            condition,
+            body,
+            else_body,
+            span,
+        } => calls(condition, builtin)
+            .then_some(*span)
+            .or_else(|| first_call_in_block(body, builtin))
+            .or_else(|| first_call_in_block(else_body.as_deref()?, builtin)),
+        Stmt::While {
+            condition,
+            body,
+            span,
+        } => calls(condition, builtin)
+            .then_some(*span)
+            .or_else(|| first_call_in_block(body, builtin)),
+    })
+}
+
+/// Whether `expr` calls `builtin` anywhere within it.
+fn calls(expr: &Expr, builtin: Builtin) -> bool {
+    match &expr.kind {
+        ExprKind::Call { callee, args } => {
+            matches!(callee, Callee::Builtin(called) if *called == builtin)
+                || args.iter().any(|arg| calls(arg, builtin))
+        }
+        ExprKind::BinaryOperator { left, right, .. } => {
+            calls(left, builtin) || calls(right, builtin)
+        }
+        ExprKind::BooleanComparison { lvalue, rvalue, .. } => {
+            calls(lvalue, builtin) || calls(rvalue, builtin)
+        }
+        ExprKind::Variable { .. }
+        | ExprKind::Const { .. }
+        | ExprKind::StringLiteral { .. }
+        | ExprKind::IntegerLiteral { .. }
+        | ExprKind::BooleanLiteral { .. } => false,
+    }
+}
+
+/// A `const` declaration, whose value was computed during lowering.
+#[derive(Debug)]
+pub struct Const {
+    pub name: String,
+    pub value: ConstValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstValue {
+    Int(i64),
+    Bool(bool),
+    Str(String),
+}
+
+impl ConstValue {
+    pub fn ty(&self) -> Type {
+        match self {
+            ConstValue::Int(_) => Type::Int,
+            ConstValue::Bool(_) => Type::Bool,
+            ConstValue::Str(_) => Type::Str,
+        }
+    }
+}
+
+/// Identifies a constant within the program.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ConstId(pub usize);
+
+/// An extern declaration. Identical redeclarations are merged during lowering.
+#[derive(Debug)]
+pub struct Extern {
+    pub name: String,
+    pub signature: Signature,
+    /// The first declaration in the source.
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub signature: Signature,
+    /// The parameters, in order. They are also the first entries of `locals`.
+    pub params: Vec<VarId>,
+    /// Every variable in the function, indexed by `VarId`.
+    pub locals: Vec<Local>,
+    /// Falling off the end of the body returns 0.
+    pub body: Vec<Stmt>,
+    /// Declared with `pub`, or the entry point when it is not `main`, so it is exported from the
+    /// object file under its own name.
+    pub public: bool,
+    /// Only the LLVM backend can be asked to inline a function or not.
+    #[cfg(feature = "llvm")]
+    pub inline: Inline,
+    /// The function's signature in the source.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub params: Vec<Type>,
+    pub return_type: Type,
+    /// Calls never return to the caller.
+    pub noreturn: bool,
+}
+
+/// Identifies a variable within its function. Each `var` declaration gets a fresh id, so a
+/// redeclaration shadows the earlier variable instead of reusing its storage.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct VarId(pub usize);
+
+#[derive(Debug)]
+pub struct Local {
+    pub name: String,
+    pub ty: Type,
+}
+
+#[derive(Debug)]
+pub enum Stmt {
+    Return {
+        value: Expr,
+        span: Span,
+    },
+    VariableDecl {
+        var: VarId,
+        value: Expr,
+        span: Span,
+    },
+    Assignment {
+        var: VarId,
+        value: Expr,
+        span: Span,
+    },
+    IfStatement {
+        condition: Expr,
+        body: Vec<Stmt>,
+        else_body: Option<Vec<Stmt>>,
+        span: Span,
+    },
+    While {
+        condition: Expr,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    #[allow(clippy::enum_variant_names)]
+    ExprStmt {
+        expr: Expr,
+        span: Span,
+    },
+}
+
+impl Stmt {
+    /// The source covered by the statement.
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Return { span, .. }
+            | Stmt::VariableDecl { span, .. }
+            | Stmt::Assignment { span, .. }
+            | Stmt::IfStatement { span, .. }
+            | Stmt::While { span, .. }
+            | Stmt::ExprStmt { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub ty: Type,
+}
+
+#[derive(Debug)]
+pub enum ExprKind {
+    Call {
+        callee: Callee,
+        args: Vec<Expr>,
+    },
+    Variable {
+        var: VarId,
+    },
+    Const {
+        id: ConstId,
+    },
+    StringLiteral {
+        value: String,
+    },
+    IntegerLiteral {
+        value: i64,
+    },
+    BooleanLiteral {
+        value: bool,
+    },
+    BinaryOperator {
+        operator: String,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    BooleanComparison {
+        lvalue: Box<Expr>,
+        operator: Token,
+        rvalue: Box<Expr>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum Callee {
+    Builtin(Builtin),
+    /// A function defined in the program.
+    Function {
+        name: String,
+        signature: Signature,
+    },
+    Extern {
+        name: String,
+        signature: Signature,
+    },
+}
+
+impl Callee {
+    /// Whether calls never return, such as `exit(1)`. Such a call ends the block wherever it
+    /// appears, so the enclosing if or loop does not branch on from it.
+    pub fn is_noreturn(&self) -> bool {
+        match self {
+            Callee::Builtin(_) => false,
+            Callee::Function { signature, .. } | Callee::Extern { signature, .. } => {
+                signature.noreturn
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeSet;
+
+use crate::common::FileId;
+use crate::hir::{Callee, Expr, ExprKind, Function, Program, Stmt};
+
+/// The part of a program that one object file holds. A program compiled separately has a unit
+/// for each source file that defines functions, and its objects are then linked together.
+pub struct Unit {
+    /// The file whose functions the object defines, or `None` for every function.
+    file: Option<FileId>,
+    /// The functions called from a file other than their own, which the linker must see.
+    called_across_files: BTreeSet<String>,
+}
+
+/// How an object file refers to one of the program's functions.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Binding {
+    /// Defined here and only called from here.
+    Local,
+    /// Defined here and visible to the linker.
+    Exported,
+    /// Defined in another object, which the linker supplies.
+    Imported,
+}
+
+impl Unit {
+    /// The whole program in a single object.
+    pub fn whole() -> Self {
+        Unit {
+            file: None,
+            called_across_files: BTreeSet::new(),
+        }
+    }
+
+    /// The functions defined in `file`.
+    pub fn file(program: &Program, file: FileId) -> Self {
+        Unit {
+            file: Some(file),
+            called_across_files: called_across_files(program),
+        }
+    }
+
+    /// The file whose functions the object defines, if it holds only one file's.
+    #[cfg(feature = "llvm")]
+    pub fn file_id(&self) -> Option<FileId> {
+        self.file
+    }
+
+    /// Only `pub` functions and the C main are exported from an object holding every function.
+    /// With one object per file, a function called from another file is exported as well.
+    pub fn binding(&self, function: &Function) -> Binding {
+        if self.file.is_some_and(|file| file != function.span.file) {
+            Binding::Imported
+        } else if (function.public && function.name != "main")
+            || self.called_across_files.contains(&function.name)
+        {
+            Binding::Exported
+        } else {
+            Binding::Local
+        }
+    }
+}
+
+/// The files that define at least one function, in order. Each is a unit of its own when the
+/// program is compiled separately.
+pub fn files(program: &Program) -> Vec<FileId> {
+    let files: BTreeSet<FileId> = program
+        .functions
+        .iter()
+        .map(|function| function.span.file)
+        .collect();
+    files.into_iter().collect()
+}
+
+/// Everything an object file depends on besides the text of its own source file: the signature
+/// and binding of every function, and every extern and constant. A file's object only needs to
+/// be generated again when its text or this changes.
+pub fn interface(program: &Program) -> String {
+    let functions: Vec<_> = program
+        .functions
+        .iter()
+        .map(|function| (&function.name, &function.signature, function.public))
+        .collect();
+    let externs: Vec<_> = program
+        .externs
+        .iter()
+        .map(|ext| (&ext.name, &ext.signature))
+        .collect();
+    let consts: Vec<_> = program
+        .consts
+        .iter()
+        .map(|constant| (&constant.name, &constant.value))
+        .collect();
+    format!(
+        "{:?}",
+        (functions, externs, consts, called_across_files(program))
+    )
+}
+
+fn called_across_files(program: &Program) -> BTreeSet<String> {
+    let mut called = BTreeSet::new();
+    for function in &program.functions {
+        let mut callees = Vec::new();
+        calls_in_block(&function.body, &mut callees);
+        for callee in callees {
+            let defined_elsewhere = program
+                .functions
+                .iter()
+                .any(|other| other.name == callee && other.span.file != function.span.file);
+            if defined_elsewhere {
+                called.insert(callee.to_string());
+            }
+        }
+    }
+    called
+}
+
+/// Adds the name of every function of the program called in `stmts` to `calls`.
+fn calls_in_block<'a>(stmts: &'a [Stmt], calls: &mut Vec<&'a str>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Return { value, .. }
+            | Stmt::VariableDecl { value, .. }
+            | Stmt::Assignment { value, .. } => calls_in_expr(value, calls),
+            Stmt::IfStatement {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                calls_in_expr(condition, calls);
+                calls_in_block(body, calls);
+                if let Some(else_body) = else_body {
+                    calls_in_block(else_body, calls);
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                calls_in_expr(condition, calls);
+                calls_in_block(body, calls);
+            }
+            Stmt::ExprStmt { expr, .. } => calls_in_expr(expr, calls),
+        }
+    }
+}
+
+fn calls_in_expr<'a>(expr: &'a Expr, calls: &mut Vec<&'a str>) {
+    match &expr.kind {
+        ExprKind::Call { callee, args } => {
+            if let Callee::Function { name, .. } = callee {
+                calls.push(name);
+            }
+            for arg in args {
+                calls_in_expr(arg, calls);
+            }
+        }
+        ExprKind::BinaryOperator { left, right, .. } => {
+            calls_in_expr(left, calls);
+            calls_in_expr(right, calls);
+        }
+        ExprKind::BooleanComparison { lvalue, rvalue, .. } => {
+            calls_in_expr(lvalue, calls);
+            calls_in_expr(rvalue, calls);
+        }
+        ExprKind::Variable { .. }
+        | ExprKind::Const { .. }
+        | ExprKind::StringLiteral { .. }
+        | ExprKind::IntegerLiteral { .. }
+        | ExprKind::BooleanLiteral { .. } => {}
+    }
+}
@@ -0,0 +1,52 @@
+use std::cell::RefCell;
+
+/// The exit status after an internal compiler error, distinct from the 1 used when the program
+/// being compiled has errors. It is `EX_SOFTWARE` from sysexits.h.
+pub const EXIT_STATUS: i32 = 70;
+
+thread_local! {
+    /// What the compiler is working on, such as "compiling function `main` at 3:1".
+    static CONTEXT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Records what the compiler is working on, for the report if it panics.
+pub fn set_context(context: String) {
+    CONTEXT.with(|current| *current.borrow_mut() = Some(context));
+}
+
+pub fn clear_context() {
+    CONTEXT.with(|current| *current.borrow_mut() = None);
+}
+
+/// Replaces the default panic output with an internal compiler error report, and exits with
+/// `EXIT_STATUS` instead of unwinding. A panic is always a bug in the compiler rather than in the
+/// program being compiled, so the report asks for it to be filed.
+pub fn install_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.as_str()
+        } else {
+            "unknown panic"
+        };
+        let context = CONTEXT
+            .with(|current| current.borrow().clone())
+            .map(|context| format!(" while {}", context))
+            .unwrap_or_default();
+        eprintln!(
+            "internal compiler error{}: {}; please report this",
+            context, message
+        );
+        eprintln!("note: ratio {}", env!("CARGO_PKG_VERSION"));
+        if let Some(location) = info.location() {
+            eprintln!("note: panicked at {}", location);
+        }
+        if std::env::var("RUST_BACKTRACE").is_ok_and(|value| value != "0") {
+            eprintln!("{}", std::backtrace::Backtrace::force_capture());
+        }
+        crate::file_io::remove_temporaries();
+        std::process::exit(EXIT_STATUS);
+    }));
+}
@@ -1,27 +1,52 @@
-use crate::common::{Builtin, Keyword, Position, Token};
+use crate::common::{Builtin, CompileError, FileId, Keyword, Position, Span, Token};
+use crate::diagnostics::ErrorCode;
 
 pub struct Lexer<'a> {
     input: &'a str,
+    /// The file `input` is the text of, which every span is in.
+    file: FileId,
     position: usize,
     current_pos: Position,
     start_pos: Position,
+    /// The text after the `//` of each comment skipped so far.
+    comments: Vec<String>,
+}
+
+/// Lexes the whole input only for its comments, returning the text after each `//` in source
+/// order. Characters that cannot start a token are skipped, so this also works on a file with
+/// errors.
+pub fn scan_comments(input: &str) -> Vec<String> {
+    let mut lexer = Lexer::new(input, FileId(0));
+    while !matches!(lexer.next_token(), Ok((Token::EOF, _))) {}
+    lexer.comments
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(input: &'a str) -> Self {
+    pub fn new(input: &'a str, file: FileId) -> Self {
+        // A `#!` line at the very start lets a source file be marked executable. Only the line's
+        // text is skipped; its newline is lexed as usual, so later lines keep their numbers.
+        let position = if input.starts_with("#!") {
+            input.find('\n').unwrap_or(input.len())
+        } else {
+            0
+        };
         Lexer {
             input,
-            position: 0,
+            file,
+            position,
             current_pos: Position::new(1, 1),
             start_pos: Position::new(1, 1),
+            comments: Vec::new(),
         }
     }
 
-    pub fn next_token(&mut self) -> (Token, Position) {
+    /// Returns the next token, or an error for a character that cannot start one. The offending
+    /// character is skipped, so lexing can carry on after an error.
+    pub fn next_token(&mut self) -> Result<(Token, Span), CompileError> {
         if self.position >= self.input.len() {
-            return (Token::EOF, self.start_pos.clone());
+            return Ok((Token::EOF, Span::at(self.file, self.current_pos)));
         }
-        self.start_pos = self.current_pos.clone();
+        self.start_pos = self.current_pos;
         let current = self.current_char();
 
         // Skip newline
@@ -36,10 +61,15 @@ impl<'a> Lexer<'a> {
             return self.next_token();
         }
 
-        self.start_pos = self.current_pos.clone();
+        if current == '/' && self.peek() == '/' {
+            self.skip_comment();
+            return self.next_token();
+        }
+
+        self.start_pos = self.current_pos;
 
         if self.position >= self.input.len() {
-            return (Token::EOF, self.start_pos);
+            return Ok((Token::EOF, Span::at(self.file, self.current_pos)));
         }
 
         let current = self.current_char();
@@ -81,8 +111,10 @@ impl<'a> Lexer<'a> {
                     self.advance();
                     Token::NotEqual
                 } else {
-                    // TODO
-                    panic!("Unary operators are not yet supported");
+                    self.advance();
+                    return Err(self.unknown_character(current).with_help(
+                        "'!' is only valid as part of '!='; there is no unary '!' yet",
+                    ));
                 }
             }
             '+' => self.consume_simple(Token::Plus),
@@ -94,24 +126,54 @@ impl<'a> Lexer<'a> {
             '}' => self.consume_simple(Token::RightBrace),
             ';' => self.consume_simple(Token::Semicolon),
             _ if current.is_alphabetic() || current == '_' => self.consume_word(),
-            _ if current.is_digit(10) => self.consume_number(),
-            _ => panic!(
-                "Unexpected character at {}:{}",
-                self.current_pos.line, self.current_pos.column
-            ),
+            _ if current.is_ascii_digit() => self.consume_number()?,
+            _ => {
+                self.advance();
+                return Err(self.unknown_character(current));
+            }
         };
 
-        (token, self.start_pos)
+        Ok((
+            token,
+            Span::new(self.file, self.start_pos, self.current_pos),
+        ))
+    }
+
+    fn unknown_character(&self, character: char) -> CompileError {
+        CompileError::new(
+            format!("unknown character '{}'", character.escape_debug()),
+            Span::new(self.file, self.start_pos, self.current_pos),
+        )
+        .with_code(ErrorCode::UnknownCharacter)
     }
 
-    fn consume_number(&mut self) -> Token {
+    fn consume_number(&mut self) -> Result<Token, CompileError> {
         let start = self.position;
-        while self.position < self.input.len() && self.current_char().is_digit(10) {
+        while self.position < self.input.len() && self.current_char().is_ascii_digit() {
             self.advance();
         }
 
         let num_str = &self.input[start..self.position];
-        Token::NumberLiteral(num_str.parse().unwrap())
+        match num_str.parse() {
+            Ok(value) => Ok(Token::NumberLiteral(value)),
+            Err(_) => {
+                let mut error = CompileError::new(
+                    "integer literal too large for i64",
+                    Span::new(self.file, self.start_pos, self.current_pos),
+                )
+                .with_code(ErrorCode::LiteralOutOfRange)
+                .with_note(format!("the largest integer literal is {}", i64::MAX));
+                // `i64::MIN` has no positive counterpart, so it can only be written by negating
+                // this literal, and there is no unary minus yet.
+                if num_str.trim_start_matches('0') == i64::MIN.unsigned_abs().to_string() {
+                    error = error.with_note(format!(
+                        "{} cannot be written as a literal, as there is no unary '-' yet",
+                        i64::MIN
+                    ));
+                }
+                Err(error)
+            }
+        }
     }
 
     fn consume_word(&mut self) -> Token {
@@ -126,18 +188,37 @@ impl<'a> Lexer<'a> {
         match word {
             "fn" => Token::Keyword(Keyword::Fn),
             "extern" => Token::Keyword(Keyword::Extern),
+            "pub" => Token::Keyword(Keyword::Pub),
+            "inline" => Token::Keyword(Keyword::Inline),
+            "noinline" => Token::Keyword(Keyword::Noinline),
+            "const" => Token::Keyword(Keyword::Const),
+            "import" => Token::Keyword(Keyword::Import),
             "var" => Token::Keyword(Keyword::Var),
             "int" => Token::Keyword(Keyword::Int),
             "bool" => Token::Keyword(Keyword::Bool),
+            "str" => Token::Keyword(Keyword::Str),
             "true" => Token::Keyword(Keyword::True),
             "false" => Token::Keyword(Keyword::False),
             "return" => Token::Keyword(Keyword::Return),
-            "print" => Token::Builtin(Builtin::Print),
-            "input" => Token::Builtin(Builtin::Input),
             "if" => Token::Keyword(Keyword::If),
+            "else" => Token::Keyword(Keyword::Else),
             "while" => Token::Keyword(Keyword::While),
-            _ => Token::Identifier(word.to_string()),
+            "noreturn" => Token::Keyword(Keyword::Noreturn),
+            _ => match Builtin::from_name(word) {
+                Some(builtin) => Token::Builtin(builtin),
+                None => Token::Identifier(word.to_string()),
+            },
+        }
+    }
+
+    /// Skips a `//` comment up to, but not including, the newline that ends it.
+    fn skip_comment(&mut self) {
+        let start = self.position + "//".len();
+        while self.position < self.input.len() && self.current_char() != '\n' {
+            self.advance();
         }
+        let text = self.input[start..self.position].trim_end_matches('\r');
+        self.comments.push(text.to_string());
     }
 
     fn consume_string(&mut self) -> Token {
@@ -157,7 +238,7 @@ impl<'a> Lexer<'a> {
     }
 
     fn current_char(&self) -> char {
-        self.input.chars().nth(self.position).unwrap()
+        self.input[self.position..].chars().next().unwrap()
     }
 
     fn advance(&mut self) {
@@ -167,7 +248,8 @@ impl<'a> Lexer<'a> {
         } else {
             self.current_pos.column += 1;
         }
-        self.position += 1;
+        // `position` is a byte offset, so that slicing `input` stays on character boundaries.
+        self.position += self.current_char().len_utf8();
     }
 
     fn consume_simple(&mut self, token: Token) -> Token {
@@ -176,11 +258,116 @@ impl<'a> Lexer<'a> {
     }
 
     fn peek(&self) -> char {
-        if self.position + 1 < self.input.len() {
-            self.input.chars().nth(self.position + 1).unwrap()
-        } else {
-            // TODO: better handling
-            '\0'
-        }
+        self.input[self.position..].chars().nth(1).unwrap_or('\0')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{FileId, Keyword, Token};
+    use crate::loader;
+
+    /// Each token of `input` with its span, as `line:column-line:column`.
+    fn spans(input: &str) -> Vec<(Token, String)> {
+        loader::lex(input, FileId(0))
+            .expect("lexes")
+            .into_iter()
+            .map(|(token, span)| (token, span.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn spans_cover_the_whole_token() {
+        assert_eq!(
+            spans("return 100 >= \"hi\""),
+            [
+                (Token::Keyword(Keyword::Return), "1:1-1:7".to_string()),
+                (Token::NumberLiteral(100), "1:8-1:11".to_string()),
+                (Token::GreaterThanOrEqual, "1:12-1:14".to_string()),
+                (
+                    Token::StringLiteral("hi".to_string()),
+                    "1:15-1:19".to_string()
+                ),
+                (Token::EOF, "1:19-1:19".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn leading_blank_lines_are_counted() {
+        assert_eq!(
+            spans("\n\n  \nx")[0],
+            (Token::Identifier("x".to_string()), "4:1-4:2".to_string())
+        );
+    }
+
+    #[test]
+    fn a_shebang_line_keeps_the_line_numbers() {
+        assert_eq!(
+            spans("#!/usr/bin/env ratio\nx")[0],
+            (Token::Identifier("x".to_string()), "2:1-2:2".to_string())
+        );
+    }
+
+    #[test]
+    fn windows_line_endings_count_one_line_each() {
+        assert_eq!(
+            spans("a\r\n\r\nb")[1],
+            (Token::Identifier("b".to_string()), "3:1-3:2".to_string())
+        );
+    }
+
+    /// Each lexing error in `input` with its span, as `line:column-line:column`.
+    fn errors(input: &str) -> Vec<(String, String)> {
+        loader::lex(input, FileId(0))
+            .expect_err("fails to lex")
+            .into_iter()
+            .map(|error| (error.message, error.span.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn unknown_characters_are_errors() {
+        assert_eq!(
+            errors("x @ y\n# $ != !"),
+            [
+                ("unknown character '@'".to_string(), "1:3-1:4".to_string()),
+                ("unknown character '#'".to_string(), "2:1-2:2".to_string()),
+                ("unknown character '$'".to_string(), "2:3-2:4".to_string()),
+                ("unknown character '!'".to_string(), "2:8-2:9".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn integer_literals_past_i64_are_errors() {
+        assert_eq!(
+            spans("9223372036854775807")[0],
+            (Token::NumberLiteral(i64::MAX), "1:1-1:20".to_string())
+        );
+        assert_eq!(
+            errors("x 99999999999999999999"),
+            [(
+                "integer literal too large for i64".to_string(),
+                "1:3-1:23".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn the_magnitude_of_i64_min_explains_why_it_is_rejected() {
+        let error = loader::lex("9223372036854775808", FileId(0)).unwrap_err();
+        let notes: Vec<&str> = error[0]
+            .notes
+            .iter()
+            .map(|note| note.message.as_str())
+            .collect();
+        assert_eq!(
+            notes,
+            [
+                "the largest integer literal is 9223372036854775807",
+                "-9223372036854775808 cannot be written as a literal, as there is no unary '-' yet",
+            ]
+        );
     }
 }
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// A kind of warning, with a stable name such as `dead-code` that `-W` uses to turn it on or off
+/// or make it an error. Every warning belongs to a lint, so a new kind of warning only needs a
+/// variant here to be controllable.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Lint {
+    DeadCode,
+    UnusedExtern,
+    UnreachableCode,
+    ConstantCondition,
+    NonLiteralFormat,
+}
+
+impl Lint {
+    /// Every lint, in the order `-W` lists them.
+    pub const ALL: [Lint; 5] = [
+        Lint::DeadCode,
+        Lint::UnusedExtern,
+        Lint::UnreachableCode,
+        Lint::ConstantCondition,
+        Lint::NonLiteralFormat,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::DeadCode => "dead-code",
+            Lint::UnusedExtern => "unused-extern",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::ConstantCondition => "constant-condition",
+            Lint::NonLiteralFormat => "non-literal-format",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// How the warnings of a lint are reported.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Level {
+    /// Not reported at all.
+    Allow,
+    /// Reported as a warning, which does not stop the program from compiling.
+    Warn,
+    /// Reported as an error, which does.
+    Deny,
+}
+
+/// The level of every lint, as the `-W` and `-w` options set it.
+pub struct LintLevels {
+    /// Indexed by `Lint`.
+    levels: [Level; Lint::ALL.len()],
+    /// Whether `-Werror` makes every lint that warns an error.
+    all_errors: bool,
+}
+
+impl Default for LintLevels {
+    /// Every lint warns, as it does without any options.
+    fn default() -> Self {
+        LintLevels {
+            levels: [Level::Warn; Lint::ALL.len()],
+            all_errors: false,
+        }
+    }
+}
+
+impl LintLevels {
+    /// Applies one `-W` option: `NAME` turns a lint on and `no-NAME` turns it off, `error`
+    /// makes every lint that is on an error and `error=NAME` makes one lint an error, and
+    /// `no-error` and `no-error=NAME` undo those. A later option overrides an earlier one.
+    /// Returns the unknown lint name if the option names one.
+    pub fn apply(&mut self, option: &str) -> Result<(), String> {
+        match option {
+            "error" => self.all_errors = true,
+            "no-error" => self.all_errors = false,
+            _ => {
+                let (name, level) = if let Some(name) = option.strip_prefix("error=") {
+                    (name, Level::Deny)
+                } else if let Some(name) = option.strip_prefix("no-error=") {
+                    (name, Level::Warn)
+                } else if let Some(name) = option.strip_prefix("no-") {
+                    (name, Level::Allow)
+                } else {
+                    (option, Level::Warn)
+                };
+                let lint = Lint::from_name(name).ok_or_else(|| name.to_string())?;
+                self.levels[lint as usize] = level;
+            }
+        }
+        Ok(())
+    }
+
+    /// Turns every lint off, as `-w` does, whatever the `-W` options say.
+    pub fn allow_all(&mut self) {
+        self.levels = [Level::Allow; Lint::ALL.len()];
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        match self.levels[lint as usize] {
+            Level::Warn if self.all_errors => Level::Deny,
+            level => level,
+        }
+    }
+}
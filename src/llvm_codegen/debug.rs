@@ -0,0 +1,136 @@
+use inkwell::builder::Builder;
+use inkwell::context::Context;
+use inkwell::debug_info::{
+    AsDIScope, DIFile, DIFlags, DIFlagsConstants, DISubprogram, DWARFEmissionKind,
+    DWARFSourceLanguage, DebugInfoBuilder,
+};
+use inkwell::module::{FlagBehavior, Linkage, Module};
+use inkwell::values::FunctionValue;
+use std::path::{Path, PathBuf};
+
+use crate::common::{FileId, Span};
+
+/// The debug information for a module, which maps the generated code back to lines of the
+/// source files so that a debugger can step through it.
+pub struct DebugInfo<'ctx> {
+    builder: DebugInfoBuilder<'ctx>,
+    /// Each source file, indexed by `FileId`.
+    files: Vec<DIFile<'ctx>>,
+    /// The function being emitted, which is the scope of every location within it.
+    function: Option<DISubprogram<'ctx>>,
+}
+
+impl<'ctx> DebugInfo<'ctx> {
+    /// Starts the debug information for a module compiled from `source_paths`, whose compile unit
+    /// is the file `unit_file`.
+    pub fn new(
+        context: &'ctx Context,
+        module: &Module<'ctx>,
+        source_paths: &[PathBuf],
+        unit_file: FileId,
+    ) -> Self {
+        let (file_name, directory) = split_path(&source_paths[unit_file.0]);
+        module.add_basic_value_flag(
+            "Debug Info Version",
+            FlagBehavior::Warning,
+            context.i32_type().const_int(3, false),
+        );
+        let (builder, compile_unit) = module.create_debug_info_builder(
+            true,
+            // DWARF has no code for Ratio, and C is what debuggers handle best.
+            DWARFSourceLanguage::C,
+            &file_name,
+            &directory,
+            concat!("ratio ", env!("CARGO_PKG_VERSION")),
+            false,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::Full,
+            0,
+            false,
+            false,
+            "",
+            "",
+        );
+        let files = source_paths
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                if i == unit_file.0 {
+                    return compile_unit.get_file();
+                }
+                let (file_name, directory) = split_path(path);
+                builder.create_file(&file_name, &directory)
+            })
+            .collect();
+        DebugInfo {
+            builder,
+            files,
+            function: None,
+        }
+    }
+
+    /// Describes `function`, which is called `name` in the source and defined at `span`, and
+    /// makes it the scope of the locations that follow.
+    pub fn enter_function(&mut self, function: FunctionValue<'ctx>, name: &str, span: Span) {
+        let file = self.files[span.file.0];
+        let subroutine_type = self
+            .builder
+            .create_subroutine_type(file, None, &[], DIFlags::ZERO);
+        let line = span.start.line as u32;
+        let linkage_name = function.get_name().to_string_lossy().into_owned();
+        let subprogram = self.builder.create_function(
+            file.as_debug_info_scope(),
+            name,
+            Some(&linkage_name),
+            file,
+            line,
+            subroutine_type,
+            function.get_linkage() == Linkage::Internal,
+            true,
+            line,
+            DIFlags::PUBLIC,
+            false,
+        );
+        function.set_subprogram(subprogram);
+        self.function = Some(subprogram);
+    }
+
+    /// Attributes the instructions emitted from now on to the start of `span`.
+    pub fn set_location(&self, context: &'ctx Context, builder: &Builder<'ctx>, span: Span) {
+        let Some(function) = self.function else {
+            return;
+        };
+        let location = self.builder.create_debug_location(
+            context,
+            span.start.line as u32,
+            span.start.column as u32,
+            function.as_debug_info_scope(),
+            None,
+        );
+        builder.set_current_debug_location(location);
+    }
+
+    /// Stops attributing instructions to the source, for code the compiler adds on its own.
+    pub fn leave_function(&mut self, builder: &Builder<'ctx>) {
+        self.function = None;
+        builder.unset_current_debug_location();
+    }
+
+    pub fn finalize(&self) {
+        self.builder.finalize();
+    }
+}
+
+/// The file name and directory of a source file, as DWARF records them.
+fn split_path(path: &Path) -> (String, String) {
+    let file_name = path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy().into_owned(),
+        _ => ".".to_string(),
+    };
+    (file_name, directory)
+}
@@ -1,200 +1,148 @@
-use inkwell::builder::Builder;
-use inkwell::context::Context;
-use inkwell::module::Module;
-use inkwell::values::{BasicValue, BasicValueEnum, PointerValue};
-use std::collections::HashMap;
+use inkwell::AddressSpace;
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum};
 
-use super::FnSig;
-use super::VarKind;
-use crate::common::{Expr, Token};
+use super::{Codegen, VarKind, basic_type};
+use crate::common::{Builtin, Token, Type};
+use crate::hir::{Callee, Expr, ExprKind};
+use crate::semantics::format;
 
-pub fn codegen_expr<'ctx>(
-    context: &'ctx Context,
-    module: &Module<'ctx>,
-    builder: &Builder<'ctx>,
-    expr: &Expr,
-    variables: &mut HashMap<String, VarKind<'ctx>>,
-    string_literals: &mut HashMap<String, PointerValue<'ctx>>,
-    fmt_int: PointerValue<'ctx>,
-    fmt_str: PointerValue<'ctx>,
-    function_table: &std::collections::HashMap<String, FnSig<'ctx>>,
-) -> BasicValueEnum<'ctx> {
-    match expr {
-        Expr::Call { callee, args } => {
-            if callee == "print" {
-                let arg = &args[0];
-                match arg {
-                    Expr::StringLiteral(s) => {
-                        let str_ptr = builder
-                            .build_global_string_ptr(&s, "str")
-                            .expect("global string")
-                            .as_pointer_value();
-                        let printf = module.get_function("printf").unwrap();
-                        builder
-                            .build_call(printf, &[fmt_str.into(), str_ptr.into()], "")
-                            .unwrap();
-                        context.i64_type().const_int(0, false).into()
-                    }
-                    Expr::Variable(name) => {
-                        if let Some(VarKind::Int(ptr)) = variables.get(name) {
-                            let val = builder
-                                .build_load(context.i64_type(), *ptr, name)
-                                .unwrap()
-                                .into_int_value();
-                            let printf = module.get_function("printf").unwrap();
-                            builder
-                                .build_call(printf, &[fmt_int.into(), val.into()], "")
-                                .unwrap();
+impl<'ctx> Codegen<'_, 'ctx> {
+    pub fn gen_expr(&mut self, expr: &Expr) -> BasicValueEnum<'ctx> {
+        let context = self.context;
+        match &expr.kind {
+            ExprKind::Call { callee, args } => match callee {
+                Callee::Builtin(Builtin::Print) if args.len() > 1 => {
+                    let format = match &args[0].kind {
+                        ExprKind::StringLiteral { value } => {
+                            self.intern_string(&format::to_printf_format(value), "fmt")
                         }
-                        context.i64_type().const_int(0, false).into()
+                        _ => self.gen_expr(&args[0]).into_pointer_value(),
+                    };
+                    let mut printf_args: Vec<BasicMetadataValueEnum> = vec![format.into()];
+                    for arg in &args[1..] {
+                        let val = self.gen_expr(arg);
+                        printf_args.push(self.widen_bool(val).into());
                     }
-                    _ => context.i64_type().const_int(0, false).into(),
+                    let printf = self.module.get_function("printf").unwrap();
+                    self.builder.build_call(printf, &printf_args, "").unwrap();
+                    if !matches!(args[0].kind, ExprKind::StringLiteral { .. }) {
+                        let newline = self.intern_string("\n", "newline");
+                        self.builder
+                            .build_call(printf, &[newline.into()], "")
+                            .unwrap();
+                    }
+                    context.i64_type().const_int(0, false).into()
                 }
-            } else if let Some(fn_sig) = function_table.get(callee) {
-                let mut arg_vals = Vec::new();
-                for (i, arg) in args.iter().enumerate() {
-                    let val = codegen_expr(
-                        context,
-                        module,
-                        builder,
-                        arg,
-                        variables,
-                        string_literals,
-                        fmt_int,
-                        fmt_str,
-                        function_table,
-                    );
-
-                    let expected = fn_sig.arg_types[i];
-                    let casted = match expected {
-                        inkwell::types::BasicTypeEnum::IntType(_) => {
-                            val.into_int_value().as_basic_value_enum()
-                        }
-                        inkwell::types::BasicTypeEnum::PointerType(_) => {
-                            val.into_pointer_value().as_basic_value_enum()
-                        }
-                        _ => val,
+                Callee::Builtin(Builtin::Print) => {
+                    let arg = &args[0];
+                    let val = self.gen_expr(arg);
+                    let fmt = match arg.ty {
+                        Type::Str => self.fmt_str,
+                        _ => self.fmt_int,
                     };
-                    arg_vals.push(casted);
-                }
-                let call = builder
-                    .build_call(
-                        fn_sig.func,
-                        &arg_vals.iter().map(|v| (*v).into()).collect::<Vec<_>>(),
-                        "calltmp",
-                    )
-                    .unwrap();
-                if fn_sig.ret_type.is_none() {
-                    builder.build_unreachable().unwrap();
+                    let printf = self.module.get_function("printf").unwrap();
+                    self.builder
+                        .build_call(printf, &[fmt.into(), self.widen_bool(val).into()], "")
+                        .unwrap();
                     context.i64_type().const_int(0, false).into()
-                } else {
-                    call.try_as_basic_value()
-                        .left()
-                        .unwrap_or(context.i64_type().const_int(0, false).into())
                 }
-            } else {
-                context.i64_type().const_int(0, false).into()
-            }
-        }
-        Expr::Variable(name) => {
-            if let Some(VarKind::Int(ptr)) = variables.get(name) {
-                builder
-                    .build_load(context.i64_type(), *ptr, name)
-                    .unwrap()
-                    .into()
-            } else {
-                context.i64_type().const_int(0, false).into()
+                Callee::Builtin(Builtin::Input) => {
+                    unreachable!("calls to `input` are rejected before code generation")
+                }
+                Callee::Function { name, .. } | Callee::Extern { name, .. } => {
+                    let mut arg_vals: Vec<BasicMetadataValueEnum> = Vec::new();
+                    for arg in args {
+                        let val = self.gen_expr(arg);
+                        arg_vals.push(self.widen_bool(val).into());
+                    }
+                    let fn_sig = &self.function_table[name];
+                    // LLVM rejects a name on a call that produces no value.
+                    let name = if fn_sig.ret_type.is_some() {
+                        "calltmp"
+                    } else {
+                        ""
+                    };
+                    let call = self
+                        .builder
+                        .build_call(fn_sig.func, &arg_vals, name)
+                        .unwrap();
+                    match fn_sig.ret_type {
+                        Some(_) => match call.try_as_basic_value().left().unwrap() {
+                            // printf, which an extern can declare as returning int, returns a
+                            // 32-bit C int.
+                            BasicValueEnum::IntValue(int)
+                                if int.get_type().get_bit_width() == 32 =>
+                            {
+                                self.builder
+                                    .build_int_s_extend(int, context.i64_type(), "")
+                                    .unwrap()
+                                    .into()
+                            }
+                            value => value,
+                        },
+                        None => context.i64_type().const_int(0, false).into(),
+                    }
+                }
+            },
+            ExprKind::Variable { var } => match self.variables[var] {
+                VarKind::Int(ptr) => self
+                    .builder
+                    .build_load(context.i64_type(), ptr, "")
+                    .unwrap(),
+                VarKind::Str(ptr) => self
+                    .builder
+                    .build_load(context.ptr_type(AddressSpace::default()), ptr, "")
+                    .unwrap(),
+            },
+            ExprKind::Const { id } => self
+                .builder
+                .build_load(basic_type(context, expr.ty), self.consts[id.0], "")
+                .unwrap(),
+            ExprKind::StringLiteral { value: s } => self.intern_string(s, "str").into(),
+            ExprKind::IntegerLiteral { value: n } => {
+                context.i64_type().const_int(*n as u64, false).into()
             }
-        }
-        Expr::StringLiteral(s) => builder
-            .build_global_string_ptr(&s, "str")
-            .expect("global string")
-            .as_pointer_value()
-            .into(),
-        Expr::IntegerLiteral(n) => context.i64_type().const_int(*n as u64, false).into(),
-        Expr::BooleanLiteral(b) => context
-            .i64_type()
-            .const_int(if *b { 1 } else { 0 }, false)
-            .into(),
-        Expr::BinaryOperator {
-            operator,
-            left,
-            right,
-        } => {
-            let l = codegen_expr(
-                context,
-                module,
-                builder,
+            ExprKind::BooleanLiteral { value: b } => context
+                .i64_type()
+                .const_int(if *b { 1 } else { 0 }, false)
+                .into(),
+            ExprKind::BinaryOperator {
+                operator,
                 left,
-                variables,
-                string_literals,
-                fmt_int,
-                fmt_str,
-                function_table,
-            )
-            .into_int_value();
-            let r = codegen_expr(
-                context,
-                module,
-                builder,
                 right,
-                variables,
-                string_literals,
-                fmt_int,
-                fmt_str,
-                function_table,
-            )
-            .into_int_value();
-            match operator.as_str() {
-                "+" => builder.build_int_add(l, r, "addtmp").unwrap().into(),
-                "-" => builder.build_int_sub(l, r, "subtmp").unwrap().into(),
-                "*" => builder.build_int_mul(l, r, "multmp").unwrap().into(),
-                "/" => builder.build_int_signed_div(l, r, "divtmp").unwrap().into(),
-                _ => context.i64_type().const_int(0, false).into(),
+            } => {
+                let l = self.gen_expr(left).into_int_value();
+                let r = self.gen_expr(right).into_int_value();
+                let builder = self.builder;
+                match operator.as_str() {
+                    "+" => builder.build_int_add(l, r, "addtmp").unwrap().into(),
+                    "-" => builder.build_int_sub(l, r, "subtmp").unwrap().into(),
+                    "*" => builder.build_int_mul(l, r, "multmp").unwrap().into(),
+                    "/" => builder.build_int_signed_div(l, r, "divtmp").unwrap().into(),
+                    _ => context.i64_type().const_int(0, false).into(),
+                }
             }
-        }
-        Expr::BooleanComparison {
-            lvalue,
-            operator,
-            rvalue,
-        } => {
-            let l = codegen_expr(
-                context,
-                module,
-                builder,
+            ExprKind::BooleanComparison {
                 lvalue,
-                variables,
-                string_literals,
-                fmt_int,
-                fmt_str,
-                function_table,
-            )
-            .into_int_value();
-            let r = codegen_expr(
-                context,
-                module,
-                builder,
+                operator,
                 rvalue,
-                variables,
-                string_literals,
-                fmt_int,
-                fmt_str,
-                function_table,
-            )
-            .into_int_value();
-            let pred = match operator {
-                Token::Equality => inkwell::IntPredicate::EQ,
-                Token::NotEqual => inkwell::IntPredicate::NE,
-                Token::LessThan => inkwell::IntPredicate::SLT,
-                Token::LessThanOrEqual => inkwell::IntPredicate::SLE,
-                Token::GreaterThan => inkwell::IntPredicate::SGT,
-                Token::GreaterThanOrEqual => inkwell::IntPredicate::SGE,
-                _ => inkwell::IntPredicate::EQ,
-            };
-            builder
-                .build_int_compare(pred, l, r, "cmptmp")
-                .unwrap()
-                .into()
+            } => {
+                let l = self.gen_expr(lvalue).into_int_value();
+                let r = self.gen_expr(rvalue).into_int_value();
+                let pred = match operator {
+                    Token::Equality => inkwell::IntPredicate::EQ,
+                    Token::NotEqual => inkwell::IntPredicate::NE,
+                    Token::LessThan => inkwell::IntPredicate::SLT,
+                    Token::LessThanOrEqual => inkwell::IntPredicate::SLE,
+                    Token::GreaterThan => inkwell::IntPredicate::SGT,
+                    Token::GreaterThanOrEqual => inkwell::IntPredicate::SGE,
+                    _ => inkwell::IntPredicate::EQ,
+                };
+                self.builder
+                    .build_int_compare(pred, l, r, "cmptmp")
+                    .unwrap()
+                    .into()
+            }
         }
     }
 }
@@ -0,0 +1,35 @@
+use inkwell::OptimizationLevel;
+use inkwell::module::Module;
+use inkwell::support;
+
+/// Why a module could not be run in memory.
+pub enum JitError {
+    /// A function the module calls but does not define, such as an extern, that this process
+    /// has no symbol for.
+    MissingSymbol(String),
+    Engine(String),
+}
+
+/// Runs the module's C main in this process and returns its exit status. Every function the
+/// module only declares is bound to the symbol of that name in this process, which includes the
+/// C library, before anything runs, so a missing one is reported rather than crashing the call.
+pub fn run_main(module: &Module, opt_level: OptimizationLevel) -> Result<i32, JitError> {
+    support::load_visible_symbols();
+    let engine = module
+        .create_jit_execution_engine(opt_level)
+        .map_err(|message| JitError::Engine(message.to_string()))?;
+    for function in module.get_functions() {
+        let name = function.get_name().to_string_lossy().into_owned();
+        // Intrinsics are implemented by LLVM itself.
+        if function.count_basic_blocks() > 0 || name.starts_with("llvm.") {
+            continue;
+        }
+        let address =
+            support::search_for_address_of_symbol(&name).ok_or(JitError::MissingSymbol(name))?;
+        engine.add_global_mapping(&function, address);
+    }
+    // SAFETY: `main` is the `int main(void)` that codegen emits for every program.
+    let main = unsafe { engine.get_function::<unsafe extern "C" fn() -> i32>("main") }
+        .expect("the module has a C main");
+    Ok(unsafe { main.call() })
+}
@@ -1,18 +1,27 @@
 use inkwell::AddressSpace;
+use inkwell::attributes::{Attribute, AttributeLoc};
 use inkwell::builder::Builder;
 use inkwell::context::Context;
-use inkwell::module::Module;
+use inkwell::module::{Linkage, Module};
 use inkwell::types::BasicTypeEnum;
-use inkwell::values::PointerValue;
+use inkwell::values::{BasicValueEnum, PointerValue};
 use std::collections::HashMap;
+use std::path::PathBuf;
 
-use crate::common::{Expr, Program, Stmt, Type};
+use crate::common::{FileId, Inline, Span, Type, content_hash};
+use crate::hir::units::{Binding, Unit};
+use crate::hir::{self, ConstValue, USER_MAIN_SYMBOL, VarId};
+use crate::{file_io, ice};
 use inkwell::types::BasicType;
 
+mod debug;
 mod expr;
+pub mod jit;
 mod stmt;
 
-#[derive(Clone)]
+use debug::DebugInfo;
+
+#[derive(Clone, Copy)]
 enum VarKind<'ctx> {
     Int(PointerValue<'ctx>),
     Str(PointerValue<'ctx>),
@@ -20,206 +29,360 @@ enum VarKind<'ctx> {
 
 pub struct FnSig<'ctx> {
     pub func: inkwell::values::FunctionValue<'ctx>,
-    pub arg_types: Vec<BasicTypeEnum<'ctx>>,
     pub ret_type: Option<BasicTypeEnum<'ctx>>,
 }
 
+/// The state of emitting one module. `variables` belongs to the function being emitted; the rest
+/// is shared by every function.
+pub struct Codegen<'a, 'ctx> {
+    context: &'ctx Context,
+    module: &'a Module<'ctx>,
+    builder: &'a Builder<'ctx>,
+    /// Every function that can be called, by its Ratio name.
+    function_table: HashMap<String, FnSig<'ctx>>,
+    /// The formats for printing a single value.
+    fmt_int: PointerValue<'ctx>,
+    fmt_str: PointerValue<'ctx>,
+    /// String constants by their contents, so that repeating a literal, even in another function,
+    /// does not add another copy.
+    string_literals: HashMap<String, PointerValue<'ctx>>,
+    /// The global holding each constant, indexed by `ConstId`.
+    consts: Vec<PointerValue<'ctx>>,
+    /// The stack slot of each local.
+    variables: HashMap<VarId, VarKind<'ctx>>,
+    /// Present when compiling with debug information.
+    debug: Option<DebugInfo<'ctx>>,
+}
+
+/// Emits the functions of `unit` into `module`. The program's other functions are only declared,
+/// for the linker to resolve against the objects that define them. `c_main` is false for an
+/// object to link into a program that has a C main of its own.
 pub fn generate_module<'ctx>(
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-    program: &Program,
+    program: &hir::Program,
+    unit: &Unit,
+    debug_sources: Option<&[PathBuf]>,
+    c_main: bool,
 ) {
-    // Declare printf so we can use it
-    let i8ptr_type = context.ptr_type(inkwell::AddressSpace::default());
-    let printf_type = context.i32_type().fn_type(&[i8ptr_type.into()], true);
-    module.add_function("printf", printf_type, None);
-
-    let mut function_table = std::collections::HashMap::new();
-
-    for ext in &program.externs {
-        let arg_types: Vec<_> = ext
-            .args
-            .iter()
-            .map(|(_, t)| match t {
-                Type::Int | Type::Bool => context.i64_type().as_basic_type_enum(),
-                _ => context
-                    .ptr_type(inkwell::AddressSpace::default())
-                    .as_basic_type_enum(),
-            })
-            .collect();
-        let fn_arg_types: Vec<_> = arg_types.iter().map(|t| (*t).into()).collect();
-        let (fn_type, ret_type) = if ext.name == "exit" || ext.return_type == "" {
-            (context.void_type().fn_type(&fn_arg_types, false), None)
-        } else {
-            (
-                context.i64_type().fn_type(&fn_arg_types, false),
-                Some(context.i64_type().as_basic_type_enum()),
-            )
-        };
-        let func = module.add_function(&ext.name, fn_type, None);
-        function_table.insert(
-            ext.name.clone(),
-            FnSig {
-                func,
-                arg_types,
-                ret_type,
-            },
+    let debug = debug_sources
+        .map(|paths| DebugInfo::new(context, module, paths, unit.file_id().unwrap_or(FileId(0))));
+    let mut codegen = Codegen::new(context, module, builder, debug);
+    codegen.declare_consts(program);
+    codegen.declare_functions(program, unit);
+    let defined = program
+        .functions
+        .iter()
+        .filter(|func| unit.binding(func) != Binding::Imported);
+    for func in defined.clone() {
+        ice::set_context(format!(
+            "compiling function `{}` at {}:{}",
+            func.name, func.span.start.line, func.span.start.column
+        ));
+        codegen.gen_function(func);
+    }
+    ice::clear_context();
+    if let Some(debug) = &codegen.debug {
+        debug.finalize();
+    }
+
+    if c_main && defined.clone().any(|func| func.name == program.entry) {
+        emit_c_main(
+            context,
+            module,
+            builder,
+            codegen.function_table[&program.entry].func,
         );
     }
+}
+
+impl<'a, 'ctx> Codegen<'a, 'ctx> {
+    fn new(
+        context: &'ctx Context,
+        module: &'a Module<'ctx>,
+        builder: &'a Builder<'ctx>,
+        debug: Option<DebugInfo<'ctx>>,
+    ) -> Self {
+        // Declare printf so we can use it. Externs for it must match `common::RUNTIME_FUNCTIONS`.
+        let i8ptr_type = context.ptr_type(inkwell::AddressSpace::default());
+        let printf_type = context.i32_type().fn_type(&[i8ptr_type.into()], true);
+        module.add_function("printf", printf_type, None);
+
+        Codegen {
+            context,
+            module,
+            builder,
+            function_table: HashMap::new(),
+            fmt_int: global_string(context, module, "%lld\n", "fmt_int"),
+            fmt_str: global_string(context, module, "%s\n", "fmt_str"),
+            string_literals: HashMap::new(),
+            consts: Vec::new(),
+            variables: HashMap::new(),
+            debug,
+        }
+    }
+
+    /// Adds a private constant global for each constant, initialized with its computed value.
+    fn declare_consts(&mut self, program: &hir::Program) {
+        for constant in &program.consts {
+            let value: BasicValueEnum = match &constant.value {
+                ConstValue::Int(value) => self
+                    .context
+                    .i64_type()
+                    .const_int(*value as u64, false)
+                    .into(),
+                ConstValue::Bool(value) => self
+                    .context
+                    .i64_type()
+                    .const_int(*value as u64, false)
+                    .into(),
+                ConstValue::Str(value) => self.intern_string(value, "str").into(),
+            };
+            let global = self
+                .module
+                .add_global(value.get_type(), None, &constant.name);
+            global.set_linkage(Linkage::Private);
+            global.set_constant(true);
+            global.set_initializer(&value);
+            self.consts.push(global.as_pointer_value());
+        }
+    }
+
+    /// Adds every extern and function to the module, so that calls can refer to functions defined
+    /// later in the file or in another object.
+    fn declare_functions(&mut self, program: &hir::Program, unit: &Unit) {
+        let context = self.context;
+        for ext in &program.externs {
+            let fn_arg_types: Vec<_> = ext
+                .signature
+                .params
+                .iter()
+                .map(|t| basic_type(context, *t).into())
+                .collect();
+            let (fn_type, ret_type) = if ext.signature.return_type == Type::Void {
+                (context.void_type().fn_type(&fn_arg_types, false), None)
+            } else {
+                let ret_type = basic_type(context, ext.signature.return_type);
+                (ret_type.fn_type(&fn_arg_types, false), Some(ret_type))
+            };
+            // A repeated extern, or one for a function the compiler declared for a builtin, shares
+            // the existing declaration. Semantic analysis has checked that the two are compatible.
+            let func = self
+                .module
+                .get_function(&ext.name)
+                .unwrap_or_else(|| self.module.add_function(&ext.name, fn_type, None));
+            if ext.signature.noreturn {
+                let noreturn = Attribute::get_named_enum_kind_id("noreturn");
+                func.add_attribute(
+                    AttributeLoc::Function,
+                    context.create_enum_attribute(noreturn, 0),
+                );
+            }
+            self.function_table
+                .insert(ext.name.clone(), FnSig { func, ret_type });
+        }
 
-    for func in &program.functions {
-        if let Stmt::Function { name, args, .. } = func {
-            let arg_types: Vec<_> = args
+        for func in &program.functions {
+            let fn_arg_types: Vec<_> = func
+                .signature
+                .params
                 .iter()
-                .map(|(_, t)| match t {
-                    Type::Int | Type::Bool => context.i64_type().as_basic_type_enum(),
-                    _ => context
-                        .ptr_type(inkwell::AddressSpace::default())
-                        .as_basic_type_enum(),
-                })
+                .map(|t| basic_type(context, *t).into())
                 .collect();
-            let fn_arg_types: Vec<_> = arg_types.iter().map(|t| (*t).into()).collect();
-            let ret_type = Some(context.i64_type().as_basic_type_enum());
-            let fn_type = ret_type.unwrap().fn_type(&fn_arg_types, false);
-            let func_val = module.add_function(name, fn_type, None);
-            function_table.insert(
-                name.clone(),
+            let ret_type = basic_type(context, func.signature.return_type);
+            let fn_type = ret_type.fn_type(&fn_arg_types, false);
+            let symbol = if func.name == "main" {
+                USER_MAIN_SYMBOL
+            } else {
+                func.name.as_str()
+            };
+            // Only the C main and the functions the unit exports are called from outside the
+            // module. Internal linkage keeps the rest out of the global symbol table, where they
+            // could clash with C library functions, and lets the optimizer drop any that are
+            // never called.
+            let linkage = match unit.binding(func) {
+                Binding::Local => Linkage::Internal,
+                Binding::Exported | Binding::Imported => Linkage::External,
+            };
+            let func_val = self.module.add_function(symbol, fn_type, Some(linkage));
+            let inline_attribute = match func.inline {
+                Inline::Default => None,
+                Inline::Always => Some("alwaysinline"),
+                Inline::Never => Some("noinline"),
+            };
+            if let Some(attribute) = inline_attribute {
+                let kind = Attribute::get_named_enum_kind_id(attribute);
+                func_val.add_attribute(
+                    AttributeLoc::Function,
+                    context.create_enum_attribute(kind, 0),
+                );
+            }
+            self.function_table.insert(
+                func.name.clone(),
                 FnSig {
                     func: func_val,
-                    arg_types,
-                    ret_type,
+                    ret_type: Some(ret_type),
                 },
             );
         }
     }
 
-    for func in &program.functions {
-        if let Stmt::Function {
-            name,
-            args,
-            body,
-            return_expr,
-        } = func
-        {
-            let fn_sig = function_table.get(name).unwrap();
-            let function = fn_sig.func;
-            let entry = context.append_basic_block(function, "entry");
-            builder.position_at_end(entry);
-
-            let fmt_int = builder
-                .build_global_string_ptr("%ld\n", "fmt_int")
-                .expect("global string")
-                .as_pointer_value();
-            let fmt_str = builder
-                .build_global_string_ptr("%s\n", "fmt_str")
-                .expect("global string")
-                .as_pointer_value();
-
-            let mut variables: HashMap<String, VarKind> = HashMap::new();
-            let mut string_literals: HashMap<String, PointerValue> = HashMap::new();
-
-            for (i, (arg_name, arg_type)) in args.iter().enumerate() {
-                let llvm_arg = function.get_nth_param(i as u32).unwrap();
-                let ptr = match arg_type {
-                    Type::Int | Type::Bool => {
-                        builder.build_alloca(context.i64_type(), arg_name).unwrap()
-                    }
-                    _ => builder
-                        .build_alloca(context.ptr_type(AddressSpace::default()), arg_name)
-                        .unwrap(),
-                };
-                builder.build_store(ptr, llvm_arg).unwrap();
-                match arg_type {
-                    Type::Int | Type::Bool => {
-                        variables.insert(arg_name.clone(), VarKind::Int(ptr));
-                    }
-                    _ => {
-                        variables.insert(arg_name.clone(), VarKind::Str(ptr));
-                    }
-                }
-            }
+    fn gen_function(&mut self, func: &hir::Function) {
+        let function = self.function_table[&func.name].func;
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+        if let Some(debug) = &mut self.debug {
+            debug.enter_function(function, &func.name, func.span);
+        }
+        self.set_location(func.span);
 
-            let mut did_return = false;
-            for stmt in body {
-                if let Stmt::Return(expr) = stmt {
-                    let ret_val = expr::codegen_expr(
-                        context,
-                        module,
-                        builder,
-                        expr,
-                        &mut variables,
-                        &mut string_literals,
-                        fmt_int,
-                        fmt_str,
-                        &function_table,
-                    );
-                    builder.build_return(Some(&ret_val)).expect("return");
-                    did_return = true;
-                    break;
-                } else if let Stmt::ExprStmt(Expr::Call { callee, .. }) = stmt {
-                    if callee == "exit" {
-                        stmt::codegen_stmt(
-                            context,
-                            module,
-                            builder,
-                            stmt,
-                            &mut variables,
-                            &mut string_literals,
-                            fmt_int,
-                            fmt_str,
-                            &function_table,
-                        );
-                        // Don't emit a return after exit()
-                        did_return = true;
-                        break;
-                    } else {
-                        stmt::codegen_stmt(
-                            context,
-                            module,
-                            builder,
-                            stmt,
-                            &mut variables,
-                            &mut string_literals,
-                            fmt_int,
-                            fmt_str,
-                            &function_table,
-                        );
-                    }
-                } else {
-                    stmt::codegen_stmt(
-                        context,
-                        module,
-                        builder,
-                        stmt,
-                        &mut variables,
-                        &mut string_literals,
-                        fmt_int,
-                        fmt_str,
-                        &function_table,
-                    );
-                }
-            }
-            if !did_return {
-                if let Some(expr) = return_expr {
-                    let ret_val = expr::codegen_expr(
-                        context,
-                        module,
-                        builder,
-                        expr,
-                        &mut variables,
-                        &mut string_literals,
-                        fmt_int,
-                        fmt_str,
-                        &function_table,
-                    );
-                    builder.build_return(Some(&ret_val)).expect("return");
-                } else {
-                    builder
-                        .build_return(Some(&context.i64_type().const_int(0, false)))
-                        .expect("return");
-                }
-            }
+        // Every local gets its stack slot up front, so a declaration inside a loop reuses one
+        // slot rather than allocating on each iteration.
+        self.variables.clear();
+        for (i, local) in func.locals.iter().enumerate() {
+            let ptr = self
+                .builder
+                .build_alloca(basic_type(self.context, local.ty), &local.name)
+                .unwrap();
+            let var_kind = match local.ty {
+                Type::Str => VarKind::Str(ptr),
+                _ => VarKind::Int(ptr),
+            };
+            self.variables.insert(VarId(i), var_kind);
+        }
+        for (i, param) in func.params.iter().enumerate() {
+            let llvm_arg = function.get_nth_param(i as u32).unwrap();
+            let (VarKind::Int(ptr) | VarKind::Str(ptr)) = self.variables[param];
+            self.builder.build_store(ptr, llvm_arg).unwrap();
+        }
+
+        self.gen_block(&func.body);
+        if !self.is_terminated() {
+            self.builder
+                .build_return(Some(&self.context.i64_type().const_int(0, false)))
+                .expect("return");
+        }
+        if let Some(debug) = &mut self.debug {
+            debug.leave_function(self.builder);
+        }
+    }
+
+    /// Attributes the instructions emitted from now on to `span`, when emitting debug information.
+    fn set_location(&self, span: Span) {
+        if let Some(debug) = &self.debug {
+            debug.set_location(self.context, self.builder, span);
+        }
+    }
+
+    /// A pointer to a string constant with the given contents, reusing an earlier one if possible.
+    /// The global is named after a hash of its contents rather than numbered in the order strings
+    /// are met, so editing one function does not rename the strings of every later one.
+    fn intern_string(&mut self, value: &str, name: &str) -> PointerValue<'ctx> {
+        let (context, module) = (self.context, self.module);
+        *self
+            .string_literals
+            .entry(value.to_string())
+            .or_insert_with(|| {
+                let name = format!("{}.{:016x}", name, content_hash(value));
+                global_string(context, module, value, &name)
+            })
+    }
+
+    /// Comparisons produce an `i1`, but bools are stored as 64-bit integers. Values are widened
+    /// with this before being stored, passed or returned, so every bool crossing those boundaries
+    /// has the same representation.
+    fn widen_bool(&self, value: BasicValueEnum<'ctx>) -> BasicValueEnum<'ctx> {
+        match value {
+            BasicValueEnum::IntValue(int) if int.get_type().get_bit_width() == 1 => self
+                .builder
+                .build_int_z_extend(int, self.context.i64_type(), "")
+                .unwrap()
+                .into(),
+            _ => value,
         }
     }
 }
+
+/// Runs LLVM's verifier on the generated module. Invalid IR is always a bug in the compiler, so a
+/// failure is reported as an internal compiler error, with the IR saved to a file for the report.
+pub fn verify_module(module: &Module) {
+    let Err(message) = module.verify() else {
+        return;
+    };
+    eprintln!("internal compiler error: generated invalid LLVM IR; please report this");
+    let broken: Vec<String> = module
+        .get_functions()
+        .filter(|function| !function.verify(false))
+        .map(|function| format!("`{}`", function.get_name().to_string_lossy()))
+        .collect();
+    if !broken.is_empty() {
+        eprintln!("note: in {}", broken.join(", "));
+    }
+    eprintln!("{}", message.to_string_lossy().trim_end());
+    let ir_path = std::env::temp_dir().join("ratio-invalid.ll");
+    if module.print_to_file(&ir_path).is_ok() {
+        eprintln!("note: the IR has been written to {}", ir_path.display());
+    }
+    eprintln!("note: ratio {}", env!("CARGO_PKG_VERSION"));
+    file_io::remove_temporaries();
+    std::process::exit(ice::EXIT_STATUS);
+}
+
+/// Adds a private, null-terminated string constant to the module and returns a pointer to it.
+/// Unlike `build_global_string_ptr`, this does not need the builder to be inside a function.
+fn global_string<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    value: &str,
+    name: &str,
+) -> PointerValue<'ctx> {
+    let string = context.const_string(value.as_bytes(), true);
+    let global = module.add_global(string.get_type(), None, name);
+    global.set_linkage(Linkage::Private);
+    global.set_constant(true);
+    global.set_unnamed_addr(true);
+    global.set_initializer(&string);
+    global.as_pointer_value()
+}
+
+/// The LLVM type that holds a Ratio value. Bools are stored as 64-bit integers.
+fn basic_type<'ctx>(context: &'ctx Context, t: Type) -> BasicTypeEnum<'ctx> {
+    match t {
+        Type::Int | Type::Bool => context.i64_type().as_basic_type_enum(),
+        Type::Str => context
+            .ptr_type(AddressSpace::default())
+            .as_basic_type_enum(),
+        Type::Void => unreachable!("void values are never stored"),
+    }
+}
+
+/// Emits `int main(void)`, which calls the entry point and returns its result as the exit status.
+fn emit_c_main<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    user_main: inkwell::values::FunctionValue<'ctx>,
+) {
+    // wasi-libc calls a `main` without parameters by this name, which clang would give it for C.
+    let triple = module.get_triple();
+    let name = if triple.as_str().to_string_lossy().contains("wasi") {
+        "__main_void"
+    } else {
+        "main"
+    };
+    let c_main = module.add_function(name, context.i32_type().fn_type(&[], false), None);
+    let entry = context.append_basic_block(c_main, "entry");
+    builder.position_at_end(entry);
+    let result = builder
+        .build_call(user_main, &[], "result")
+        .unwrap()
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_int_value();
+    let status = builder
+        .build_int_truncate(result, context.i32_type(), "status")
+        .unwrap();
+    builder.build_return(Some(&status)).expect("return");
+}
@@ -1,235 +1,157 @@
-use inkwell::AddressSpace;
-use inkwell::builder::Builder;
-use inkwell::context::Context;
-use inkwell::module::Module;
-use inkwell::values::PointerValue;
-use std::collections::HashMap;
+use inkwell::IntPredicate;
+use inkwell::values::{BasicValueEnum, IntValue};
 
-use super::FnSig;
-use super::VarKind;
-use super::expr::codegen_expr;
-use crate::common::Stmt;
+use super::{Codegen, VarKind};
+use crate::hir::{ExprKind, Stmt};
 
-pub fn codegen_stmt<'ctx>(
-    context: &'ctx Context,
-    module: &Module<'ctx>,
-    builder: &Builder<'ctx>,
-    stmt: &Stmt,
-    variables: &mut HashMap<String, VarKind<'ctx>>,
-    string_literals: &mut HashMap<String, PointerValue<'ctx>>,
-    fmt_int: PointerValue<'ctx>,
-    fmt_str: PointerValue<'ctx>,
-    function_table: &std::collections::HashMap<String, FnSig<'ctx>>,
-) {
-    match stmt {
-        Stmt::VariableDecl {
-            name,
-            type_name,
-            value,
-        } => {
-            match type_name.as_str() {
-                "int" | "bool" => {
-                    let val = codegen_expr(
-                        context,
-                        module,
-                        builder,
-                        value,
-                        variables,
-                        string_literals,
-                        fmt_int,
-                        fmt_str,
-                        function_table,
-                    );
-                    let ptr = builder.build_alloca(context.i64_type(), name).unwrap();
-                    builder
-                        .build_store(ptr, val.into_int_value())
-                        .expect("store int");
-                    variables.insert(name.clone(), VarKind::Int(ptr));
-                }
-                _ => {
-                    // Assume string type.
-                    // This shouldn't be reachable because of the parser
-                    let val = codegen_expr(
-                        context,
-                        module,
-                        builder,
-                        value,
-                        variables,
-                        string_literals,
-                        fmt_int,
-                        fmt_str,
-                        function_table,
-                    );
-                    let ptr = builder
-                        .build_alloca(context.ptr_type(AddressSpace::default()), name)
-                        .unwrap();
-                    builder
-                        .build_store(ptr, val.into_pointer_value())
-                        .expect("store ptr");
-                    variables.insert(name.clone(), VarKind::Str(ptr));
-                }
+impl<'ctx> Codegen<'_, 'ctx> {
+    pub fn gen_stmt(&mut self, stmt: &Stmt) {
+        self.set_location(stmt.span());
+        match stmt {
+            Stmt::Return { value, .. } => {
+                let val = self.gen_expr(value);
+                let val = self.widen_bool(val);
+                self.builder.build_return(Some(&val)).expect("return");
             }
-        }
-        Stmt::Assignment { name, value } => {
-            let var_kind = variables.get(name).cloned();
-            if let Some(var) = var_kind {
-                match var {
+            Stmt::VariableDecl { var, value, .. } | Stmt::Assignment { var, value, .. } => {
+                let val = self.gen_expr(value);
+                let val = self.widen_bool(val);
+                match self.variables[var] {
                     VarKind::Int(ptr) => {
-                        let val = codegen_expr(
-                            context,
-                            module,
-                            builder,
-                            value,
-                            variables,
-                            string_literals,
-                            fmt_int,
-                            fmt_str,
-                            function_table,
-                        );
-                        builder
+                        self.builder
                             .build_store(ptr, val.into_int_value())
                             .expect("store int");
                     }
                     VarKind::Str(ptr) => {
-                        let val = codegen_expr(
-                            context,
-                            module,
-                            builder,
-                            value,
-                            variables,
-                            string_literals,
-                            fmt_int,
-                            fmt_str,
-                            function_table,
-                        );
-                        builder
+                        self.builder
                             .build_store(ptr, val.into_pointer_value())
                             .expect("store ptr");
                     }
                 }
             }
-        }
-        Stmt::ExprStmt(expr) => {
-            codegen_expr(
-                context,
-                module,
-                builder,
-                expr,
-                variables,
-                string_literals,
-                fmt_int,
-                fmt_str,
-                function_table,
-            );
-        }
-        Stmt::IfStatement {
-            condition,
-            body,
-            else_body,
-        } => {
-            let parent = builder.get_insert_block().unwrap().get_parent().unwrap();
-            let then_bb = context.append_basic_block(parent, "then");
-            let else_bb = context.append_basic_block(parent, "else");
-            let merge_bb = context.append_basic_block(parent, "ifcont");
-
-            let cond_val = codegen_expr(
-                context,
-                module,
-                builder,
+            Stmt::ExprStmt { expr, .. } => {
+                self.gen_expr(expr);
+                if let ExprKind::Call { callee, .. } = &expr.kind
+                    && callee.is_noreturn()
+                {
+                    self.builder.build_unreachable().unwrap();
+                }
+            }
+            Stmt::IfStatement {
                 condition,
-                variables,
-                string_literals,
-                fmt_int,
-                fmt_str,
-                function_table,
-            );
+                body,
+                else_body,
+                ..
+            } => {
+                let parent = self
+                    .builder
+                    .get_insert_block()
+                    .unwrap()
+                    .get_parent()
+                    .unwrap();
+                let then_bb = self.context.append_basic_block(parent, "then");
+                let else_bb = self.context.append_basic_block(parent, "else");
 
-            let cond_bool = cond_val.into_int_value();
-            builder
-                .build_conditional_branch(cond_bool, then_bb, else_bb)
-                .unwrap();
+                let cond_val = self.gen_expr(condition);
+                let cond_bool = self.condition_value(cond_val);
+                self.builder
+                    .build_conditional_branch(cond_bool, then_bb, else_bb)
+                    .unwrap();
 
-            // Then
-            builder.position_at_end(then_bb);
-            for stmt in body {
-                codegen_stmt(
-                    context,
-                    module,
-                    builder,
-                    stmt,
-                    variables,
-                    string_literals,
-                    fmt_int,
-                    fmt_str,
-                    function_table,
-                );
-            }
-            builder.build_unconditional_branch(merge_bb).unwrap();
+                // Then
+                self.builder.position_at_end(then_bb);
+                self.gen_block(body);
+                // Nested statements may have moved emission on to a later block.
+                let then_end = self.builder.get_insert_block().unwrap();
+
+                // Else
+                self.builder.position_at_end(else_bb);
+                if let Some(else_body) = else_body {
+                    self.gen_block(else_body);
+                }
+                let else_end = self.builder.get_insert_block().unwrap();
 
-            // Else
-            builder.position_at_end(else_bb);
-            if let Some(else_body) = else_body {
-                for stmt in else_body {
-                    codegen_stmt(
-                        context,
-                        module,
-                        builder,
-                        stmt,
-                        variables,
-                        string_literals,
-                        fmt_int,
-                        fmt_str,
-                        function_table,
-                    );
+                // A branch that ended in a return has nowhere to go, so the merge block only
+                // exists when at least one branch falls through to it.
+                let fall_through: Vec<_> = [then_end, else_end]
+                    .into_iter()
+                    .filter(|block| block.get_terminator().is_none())
+                    .collect();
+                if !fall_through.is_empty() {
+                    let merge_bb = self.context.append_basic_block(parent, "ifcont");
+                    for block in fall_through {
+                        self.builder.position_at_end(block);
+                        self.builder.build_unconditional_branch(merge_bb).unwrap();
+                    }
+                    self.builder.position_at_end(merge_bb);
                 }
             }
-            builder.build_unconditional_branch(merge_bb).unwrap();
+            Stmt::While {
+                condition, body, ..
+            } => {
+                let parent = self
+                    .builder
+                    .get_insert_block()
+                    .unwrap()
+                    .get_parent()
+                    .unwrap();
+                let cond_bb = self.context.append_basic_block(parent, "while.cond");
+                let body_bb = self.context.append_basic_block(parent, "while.body");
+                let after_bb = self.context.append_basic_block(parent, "while.after");
 
-            builder.position_at_end(merge_bb);
-        }
-        Stmt::While { condition, body } => {
-            let parent = builder.get_insert_block().unwrap().get_parent().unwrap();
-            let cond_bb = context.append_basic_block(parent, "while.cond");
-            let body_bb = context.append_basic_block(parent, "while.body");
-            let after_bb = context.append_basic_block(parent, "while.after");
+                self.builder.build_unconditional_branch(cond_bb).unwrap();
+                self.builder.position_at_end(cond_bb);
+                let cond_val = self.gen_expr(condition);
+                let cond_bool = self.condition_value(cond_val);
+                self.builder
+                    .build_conditional_branch(cond_bool, body_bb, after_bb)
+                    .unwrap();
 
-            builder.build_unconditional_branch(cond_bb).unwrap();
-            builder.position_at_end(cond_bb);
-            let cond_val = codegen_expr(
-                context,
-                module,
-                builder,
-                condition,
-                variables,
-                string_literals,
-                fmt_int,
-                fmt_str,
-                function_table,
-            );
+                self.builder.position_at_end(body_bb);
+                self.gen_block(body);
+                // A body that ends in a return leaves the loop rather than going round again.
+                if !self.is_terminated() {
+                    self.builder.build_unconditional_branch(cond_bb).unwrap();
+                }
 
-            let cond_bool = cond_val.into_int_value();
-            builder
-                .build_conditional_branch(cond_bool, body_bb, after_bb)
-                .unwrap();
+                self.builder.position_at_end(after_bb);
+            }
+        }
+    }
 
-            builder.position_at_end(body_bb);
-            for stmt in body {
-                codegen_stmt(
-                    context,
-                    module,
-                    builder,
-                    stmt,
-                    variables,
-                    string_literals,
-                    fmt_int,
-                    fmt_str,
-                    function_table,
-                );
+    /// Emits the statements of a block, stopping once one of them ends the current basic block,
+    /// since nothing after a `return` can execute.
+    pub fn gen_block(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.gen_stmt(stmt);
+            if self.is_terminated() {
+                break;
             }
-            builder.build_unconditional_branch(cond_bb).unwrap();
+        }
+    }
+
+    /// Whether the block being emitted into already ends in a terminator, such as a `ret`.
+    pub fn is_terminated(&self) -> bool {
+        self.builder
+            .get_insert_block()
+            .and_then(|block| block.get_terminator())
+            .is_some()
+    }
 
-            builder.position_at_end(after_bb);
+    /// Branches need an `i1` condition. Comparisons already produce one, but a bool read from a
+    /// variable is stored as an `i64`, so it is compared against zero first.
+    fn condition_value(&self, value: BasicValueEnum<'ctx>) -> IntValue<'ctx> {
+        let value = value.into_int_value();
+        if value.get_type().get_bit_width() == 1 {
+            return value;
         }
-        _ => {}
+        self.builder
+            .build_int_compare(
+                IntPredicate::NE,
+                value,
+                value.get_type().const_zero(),
+                "cond",
+            )
+            .unwrap()
     }
 }
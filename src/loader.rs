@@ -0,0 +1,192 @@
+use std::path::{Path, PathBuf};
+
+use crate::common::{CompileError, FileId, Program, Span, Token};
+use crate::diagnostics::{ErrorCode, SourceFile};
+use crate::file_io;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+/// Reads and parses the files in `source_paths` and every file they import, and merges them
+/// into one program. Returns every file read, indexed by `FileId`, so that diagnostics can show
+/// any of them, together with the program or the errors of all the files.
+///
+/// A file imported more than once, directly or through other files, is only read the first
+/// time. Each file's imports are loaded straight after it, so a file that imports itself, through
+/// any number of other files, is an error naming the chain of imports. A file given in
+/// `source_paths` that cannot be read stops loading, since there is no import to point at.
+///
+/// `defines` is a file the compiler wrote itself, declaring the constants given on the command
+/// line. It comes before every other file, so its constants are declared first.
+pub fn load_program(
+    source_paths: &[PathBuf],
+    defines: Option<SourceFile>,
+) -> Result<LoadedProgram, UnreadableFile> {
+    let mut loader = Loader {
+        files: Vec::new(),
+        canonical_paths: Vec::new(),
+        importing: Vec::new(),
+        program: Program::default(),
+        errors: Vec::new(),
+    };
+    if let Some(defines) = defines {
+        loader.load_defines(defines);
+    }
+    for path in source_paths {
+        loader.load(path, None)?;
+    }
+    let program = if loader.errors.is_empty() {
+        Ok(loader.program)
+    } else {
+        Err(loader.errors)
+    };
+    Ok((loader.files, program))
+}
+
+/// Every file read, indexed by `FileId`, and the program they make up or the errors in them.
+pub type LoadedProgram = (Vec<SourceFile>, Result<Program, Vec<CompileError>>);
+
+/// A source file given on the command line that could not be read.
+pub struct UnreadableFile {
+    pub path: PathBuf,
+    pub error: std::io::Error,
+}
+
+/// Splits a source file into tokens, ending with `Token::EOF`.
+pub fn lex(input: &str, file: FileId) -> Result<Vec<(Token, Span)>, Vec<CompileError>> {
+    let mut lexer = Lexer::new(input, file);
+    let mut tokens: Vec<(Token, Span)> = Vec::new();
+    let mut lex_errors = Vec::new();
+    loop {
+        match lexer.next_token() {
+            Ok((Token::EOF, span)) => {
+                tokens.push((Token::EOF, span));
+                break;
+            }
+            Ok(token) => tokens.push(token),
+            Err(error) => lex_errors.push(error),
+        }
+    }
+    if !lex_errors.is_empty() {
+        return Err(lex_errors);
+    }
+    Ok(tokens)
+}
+
+/// Parses the tokens of a source file into a syntax tree.
+pub fn parse(tokens: Vec<(Token, Span)>, file: FileId) -> Result<Program, Vec<CompileError>> {
+    Parser::new(tokens, file).parse()
+}
+
+struct Loader {
+    files: Vec<SourceFile>,
+    /// The canonical path of each file in `files`, so that a file is recognised however the
+    /// imports name it.
+    canonical_paths: Vec<PathBuf>,
+    /// The files whose imports are being loaded, outermost first.
+    importing: Vec<FileId>,
+    program: Program,
+    errors: Vec<CompileError>,
+}
+
+impl Loader {
+    /// Loads a file that is not read from disk, and so has no imports to follow.
+    fn load_defines(&mut self, defines: SourceFile) {
+        let file = FileId(self.files.len());
+        self.files.push(defines);
+        // No import names an empty path, so none can reach this file.
+        self.canonical_paths.push(PathBuf::new());
+        match lex(&self.files[file.0].text, file).and_then(|tokens| parse(tokens, file)) {
+            Ok(program) => self.program.merge(program),
+            Err(errors) => self.errors.extend(errors),
+        }
+    }
+
+    /// Loads the file at `path` and then the files it imports. `import` is the span of the
+    /// import that named the file, or `None` for a file given on the command line. Only a file
+    /// given on the command line is an `UnreadableFile`; an import that cannot be read is
+    /// reported as an error at the import.
+    fn load(&mut self, path: &Path, import: Option<Span>) -> Result<(), UnreadableFile> {
+        let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Some(index) = self.canonical_paths.iter().position(|p| *p == canonical) {
+            // A file still loading its imports has been reached again through one of them.
+            let cycle_start = self.importing.iter().position(|id| id.0 == index);
+            if let (Some(span), Some(start)) = (import, cycle_start) {
+                let mut chain: Vec<&str> = self.importing[start..]
+                    .iter()
+                    .map(|id| self.files[id.0].name.as_str())
+                    .collect();
+                chain.push(&self.files[index].name);
+                let error =
+                    CompileError::new(format!("import cycle: {}", chain.join(" -> ")), span)
+                        .with_code(ErrorCode::ImportCycle)
+                        .with_help("move what the files share into a file that neither imports");
+                self.errors.push(error);
+            }
+            return Ok(());
+        }
+
+        let text = match (file_io::read_source(path), import) {
+            (Ok(text), _) => text,
+            (Err(error), Some(span)) => {
+                let error =
+                    CompileError::new(format!("cannot read `{}`: {}", path.display(), error), span)
+                        .with_code(ErrorCode::UnresolvedImport)
+                        .with_note(
+                            "imports are relative to the directory of the file that imports them",
+                        );
+                self.errors.push(error);
+                return Ok(());
+            }
+            (Err(error), None) => {
+                let path = path.to_path_buf();
+                return Err(UnreadableFile { path, error });
+            }
+        };
+        let file = FileId(self.files.len());
+        self.files.push(SourceFile {
+            name: file_io::source_name(path),
+            text: text.trim_end().to_string(),
+        });
+        self.canonical_paths.push(canonical);
+
+        let program =
+            match lex(&self.files[file.0].text, file).and_then(|tokens| parse(tokens, file)) {
+                Ok(program) => program,
+                Err(errors) => {
+                    // Without a syntax tree there are no imports to follow.
+                    self.errors.extend(errors);
+                    return Ok(());
+                }
+            };
+        let directory = path.parent().unwrap_or(Path::new(""));
+        self.importing.push(file);
+        for import in &program.imports {
+            self.load(&directory.join(&import.path), Some(import.span))?;
+        }
+        self.importing.pop();
+        self.program.merge(program);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::load_program;
+
+    #[test]
+    fn leading_blank_lines_keep_their_line_numbers() {
+        let dir = std::env::temp_dir().join(format!("ratio-loader-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("blank.ratio");
+        std::fs::write(&path, "\n\nfn main() {\n    return 0\n}\n\n\n").unwrap();
+        let loaded = load_program(&[path], None);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let Ok((files, program)) = loaded else {
+            panic!("the file cannot be read");
+        };
+        assert!(files[0].text.starts_with("\n\nfn main()"));
+        let errors = program.unwrap_err();
+        assert_eq!(errors[0].span.start.line, 4);
+    }
+}
@@ -1,86 +1,2078 @@
+#[cfg(not(any(feature = "llvm", feature = "cranelift")))]
+compile_error!("ratio needs a backend to generate code: enable the `llvm` or `cranelift` feature");
+
+mod cache;
 mod common;
+#[cfg(feature = "cranelift")]
+mod cranelift_codegen;
+mod diagnostics;
+mod dump;
 mod file_io;
+mod formatter;
+mod header;
+mod hir;
+mod ice;
 mod lexer;
+mod lint;
+#[cfg(feature = "llvm")]
 mod llvm_codegen;
+mod loader;
+mod manifest;
 mod parser;
+mod semantics;
+mod test_runner;
 mod type_system;
 
-use common::{Position, Token};
-use lexer::Lexer;
+use common::{Builtin, CompileError, FileId};
+use diagnostics::{ErrorCode, Renderer, Severity, SourceFile};
+use hir::units::Unit;
+use lint::{Level, Lint, LintLevels};
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+#[cfg(feature = "llvm")]
 use inkwell::context::Context;
+#[cfg(feature = "llvm")]
+use inkwell::passes::PassBuilderOptions;
+#[cfg(feature = "llvm")]
+use inkwell::targets::{CodeModel, Target, TargetMachine, TargetTriple};
+#[cfg(feature = "llvm")]
+use llvm_codegen::jit::JitError;
+use std::io::{IsTerminal, Write};
 use std::process;
 
-#[derive(Parser)]
-#[command(version, about, long_about = None)]
+/// Reporting stops after this many errors, since later ones are often knock-on effects.
+const MAX_REPORTED_ERRORS: usize = 20;
+
+/// The name diagnostics give the constants defined with `--define`, which are compiled as a
+/// source file of their own.
+const DEFINES_FILE: &str = "<command line>";
+
+/// The exit status when the program has errors, a file is not formatted or a test fails.
+const EXIT_ERRORS: i32 = 1;
+/// The exit status when the compiler was run wrongly, or cannot read or write a file. It is also
+/// what clap exits with for a bad command line.
+const EXIT_USAGE: i32 = 2;
+/// The exit status when a tool the compiler relies on fails, such as the linker or LLVM.
+const EXIT_TOOLCHAIN: i32 = 3;
+
+/// Documents the exit statuses at the end of `--help`.
+const EXIT_STATUS_HELP: &str = "Exit status:
+  0   success
+  1   the program has errors, a file is not formatted or a test failed
+  2   the compiler was run wrongly, or a file could not be read or written
+  3   a tool the compiler relies on, such as the linker, failed
+  70  internal compiler error
+`run` and `--jit` exit with the program's own status once it runs.";
+
+#[derive(Parser, Clone)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    subcommand_negates_reqs = true,
+    after_help = EXIT_STATUS_HELP
+)]
 struct Arguments {
-    source_path: std::path::PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The source files of the program, or `-` to read it from stdin. Inputs with an extension
+    /// other than `.ratio`, such as object files and static libraries, are linked in as they are
+    #[arg(required_unless_present = "explain", value_name = "SOURCE")]
+    source_paths: Vec<std::path::PathBuf>,
 
+    /// Where to write the output [default: the first source file's name without `.ratio`, in the
+    /// current directory, with `.ll` for `--emit llvm-ir`]
     #[arg(short, long)]
-    output: String,
+    output: Option<String>,
+
+    /// Print the tokens after lexing, instead of compiling
+    #[arg(long, conflicts_with = "dump_ast")]
+    dump_tokens: bool,
+
+    /// Print the syntax tree after parsing, instead of compiling
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "pretty"
+    )]
+    dump_ast: Option<AstFormat>,
+
+    /// What to produce
+    #[arg(long, value_enum, value_name = "KIND", default_value_t = Emit::Link)]
+    emit: Emit,
+
+    /// Also write a C header declaring the functions the program exports, so that C code can call
+    /// them
+    #[arg(long, value_name = "PATH", global = true)]
+    emit_header: Option<std::path::PathBuf>,
+
+    /// Function that execution starts in. Unless it is `main`, it is exported under its own name,
+    /// and with `--emit obj` the object has no C main, so that a C program can call it
+    #[arg(long, value_name = "NAME", global = true, default_value = "main")]
+    entry: String,
+
+    /// Print a detailed explanation of an error code, such as R0003
+    #[arg(long, value_name = "CODE", conflicts_with = "source_paths")]
+    explain: Option<String>,
+
+    /// When to color diagnostics
+    #[arg(long, value_enum, global = true, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Define a constant for the program, as if it were declared before anything else. VALUE is
+    /// an integer, `true` or `false`; can be given more than once
+    #[arg(short = 'D', long = "define", value_name = "NAME=VALUE", global = true)]
+    defines: Vec<String>,
+
+    /// Turn on the warnings of a lint, such as `-Wdead-code`, or turn them off with `no-LINT`.
+    /// `error` makes every warning an error and `error=LINT` one lint's; can be given more than
+    /// once, and later options win
+    #[arg(short = 'W', value_name = "LINT", global = true)]
+    warnings: Vec<String>,
+
+    /// Print no warnings, whatever -W says
+    #[arg(short = 'w', global = true)]
+    no_warnings: bool,
+
+    /// Generate debug information, so that a debugger can show the program's source lines
+    #[arg(short = 'g', global = true)]
+    debug_info: bool,
+
+    /// Optimization level [default: 2, or 3 with --release]
+    #[arg(short = 'O', value_enum, value_name = "LEVEL", global = true)]
+    opt_level: Option<OptLevel>,
+
+    /// Build for release: optimize at -O3 and strip the executable's symbols. -O chooses another
+    /// level, and -g keeps the symbols along with the debug information
+    #[arg(long, global = true)]
+    release: bool,
+
+    /// Code generator to compile with [default: llvm when it is built in]
+    #[arg(long, value_enum, value_name = "BACKEND", global = true, default_value_t = Backend::DEFAULT, hide_default_value = true)]
+    backend: Backend,
+
+    /// Relocation model of the generated code
+    #[arg(long, value_enum, value_name = "MODEL", global = true, default_value_t = Relocation::Default)]
+    reloc: Relocation,
+
+    /// How to link the C library into the executable [default: static, or dynamic with
+    /// `--reloc pic`]
+    #[arg(long, value_enum, value_name = "KIND", global = true)]
+    link: Option<Linking>,
+
+    #[command(flatten)]
+    target: TargetOptions,
+
+    /// C compiler that links the object files into an executable, also taken from RATIO_CC
+    /// [default: the first of cc, gcc and clang on PATH, for the host only]
+    #[arg(long, visible_alias = "cc", value_name = "PROGRAM", global = true)]
+    linker: Option<String>,
+
+    /// Library to link the executable with, such as `m` for `-l m`; can be given more than once
+    #[arg(short = 'l', value_name = "NAME", global = true)]
+    libraries: Vec<String>,
+
+    /// Directory to search for the libraries given with `-l`; can be given more than once
+    #[arg(short = 'L', value_name = "DIR", global = true)]
+    library_dirs: Vec<std::path::PathBuf>,
+
+    /// Argument to pass to the linker as it is, after the ones the compiler adds, such as
+    /// `--link-arg=-fuse-ld=lld`; can be given more than once
+    #[arg(
+        long = "link-arg",
+        value_name = "ARG",
+        allow_hyphen_values = true,
+        global = true
+    )]
+    link_args: Vec<String>,
+
+    /// Print what the compiler chose for the target, and what it did with the cache and the
+    /// linker; -vv also prints each stage of the compile and how long it took. RATIO_LOG=info or
+    /// RATIO_LOG=trace does the same without the option
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Run the program in memory and exit with its status, instead of writing an executable
+    #[arg(long, conflicts_with_all = ["output", "emit", "link", "linker", "libraries", "library_dirs", "link_args", "triple"])]
+    jit: bool,
+
+    /// Generate code even when an object file cached by an earlier build could be reused
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// Keep the object file and LLVM IR generated for each source file, in DIR or else next to
+    /// the output. Implies --no-cache, so that every one is generated
+    #[arg(
+        long,
+        value_name = "DIR",
+        num_args = 0..=1,
+        require_equals = true,
+        global = true
+    )]
+    keep_intermediates: Option<Option<std::path::PathBuf>>,
+}
+
+#[derive(Subcommand, Clone)]
+enum Command {
+    /// Compile a program to a temporary executable and run it, exiting with its status
+    Run {
+        #[arg(required = true, value_name = "SOURCE")]
+        source_paths: Vec<std::path::PathBuf>,
+
+        /// Arguments for the program, after `--`
+        #[arg(last = true, value_name = "ARGS")]
+        program_args: Vec<String>,
+    },
+    /// Report a program's errors and warnings without generating code
+    Check {
+        #[arg(required = true, value_name = "SOURCE")]
+        source_paths: Vec<std::path::PathBuf>,
+    },
+    /// Print a program in the canonical style
+    Fmt {
+        source_path: std::path::PathBuf,
+
+        /// Print nothing, but exit with 1 if the file is not already formatted
+        #[arg(long, conflicts_with = "write")]
+        check: bool,
+
+        /// Format the file in place instead of printing it
+        #[arg(long)]
+        write: bool,
+    },
+    /// Compile and run every `.ratio` file in a directory, checking the output against the
+    /// `// expect:` comments in each
+    Test { dir: std::path::PathBuf },
+    /// Remove the object files cached by earlier builds
+    Clean,
+    /// Compile the project that `ratio.toml` in the current directory describes. Libraries and
+    /// linker arguments given here are added to the manifest's, and -O or --release override its
+    /// optimization level
+    Build {
+        /// Where to write the executable, instead of where the manifest says
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(clap::Args, Clone)]
+struct TargetOptions {
+    /// Target triple to compile for, such as `aarch64-unknown-linux-gnu` [default: the host]
+    #[arg(long = "target", value_name = "TRIPLE")]
+    triple: Option<String>,
+
+    /// CPU to generate code for, or `native` for the CPU of this machine
+    #[arg(long, value_name = "CPU", global = true, default_value = "generic")]
+    mcpu: String,
+
+    /// Target features to enable or disable, such as `+avx2,-sse4.1`
+    #[arg(long, value_name = "FEATURES", global = true, default_value = "")]
+    mattr: String,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    /// Color when stderr is a terminal and NO_COLOR is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Emit {
+    /// An executable
+    Link,
+    /// LLVM's textual IR, after optimization, instead of an executable
+    LlvmIr,
+    /// An object file to link into another program, instead of an executable
+    Obj,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AstFormat {
+    /// An indented tree for reading
+    Pretty,
+    /// A JSON document for other tools, with every span and literal value
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Backend {
+    /// LLVM, which supports every option
+    Llvm,
+    /// Cranelift, which compiles faster but only for the host, without debug information
+    Cranelift,
+}
+
+impl Backend {
+    const DEFAULT: Backend = if cfg!(feature = "llvm") {
+        Backend::Llvm
+    } else {
+        Backend::Cranelift
+    };
+}
+
+/// How much the compiler says about its own work, on stderr. Diagnostics are printed whatever the
+/// level.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum LogLevel {
+    Off,
+    /// What the compiler chose, such as the target and the linker command.
+    Info,
+    /// Also each stage of the compile, with its time.
+    Trace,
+}
+
+impl LogLevel {
+    /// The level set by `-v` or `-vv`, or else by RATIO_LOG, which is off unless it names a
+    /// level.
+    fn of(args: &Arguments) -> Self {
+        match args.verbose {
+            0 => std::env::var("RATIO_LOG")
+                .ok()
+                .and_then(|level| LogLevel::from_str(&level, true).ok())
+                .unwrap_or(LogLevel::Off),
+            1 => LogLevel::Info,
+            _ => LogLevel::Trace,
+        }
+    }
+}
+
+/// Says at the trace level how long `stage` took, from `started` until now.
+fn trace(args: &Arguments, stage: &str, started: std::time::Instant) {
+    if LogLevel::of(args) >= LogLevel::Trace {
+        eprintln!("trace: {} took {:.2?}", stage, started.elapsed());
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OptLevel {
+    /// No optimization
+    #[value(name = "0")]
+    O0,
+    #[value(name = "1")]
+    O1,
+    #[value(name = "2")]
+    O2,
+    #[value(name = "3")]
+    O3,
+}
+
+impl OptLevel {
+    /// The level given with `-O`, or else the default for the build.
+    fn of(args: &Arguments) -> Self {
+        match args.opt_level {
+            Some(level) => level,
+            None if args.release => OptLevel::O3,
+            None => OptLevel::O2,
+        }
+    }
+
+    /// The new pass manager pipeline for this level.
+    #[cfg(feature = "llvm")]
+    fn pipeline(self) -> &'static str {
+        match self {
+            OptLevel::O0 => "default<O0>",
+            OptLevel::O1 => "default<O1>",
+            OptLevel::O2 => "default<O2>",
+            OptLevel::O3 => "default<O3>",
+        }
+    }
+
+    #[cfg(feature = "llvm")]
+    fn codegen_level(self) -> inkwell::OptimizationLevel {
+        match self {
+            OptLevel::O0 => inkwell::OptimizationLevel::None,
+            OptLevel::O1 => inkwell::OptimizationLevel::Less,
+            OptLevel::O2 => inkwell::OptimizationLevel::Default,
+            OptLevel::O3 => inkwell::OptimizationLevel::Aggressive,
+        }
+    }
+
+    /// Cranelift's `opt_level` setting for this level. Cranelift has no levels between none and
+    /// full optimization.
+    #[cfg(feature = "cranelift")]
+    fn cranelift_level(self) -> &'static str {
+        match self {
+            OptLevel::O0 => "none",
+            OptLevel::O1 | OptLevel::O2 | OptLevel::O3 => "speed",
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Relocation {
+    /// The backend's default for the target
+    Default,
+    /// Non-relocatable code
+    Static,
+    /// Position-independent code, which is linked as a position-independent executable
+    Pic,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Linking {
+    /// Copy the C library into the executable, so that it runs without one installed
+    Static,
+    /// Load the shared C library when the executable starts
+    Dynamic,
+}
+
+/// The operating systems whose executables are linked differently.
+#[derive(Clone, Copy, PartialEq)]
+enum Platform {
+    /// macOS and Apple's other systems, which have no static C library.
+    Apple,
+    Windows,
+    /// Linux and the other systems with ELF executables, and WebAssembly.
+    Other,
+}
+
+impl Platform {
+    /// The platform code is generated for: the target's, or the host's for backends that only
+    /// generate code for the host.
+    fn of(args: &Arguments) -> Self {
+        match args.backend {
+            #[cfg(feature = "llvm")]
+            Backend::Llvm => {
+                let triple = target_triple(&args.target);
+                let name = triple.as_str().to_string_lossy();
+                if name.contains("apple") || name.contains("darwin") {
+                    Platform::Apple
+                } else if name.contains("windows") {
+                    Platform::Windows
+                } else {
+                    Platform::Other
+                }
+            }
+            #[allow(unreachable_patterns)]
+            _ if cfg!(target_vendor = "apple") => Platform::Apple,
+            _ if cfg!(windows) => Platform::Windows,
+            _ => Platform::Other,
+        }
+    }
+}
+
+impl Relocation {
+    #[cfg(feature = "llvm")]
+    fn mode(self) -> inkwell::targets::RelocMode {
+        match self {
+            Relocation::Default => inkwell::targets::RelocMode::Default,
+            Relocation::Static => inkwell::targets::RelocMode::Static,
+            Relocation::Pic => inkwell::targets::RelocMode::PIC,
+        }
+    }
+}
+
+impl Linking {
+    /// How to link when `--link` is not given: statically, unless the code is
+    /// position-independent, since a static position-independent executable needs a C library
+    /// built for it as well, or the target is Apple's, which only has a shared C library.
+    fn of(args: &Arguments) -> Self {
+        match (args.link, args.reloc) {
+            (Some(linking), _) => linking,
+            (None, _) if Platform::of(args) == Platform::Apple => Linking::Dynamic,
+            (None, Relocation::Pic) => Linking::Dynamic,
+            (None, Relocation::Default | Relocation::Static) => Linking::Static,
+        }
+    }
+
+    /// The C compiler flags for linking code of the relocation model `reloc` this way. A static
+    /// executable needs the static C library, which many distributions do not install by
+    /// default. Toolchains that build position-independent executables by default must be told
+    /// not to when the code is not position-independent. Windows executables are never
+    /// position-independent in that sense, so only static linking needs a flag there, and
+    /// Apple's are always dynamically linked position-independent executables, which the linker
+    /// builds whatever the code.
+    fn link_args(self, reloc: Relocation, platform: Platform) -> &'static [&'static str] {
+        match (self, reloc) {
+            // Static linking was already rejected by `check_linking`.
+            (_, _) if platform == Platform::Apple => &[],
+            (Linking::Static, _) if platform == Platform::Windows => &["-static"],
+            (Linking::Dynamic, _) if platform == Platform::Windows => &[],
+            (Linking::Static, Relocation::Default | Relocation::Static) => &["-static"],
+            (Linking::Static, Relocation::Pic) => &["-static-pie"],
+            (Linking::Dynamic, Relocation::Default | Relocation::Static) => &["-no-pie"],
+            (Linking::Dynamic, Relocation::Pic) => &["-pie"],
+        }
+    }
+
+    /// The C library for an MSVC-style linker to link this way. The objects name no default
+    /// libraries, unlike the MSVC compiler's, so the C runtime is given explicitly: the static
+    /// `libcmt` or the DLL import library `msvcrt`. `printf` is only inline in the headers of
+    /// the Universal CRT, so it comes from `legacy_stdio_definitions`.
+    fn msvc_libraries(self) -> &'static [&'static str] {
+        match self {
+            Linking::Static => &["libcmt.lib", "legacy_stdio_definitions.lib"],
+            Linking::Dynamic => &["msvcrt.lib", "legacy_stdio_definitions.lib"],
+        }
+    }
 }
 
 fn main() {
+    ice::install_hook();
     let args = Arguments::parse();
-    let input = file_io::read_file(&args.source_path).expect("Failed to read file");
-    let input = input.trim();
+    if let Some(code) = &args.explain {
+        explain(&Renderer::new(&[], args.color.enabled()), code);
+    }
+    if args.command.is_some() {
+        reject_compile_options(&args);
+    }
+    if let Some(Command::Run {
+        source_paths,
+        program_args,
+    }) = &args.command
+    {
+        run(&args, source_paths, program_args);
+    }
+    if let Some(Command::Check { source_paths }) = &args.command {
+        check(&args, source_paths);
+    }
+    if let Some(Command::Fmt {
+        source_path,
+        check,
+        write,
+    }) = &args.command
+    {
+        fmt(&args, source_path, *check, *write);
+    }
+    if let Some(Command::Test { dir }) = &args.command {
+        test(&args, dir);
+    }
+    if let Some(Command::Clean) = &args.command {
+        clean(&args);
+    }
+    if let Some(Command::Build { output }) = &args.command {
+        build(&args, output.as_deref());
+    }
+    compile(&args, &args.source_paths, || match &args.output {
+        Some(output) => output.clone(),
+        // A program run with --jit is never written out.
+        None if args.jit => String::new(),
+        None => default_output(&args),
+    });
+}
+
+/// Exits if an option that only applies when compiling without a subcommand, such as `-o`, was
+/// given before one. Options that a subcommand uses can be given before it or after it.
+fn reject_compile_options(args: &Arguments) {
+    let given = [
+        ("-o", args.output.is_some()),
+        ("--dump-tokens", args.dump_tokens),
+        ("--dump-ast", args.dump_ast.is_some()),
+        ("--emit", !matches!(args.emit, Emit::Link)),
+        ("--target", args.target.triple.is_some()),
+        ("--jit", args.jit),
+    ];
+    if let Some((option, _)) = given.iter().find(|(_, given)| *given) {
+        let renderer = Renderer::new(&[], args.color.enabled());
+        let message = format!("`{}` cannot be used with a subcommand", option);
+        eprintln!("{}", renderer.header(Severity::Error, None, &message));
+        eprintln!("help: it only applies when compiling a program without a subcommand");
+        process::exit(EXIT_USAGE);
+    }
+}
+
+/// The output path when none is given: the first source file's name without its extension, in
+/// the current directory, with the extension of what is written.
+fn default_output(args: &Arguments) -> String {
+    let source = args
+        .source_paths
+        .iter()
+        .find(|path| !is_link_input(path))
+        .expect("compile checks that there is a source file");
+    if file_io::is_stdin(source) {
+        let renderer = Renderer::new(&[], args.color.enabled());
+        let message = "a program read from stdin has no name to give the output";
+        eprintln!("{}", renderer.header(Severity::Error, None, message));
+        eprintln!("help: give an output path with `-o <OUTPUT>`");
+        process::exit(EXIT_USAGE);
+    }
+    let stem = source.file_stem().unwrap_or(source.as_os_str());
+    let extension = match args.emit {
+        Emit::LlvmIr => "ll",
+        Emit::Obj => object_extension(args),
+        Emit::Link => executable_extension(args),
+    };
+    std::path::Path::new(stem)
+        .with_extension(extension)
+        .display()
+        .to_string()
+}
+
+/// The extension of an object file for the target: `.obj` on Windows and `.o` elsewhere.
+fn object_extension(args: &Arguments) -> &'static str {
+    match Platform::of(args) {
+        Platform::Windows => "obj",
+        Platform::Apple | Platform::Other => "o",
+    }
+}
+
+/// The extension of a linked program for the target: none on Unix, `.exe` on Windows and
+/// `.wasm` for WebAssembly. An object file's when the target has no linker, as the object file
+/// is written instead.
+fn executable_extension(args: &Arguments) -> &'static str {
+    match args.backend {
+        #[cfg(feature = "llvm")]
+        Backend::Llvm => {
+            let triple = target_triple(&args.target);
+            let name = triple.as_str().to_string_lossy();
+            if name.starts_with("wasm") {
+                "wasm"
+            } else if target_linker(args, &triple).is_none() {
+                object_extension(args)
+            } else if name.contains("windows") {
+                "exe"
+            } else {
+                ""
+            }
+        }
+        #[allow(unreachable_patterns)]
+        _ => std::env::consts::EXE_EXTENSION,
+    }
+}
+
+/// Makes sure the program can be written to `output`, creating its directory if need be. It may
+/// not be a directory or one of the source files. Exits after reporting a problem.
+fn prepare_output(renderer: &Renderer, files: &[SourceFile], output: &str) {
+    let fail = |message: String| -> ! {
+        eprintln!("{}", renderer.header(Severity::Error, None, &message));
+        process::exit(EXIT_USAGE);
+    };
+    let path = std::path::Path::new(output);
+    if path.is_dir() {
+        fail(format!(
+            "cannot write the output to `{}`, which is a directory",
+            output
+        ));
+    }
+    if let Ok(output) = std::fs::canonicalize(path)
+        && files
+            .iter()
+            .any(|file| std::fs::canonicalize(&file.name).is_ok_and(|source| source == output))
+    {
+        eprintln!(
+            "{}",
+            renderer.header(
+                Severity::Error,
+                None,
+                &format!(
+                    "writing the output to `{}` would overwrite the source file",
+                    path.display()
+                )
+            )
+        );
+        eprintln!("help: give another output path with `-o <OUTPUT>`");
+        process::exit(EXIT_USAGE);
+    }
+    if let Some(directory) = path.parent()
+        && !directory.as_os_str().is_empty()
+        && let Err(error) = std::fs::create_dir_all(directory)
+    {
+        fail(format!(
+            "cannot create the directory `{}`: {}",
+            directory.display(),
+            error
+        ));
+    }
+}
+
+/// Reports that the source file `path` could not be read, and exits.
+fn read_failed(renderer: &Renderer, path: &std::path::Path, error: std::io::Error) -> ! {
+    let message = format!("cannot read `{}`: {}", file_io::source_name(path), error);
+    eprintln!("{}", renderer.header(Severity::Error, None, &message));
+    match error.kind() {
+        std::io::ErrorKind::IsADirectory => {
+            eprintln!("help: name the `.ratio` files in it instead");
+        }
+        std::io::ErrorKind::InvalidData => {
+            eprintln!("note: source files must be UTF-8; save it in that encoding");
+        }
+        _ => {}
+    }
+    process::exit(EXIT_USAGE);
+}
+
+/// Reports that `path` could not be written, and exits.
+fn write_failed(renderer: &Renderer, path: &str, error: impl std::fmt::Display) -> ! {
+    let message = format!("cannot write `{}`: {}", path, error);
+    eprintln!("{}", renderer.header(Severity::Error, None, &message));
+    file_io::remove_temporaries();
+    process::exit(EXIT_USAGE);
+}
+
+/// Writes `text` to stdout. A reader that closes the pipe early, such as `head`, ends the
+/// compiler quietly; any other failure is reported.
+fn print_stdout(renderer: &Renderer, text: &str) {
+    let mut stdout = std::io::stdout().lock();
+    if let Err(error) = stdout
+        .write_all(text.as_bytes())
+        .and_then(|()| stdout.flush())
+    {
+        if error.kind() == std::io::ErrorKind::BrokenPipe {
+            process::exit(0);
+        }
+        write_failed(renderer, "<stdout>", error);
+    }
+}
+
+/// Writes the output with `write`, which is given a temporary path beside it, then renames that
+/// over the output. A compile that fails or is interrupted part way therefore never leaves a
+/// partly written output, nor replaces a good one.
+fn write_output(renderer: &Renderer, output: &str, write: impl FnOnce(&str) -> Result<(), String>) {
+    let path = std::path::Path::new(output);
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let partial =
+        file_io::TempPath::new(path.with_file_name(format!(".{}.{}.partial", name, process::id())));
+    if let Err(error) = write(&partial.path().display().to_string()) {
+        write_failed(renderer, output, error);
+    }
+    if let Err(error) = partial.persist(path) {
+        write_failed(renderer, output, error);
+    }
+}
+
+/// Compiles the program in the source files among `inputs` and writes it to the path `output`
+/// returns. That is only asked for once the program has compiled without errors. The other
+/// inputs are linked into the executable after the program.
+fn compile(args: &Arguments, inputs: &[std::path::PathBuf], output: impl FnOnce() -> String) {
+    let (source_paths, link_inputs) = split_inputs(args, inputs);
+    let source_paths = source_paths.as_slice();
+    check_linking(args);
+    // Tokens are dumped for the files given, without following their imports.
+    if args.dump_tokens {
+        let files = read_sources(args, source_paths);
+        let renderer = Renderer::new(&files, args.color.enabled());
+        for (i, file) in files.iter().enumerate() {
+            let tokens = match loader::lex(&file.text, FileId(i)) {
+                Ok(tokens) => tokens,
+                Err(errors) => {
+                    report_errors(&renderer, errors);
+                    process::exit(EXIT_ERRORS);
+                }
+            };
+            // Spans do not name their file, so each file's tokens follow its name.
+            if files.len() > 1 {
+                print_stdout(&renderer, &format!("{}:\n", file.name));
+            }
+            print_stdout(&renderer, &dump::tokens::print_tokens(&tokens));
+        }
+        return;
+    }
+    let started = std::time::Instant::now();
+    let (files, program) = load_program(args, source_paths);
+    trace(args, "loading the source files", started);
+    let renderer = Renderer::new(&files, args.color.enabled());
+    let program = program.unwrap_or_else(|errors| {
+        report_errors(&renderer, errors);
+        process::exit(EXIT_ERRORS);
+    });
+    if let Some(format) = args.dump_ast {
+        match format {
+            AstFormat::Pretty => print_stdout(&renderer, &dump::ast::print_program(&program)),
+            AstFormat::Json => {
+                print_stdout(&renderer, &(dump::json::print_program(&program) + "\n"));
+            }
+        }
+        return;
+    }
+    let started = std::time::Instant::now();
+    let program = analyze(&renderer, args, &program, &files);
+    trace(args, "checking the program", started);
+    if let Some(path) = &args.emit_header {
+        let file_name = path
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let header = header::c_header(&program, &header::guard_name(&file_name));
+        write_output(&renderer, &path.display().to_string(), |partial| {
+            file_io::write_file(std::path::Path::new(partial), &header)
+                .map_err(|error| error.to_string())
+        });
+    }
+
+    let output = output();
+    if !output.is_empty() {
+        prepare_output(&renderer, &files, &output);
+    }
+    // Only executables are cached; IR and programs run with --jit are always generated afresh.
+    // Each object adds its own source file to the key.
+    let cache_key = (!args.no_cache
+        && args.keep_intermediates.is_none()
+        && matches!(args.emit, Emit::Link)
+        && !args.jit)
+        .then(|| {
+            let mut key = cache::Key::for_compiler()
+                .with(&value_name(args.backend))
+                .with(&value_name(OptLevel::of(args)))
+                .with(&value_name(args.reloc))
+                .with(&args.entry);
+            // Debug information records where the source files are.
+            if args.debug_info {
+                key = key.with("-g");
+                for file in &files {
+                    key = key.with(&file.name);
+                }
+            }
+            key
+        });
+    if LogLevel::of(args) >= LogLevel::Info {
+        eprintln!("opt-level: {}", value_name(OptLevel::of(args)));
+        eprintln!("debug info: {}", if args.debug_info { "yes" } else { "no" });
+        if matches!(args.emit, Emit::Link) && !args.jit {
+            eprintln!("strip: {}", if strips(args) { "yes" } else { "no" });
+        }
+    }
+    let started = std::time::Instant::now();
+    match args.backend {
+        #[cfg(feature = "llvm")]
+        Backend::Llvm => compile_with_llvm(
+            &renderer,
+            args,
+            &program,
+            &files,
+            &output,
+            cache_key,
+            &link_inputs,
+        ),
+        #[cfg(feature = "cranelift")]
+        Backend::Cranelift => compile_with_cranelift(
+            &renderer,
+            args,
+            &program,
+            &files,
+            &output,
+            cache_key,
+            &link_inputs,
+        ),
+        #[allow(unreachable_patterns)]
+        backend => {
+            let name = backend.to_possible_value().unwrap();
+            let message = format!(
+                "this compiler was built without the {} backend",
+                name.get_name()
+            );
+            eprintln!("{}", renderer.header(Severity::Error, None, &message));
+            eprintln!("help: rebuild it with `--features {}`", name.get_name());
+            process::exit(EXIT_USAGE);
+        }
+    }
+    trace(args, "generating the output", started);
+}
+
+/// Whether `path` is passed on to the linker rather than compiled: an object file or library,
+/// or anything else with an extension other than `.ratio`.
+fn is_link_input(path: &std::path::Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension != "ratio")
+}
+
+/// Splits the inputs on the command line into the program's source files and the files to link
+/// into it as they are, in the order given. Exits if there is no source file or a file to link
+/// is missing, so that the mistake is reported before compiling.
+fn split_inputs(
+    args: &Arguments,
+    inputs: &[std::path::PathBuf],
+) -> (Vec<std::path::PathBuf>, Vec<String>) {
+    let renderer = Renderer::new(&[], args.color.enabled());
+    let fail = |message: String, help: &str| -> ! {
+        eprintln!("{}", renderer.header(Severity::Error, None, &message));
+        eprintln!("help: {}", help);
+        process::exit(EXIT_USAGE);
+    };
+    let (link_inputs, source_paths): (Vec<_>, Vec<_>) =
+        inputs.iter().cloned().partition(|path| is_link_input(path));
+    if source_paths.is_empty() {
+        fail(
+            "no Ratio source file to compile".to_string(),
+            "source files have the `.ratio` extension or none at all",
+        );
+    }
+    if let Some(path) = link_inputs.first()
+        && (args.jit || !matches!(args.emit, Emit::Link))
+    {
+        fail(
+            format!("`{}` can only be linked into an executable", path.display()),
+            "leave out --jit and --emit to link it",
+        );
+    }
+    if let Some(path) = link_inputs.iter().find(|path| !path.is_file()) {
+        fail(
+            format!("cannot find `{}` to link", path.display()),
+            "inputs with an extension other than `.ratio` are passed on to the linker",
+        );
+    }
+    let link_inputs = link_inputs
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    (source_paths, link_inputs)
+}
+
+/// Compiles the project that the manifest in the current directory describes, then exits. The
+/// options on the command line are merged over the manifest's: lists such as `-l` add to its
+/// own, and `-O` or `--release` replace its optimization level.
+fn build(args: &Arguments, output: Option<&str>) -> ! {
+    let path = std::path::Path::new(manifest::MANIFEST_NAME);
+    let text = match file_io::read_file(path) {
+        Ok(text) => text,
+        Err(error) => {
+            let renderer = Renderer::new(&[], args.color.enabled());
+            if error.kind() != std::io::ErrorKind::NotFound {
+                read_failed(&renderer, path, error);
+            }
+            let message = format!("no `{}` in the current directory", manifest::MANIFEST_NAME);
+            eprintln!("{}", renderer.header(Severity::Error, None, &message));
+            eprintln!("help: run `build` in the project's directory, or create a manifest there");
+            eprintln!("note: a manifest needs at least the project's name: `name = \"hello\"`");
+            process::exit(EXIT_USAGE);
+        }
+    };
+    let files = [SourceFile {
+        name: manifest::MANIFEST_NAME.to_string(),
+        text,
+    }];
+    let renderer = Renderer::new(&files, args.color.enabled());
+    let project = manifest::parse(&files[0].text, FileId(0), std::path::Path::new(""))
+        .unwrap_or_else(|error| {
+            report_errors(&renderer, vec![error]);
+            process::exit(EXIT_USAGE);
+        });
+
+    let mut args = args.clone();
+    if args.opt_level.is_none() && !args.release {
+        args.opt_level = project
+            .opt_level
+            .and_then(|level| OptLevel::value_variants().get(usize::from(level)).copied());
+    }
+    args.libraries = [project.libraries, args.libraries].concat();
+    args.library_dirs = [project.library_dirs, args.library_dirs].concat();
+    args.link_args = [project.link_args, args.link_args].concat();
+    let output = match (output, project.output) {
+        (Some(output), _) => output.to_string(),
+        (None, Some(output)) => output.display().to_string(),
+        (None, None) => match executable_extension(&args) {
+            "" => project.name,
+            extension => format!("{}.{}", project.name, extension),
+        },
+    };
+    compile(&args, &project.source_paths, || output);
+    process::exit(0);
+}
+
+/// Lexes, parses and analyzes the program in `source_paths` without generating code, then exits
+/// with 1 if there were errors and 0 otherwise.
+fn check(args: &Arguments, source_paths: &[std::path::PathBuf]) -> ! {
+    let (files, program) = load_program(args, source_paths);
+    let renderer = Renderer::new(&files, args.color.enabled());
+    let program = program.unwrap_or_else(|errors| {
+        report_errors(&renderer, errors);
+        process::exit(EXIT_ERRORS);
+    });
+    analyze(&renderer, args, &program, &files);
+    process::exit(0);
+}
+
+/// Parses the program at `source_path` and prints it in the canonical style. With `check` it
+/// instead exits with 1 if that differs from the file, and with `write` it replaces the file.
+fn fmt(args: &Arguments, source_path: &std::path::Path, check: bool, write: bool) -> ! {
+    if write && file_io::is_stdin(source_path) {
+        let renderer = Renderer::new(&[], args.color.enabled());
+        let message = "`--write` cannot replace a program read from stdin";
+        eprintln!("{}", renderer.header(Severity::Error, None, message));
+        eprintln!("help: leave out `--write` to print the formatted program instead");
+        process::exit(EXIT_USAGE);
+    }
+    let original = file_io::read_source(source_path).unwrap_or_else(|error| {
+        read_failed(
+            &Renderer::new(&[], args.color.enabled()),
+            source_path,
+            error,
+        )
+    });
+    let files = [SourceFile {
+        name: file_io::source_name(source_path),
+        text: original.trim_end().to_string(),
+    }];
+    let renderer = Renderer::new(&files, args.color.enabled());
+
+    // Only this file is formatted, so its imports are left unread.
+    let program = loader::lex(&files[0].text, FileId(0))
+        .and_then(|tokens| loader::parse(tokens, FileId(0)))
+        .unwrap_or_else(|errors| {
+            report_errors(&renderer, errors);
+            process::exit(EXIT_ERRORS);
+        });
+    if !lexer::scan_comments(&files[0].text).is_empty() {
+        let message = "formatting would remove the comments in this file";
+        eprintln!("{}", renderer.header(Severity::Error, None, message));
+        eprintln!("note: the formatter cannot keep comments yet");
+        process::exit(EXIT_ERRORS);
+    }
+    let formatted = formatter::format_program(&program);
+    if check {
+        if formatted != original {
+            eprintln!("`{}` would be reformatted", files[0].name);
+            process::exit(EXIT_ERRORS);
+        }
+    } else if write {
+        if formatted != original
+            && let Err(error) = file_io::write_file(source_path, &formatted)
+        {
+            write_failed(&renderer, &files[0].name, error);
+        }
+    } else {
+        print_stdout(&renderer, &formatted);
+    }
+    process::exit(0);
+}
+
+/// Removes the cached object files, and exits.
+fn clean(args: &Arguments) -> ! {
+    if let Err(error) = cache::clean() {
+        let message = format!("cannot remove `{}`: {}", cache::dir().display(), error);
+        let renderer = Renderer::new(&[], args.color.enabled());
+        eprintln!("{}", renderer.header(Severity::Error, None, &message));
+        process::exit(EXIT_USAGE);
+    }
+    if LogLevel::of(args) >= LogLevel::Info {
+        eprintln!("removed `{}`", cache::dir().display());
+    }
+    process::exit(0);
+}
+
+/// The name of an option's value, as it is written on the command line.
+fn value_name(value: impl ValueEnum) -> String {
+    value.to_possible_value().unwrap().get_name().to_string()
+}
+
+/// Runs the tests in `dir` with this compiler, passing on the code generation options, and exits
+/// with 1 if any failed.
+fn test(args: &Arguments, dir: &std::path::Path) -> ! {
+    let mut compiler_args = vec![
+        "--color".to_string(),
+        "never".to_string(),
+        "--backend".to_string(),
+        value_name(args.backend),
+        "-O".to_string(),
+        value_name(OptLevel::of(args)),
+        "--reloc".to_string(),
+        value_name(args.reloc),
+        "--mcpu".to_string(),
+        args.target.mcpu.clone(),
+        "--mattr".to_string(),
+        args.target.mattr.clone(),
+    ];
+    if args.debug_info {
+        compiler_args.push("-g".to_string());
+    }
+    if let Some(linking) = args.link {
+        compiler_args.extend(["--link".to_string(), value_name(linking)]);
+    }
+    if let Some(linker) = &args.linker {
+        compiler_args.extend(["--linker".to_string(), linker.clone()]);
+    }
+    for dir in &args.library_dirs {
+        compiler_args.extend(["-L".to_string(), dir.display().to_string()]);
+    }
+    for library in &args.libraries {
+        compiler_args.extend(["-l".to_string(), library.clone()]);
+    }
+    for arg in &args.link_args {
+        compiler_args.push(format!("--link-arg={}", arg));
+    }
+    for define in &args.defines {
+        compiler_args.push(format!("--define={}", define));
+    }
+    for option in &args.warnings {
+        compiler_args.push(format!("-W{}", option));
+    }
+    if args.no_warnings {
+        compiler_args.push("-w".to_string());
+    }
+
+    let renderer = Renderer::new(&[], args.color.enabled());
+    let compiler = std::env::current_exe().unwrap_or_else(|error| {
+        let message = format!("cannot find the compiler's own executable: {}", error);
+        eprintln!("{}", renderer.header(Severity::Error, None, &message));
+        process::exit(EXIT_TOOLCHAIN);
+    });
+    match test_runner::run_tests(dir, &compiler, &compiler_args) {
+        Ok(true) => process::exit(0),
+        Ok(false) => process::exit(EXIT_ERRORS),
+        Err(message) => {
+            eprintln!("{}", renderer.header(Severity::Error, None, &message));
+            process::exit(EXIT_USAGE);
+        }
+    }
+}
+
+/// Loads the program from `source_paths` and the files they import, exiting if one of
+/// `source_paths` cannot be read.
+fn load_program(args: &Arguments, source_paths: &[std::path::PathBuf]) -> loader::LoadedProgram {
+    loader::load_program(source_paths, defines_file(args)).unwrap_or_else(|unreadable| {
+        let renderer = Renderer::new(&[], args.color.enabled());
+        read_failed(&renderer, &unreadable.path, unreadable.error)
+    })
+}
+
+/// The constants given with `--define`, declared in the text of a source file, or `None` if
+/// there are none. Exits if one is not a name and a value that is an integer or a bool.
+fn defines_file(args: &Arguments) -> Option<SourceFile> {
+    if args.defines.is_empty() {
+        return None;
+    }
+    let mut text = String::new();
+    for define in &args.defines {
+        let fail = |message: &str| -> ! {
+            let renderer = Renderer::new(&[], args.color.enabled());
+            let message = format!("invalid `--define {}`: {}", define, message);
+            eprintln!("{}", renderer.header(Severity::Error, None, &message));
+            eprintln!("help: define a constant with `--define NAME=VALUE`, such as `LIMIT=100`");
+            process::exit(EXIT_USAGE);
+        };
+        let Some((name, value)) = define.split_once('=') else {
+            fail("there is no `=` between the name and the value");
+        };
+        let valid_name = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !valid_name {
+            fail(&format!("`{}` is not a valid name", name));
+        }
+        // There is no unary minus, so a negative value is written as a subtraction.
+        let (ty, value) = match value {
+            "true" | "false" => ("bool", value.to_string()),
+            _ => match value.parse::<i64>() {
+                Ok(value) if value < 0 => ("int", format!("0 - {}", value.unsigned_abs())),
+                Ok(value) => ("int", value.to_string()),
+                Err(_) => fail("the value must be an integer, `true` or `false`"),
+            },
+        };
+        text.push_str(&format!("const {}: {} = {};\n", name, ty, value));
+    }
+    Some(SourceFile {
+        name: DEFINES_FILE.to_string(),
+        text,
+    })
+}
+
+/// Reads the source files of the program, exiting if one cannot be read.
+fn read_sources(args: &Arguments, source_paths: &[std::path::PathBuf]) -> Vec<SourceFile> {
+    source_paths
+        .iter()
+        .map(|path| {
+            let text = file_io::read_source(path).unwrap_or_else(|error| {
+                read_failed(&Renderer::new(&[], args.color.enabled()), path, error)
+            });
+            SourceFile {
+                name: file_io::source_name(path),
+                text: text.trim_end().to_string(),
+            }
+        })
+        .collect()
+}
 
-    let mut lexer = Lexer::new(input);
-    let mut tokens: Vec<(Token, Position)> = Vec::new();
-    loop {
-        let (token, pos) = lexer.next_token();
-        if token == Token::EOF {
-            break;
+/// Runs the semantic checks and lowers the program for a backend, printing the warnings that
+/// `-W` and `-w` leave on. Exits after reporting any errors, including warnings made errors.
+fn analyze(
+    renderer: &Renderer,
+    args: &Arguments,
+    program: &common::Program,
+    files: &[SourceFile],
+) -> hir::Program {
+    let source_names: Vec<&str> = files
+        .iter()
+        .map(|file| file.name.as_str())
+        .filter(|name| *name != DEFINES_FILE)
+        .collect();
+    let analysis =
+        semantics::Analyzer::new(program, &source_names.join(", "), &args.entry).analyze();
+    let levels = lint_levels(args);
+    let mut denied = 0;
+    for warning in &analysis.warnings {
+        let severity = match levels.level(warning.lint) {
+            Level::Allow => continue,
+            Level::Warn => Severity::Warning,
+            Level::Deny => {
+                denied += 1;
+                Severity::Error
+            }
+        };
+        eprintln!("{}\n", renderer.warning(warning, severity));
+    }
+    // Lowering runs even when analysis failed, so that unresolved names are reported together
+    // with the other errors.
+    let mut errors = analysis.errors;
+    let program = match hir::lower::lower_program(program, &args.entry) {
+        Ok(program) => Some(program),
+        Err(lowering_errors) => {
+            errors.extend(lowering_errors);
+            None
+        }
+    };
+    match program {
+        Some(program) if errors.is_empty() && denied == 0 => program,
+        _ => {
+            report_errors(renderer, errors);
+            std::process::exit(EXIT_ERRORS);
         }
-        tokens.push((token, pos));
     }
-    let mut parser = parser::Parser::new(tokens);
-    let program = match parser.parse() {
-        Ok(program) => program,
-        Err(e) => {
-            eprintln!("E: {}", e);
-            std::process::exit(1);
+}
+
+/// The level of each lint after the `-W` options, in order, and then `-w`. Exits if an option
+/// names a lint that does not exist.
+fn lint_levels(args: &Arguments) -> LintLevels {
+    let mut levels = LintLevels::default();
+    for option in &args.warnings {
+        if let Err(name) = levels.apply(option) {
+            let renderer = Renderer::new(&[], args.color.enabled());
+            let message = format!("unknown lint `{}` in `-W{}`", name, option);
+            eprintln!("{}", renderer.header(Severity::Error, None, &message));
+            let names = Lint::ALL.map(|lint| lint.name());
+            if let Some(similar) = diagnostics::closest_match(&name, names) {
+                eprintln!("help: a lint with a similar name exists: `{}`", similar);
+            }
+            eprintln!("note: the lints are {}", names.join(", "));
+            process::exit(EXIT_USAGE);
+        }
+    }
+    if args.no_warnings {
+        levels.allow_all();
+    }
+    levels
+}
+
+/// Compiles the program in `source_paths` into a new temporary directory and runs it with
+/// `program_args`, then removes the directory and exits with the program's status.
+fn run(args: &Arguments, source_paths: &[std::path::PathBuf], program_args: &[String]) -> ! {
+    // Registered as a temporary, so that a failed compile or link removes it before exiting.
+    let run_dir =
+        file_io::TempPath::new(std::env::temp_dir().join(format!("ratio-run-{}", process::id())));
+    let dir = run_dir.path();
+    let stem = source_paths
+        .iter()
+        .find(|path| !is_link_input(path))
+        .and_then(|path| path.file_stem())
+        .unwrap_or("program".as_ref());
+    let executable = dir.join(stem);
+    compile(args, source_paths, || {
+        if let Err(error) = std::fs::create_dir_all(dir) {
+            let renderer = Renderer::new(&[], args.color.enabled());
+            let message = format!("cannot create the directory `{}`: {}", dir.display(), error);
+            eprintln!("{}", renderer.header(Severity::Error, None, &message));
+            process::exit(EXIT_USAGE);
+        }
+        executable.display().to_string()
+    });
+    let status = process::Command::new(&executable)
+        .args(program_args)
+        .status();
+    drop(run_dir);
+    match status {
+        Ok(status) => process::exit(exit_code(status)),
+        Err(error) => {
+            let message = format!("cannot run `{}`: {}", source_paths[0].display(), error);
+            let renderer = Renderer::new(&[], args.color.enabled());
+            eprintln!("{}", renderer.header(Severity::Error, None, &message));
+            process::exit(EXIT_TOOLCHAIN);
+        }
+    }
+}
+
+/// The status to exit with to pass on how a child process ended. A process killed by a signal
+/// gets 128 plus the signal number, as in a shell.
+fn exit_code(status: process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(1)
+}
+
+/// Generates code with LLVM, an object file per source file, then links them. When there is no
+/// linker for the target, a single object file for the whole program is the output, and with
+/// `--emit llvm-ir` the IR for the whole program is written instead.
+#[cfg(feature = "llvm")]
+fn compile_with_llvm(
+    renderer: &Renderer,
+    args: &Arguments,
+    program: &hir::Program,
+    files: &[SourceFile],
+    output: &str,
+    cache_key: Option<cache::Key>,
+    link_inputs: &[String],
+) {
+    reject_unsupported_builtins(renderer, program, "llvm");
+    inkwell::targets::Target::initialize_all(&inkwell::targets::InitializationConfig::default());
+    let target_triple = target_triple(&args.target);
+    let triple_name = target_triple.as_str().to_string_lossy().into_owned();
+    let wasm = triple_name.starts_with("wasm");
+    // Printing goes through printf, and on WebAssembly only WASI provides a C library for it.
+    if wasm && !triple_name.contains("wasi") {
+        let message = format!(
+            "`{}` has no C library, so Ratio programs cannot print on it",
+            triple_name
+        );
+        eprintln!("{}", renderer.header(Severity::Error, None, &message));
+        eprintln!("help: compile for `wasm32-wasi` to run under a WASI runtime such as wasmtime");
+        process::exit(EXIT_USAGE);
+    }
+    let target_machine = create_target_machine(
+        renderer,
+        &target_triple,
+        &args.target,
+        OptLevel::of(args),
+        args.reloc,
+    );
+    if LogLevel::of(args) >= LogLevel::Info {
+        eprintln!("target: {}", target_triple);
+        eprintln!("cpu: {}", target_machine.get_cpu());
+        eprintln!(
+            "features: {}",
+            target_machine.get_feature_string().to_string_lossy()
+        );
+    }
+    let linker = target_linker(args, &target_triple);
+    let link = |linker: &str, objects: &[String]| {
+        let mut link = process::Command::new(linker);
+        if wasm {
+            // There is no static or shared C library to choose between; wasi-libc comes from the
+            // sysroot, which WASI_SYSROOT points to when clang was not installed with one.
+            link.arg(format!("--target={}", triple_name));
+            if let Some(sysroot) = std::env::var_os("WASI_SYSROOT") {
+                link.arg("--sysroot").arg(sysroot);
+            }
+        } else {
+            link.args(c_library_args(args, linker));
         }
+        run_linker(renderer, args, program, link, objects, output);
     };
 
     let context = Context::create();
-    let module = context.create_module("main");
     let builder = context.create_builder();
-    llvm_codegen::generate_module(&context, &module, &builder, &program);
+    // Spans are in the files in the order they were loaded, imported files included.
+    let source_paths: Vec<std::path::PathBuf> =
+        files.iter().map(|file| file.name.clone().into()).collect();
+    let intermediates = intermediates_dir(args, output);
+    let generate = |unit: &Unit| {
+        let module = context.create_module("main");
+        module.set_triple(&target_triple);
+        module.set_data_layout(&target_machine.get_target_data().get_data_layout());
+        llvm_codegen::generate_module(
+            &context,
+            &module,
+            &builder,
+            program,
+            unit,
+            args.debug_info.then_some(&source_paths),
+            emits_c_main(args),
+        );
+        llvm_codegen::verify_module(&module);
 
-    inkwell::targets::Target::initialize_all(&inkwell::targets::InitializationConfig::default());
-    let target_triple = inkwell::targets::TargetMachine::get_default_triple();
-    let target =
-        inkwell::targets::Target::from_triple(&target_triple).expect("Failed to get target");
-    let target_machine = target
-        .create_target_machine(
-            &target_triple,
-            "generic",
-            "",
-            inkwell::OptimizationLevel::Default,
-            inkwell::targets::RelocMode::Default,
-            inkwell::targets::CodeModel::Default,
-        )
-        .expect("Failed to create target machine");
-    module.set_triple(&target_triple);
-    module.set_data_layout(&target_machine.get_target_data().get_data_layout());
+        // Every variable is emitted as a stack slot. From -O1 up, the pipeline promotes the
+        // slots to SSA registers, so that a loop runs without a load and a store for each
+        // variable access.
+        let passes = module.run_passes(
+            OptLevel::of(args).pipeline(),
+            &target_machine,
+            PassBuilderOptions::create(),
+        );
+        if let Err(error) = passes {
+            let message = format!("LLVM cannot optimize the program: {}", error);
+            eprintln!("{}", renderer.header(Severity::Error, None, &message));
+            file_io::remove_temporaries();
+            process::exit(EXIT_TOOLCHAIN);
+        }
+        if let (Some(dir), Some(file)) = (&intermediates, unit.file_id()) {
+            let path = intermediate_path(dir, files, file, "ll");
+            if let Err(error) = module.print_to_file(&path) {
+                write_failed(
+                    renderer,
+                    &path.display().to_string(),
+                    error.to_string_lossy(),
+                );
+            }
+            if LogLevel::of(args) >= LogLevel::Info {
+                eprintln!("kept `{}`", path.display());
+            }
+        }
+        module
+    };
+
+    if args.jit {
+        let module = generate(&Unit::whole());
+        let report = |message: String| {
+            let message = format!("cannot run the program with --jit: {}", message);
+            eprintln!("{}", renderer.header(Severity::Error, None, &message));
+        };
+        match llvm_codegen::jit::run_main(&module, OptLevel::of(args).codegen_level()) {
+            Ok(status) => process::exit(status),
+            Err(JitError::MissingSymbol(name)) => {
+                report(format!("`{}` is not defined in this process", name));
+                eprintln!(
+                    "help: compile it to an executable to link functions from other libraries"
+                );
+            }
+            Err(JitError::Engine(message)) => report(message),
+        }
+        process::exit(EXIT_TOOLCHAIN);
+    }
 
-    let obj_path = "/tmp/output.o";
+    if let Emit::LlvmIr = args.emit {
+        let module = generate(&Unit::whole());
+        write_output(renderer, output, |path| {
+            std::fs::write(path, module.print_to_string().to_string())
+                .map_err(|error| error.to_string())
+        });
+        return;
+    }
+    if let Emit::Obj = args.emit {
+        let module = generate(&Unit::whole());
+        write_output(renderer, output, |path| {
+            write_object(&target_machine, &module, path)
+        });
+        return;
+    }
+
+    let Some(linker) = linker else {
+        let module = generate(&Unit::whole());
+        write_output(renderer, output, |path| {
+            write_object(&target_machine, &module, path)
+        });
+        eprintln!(
+            "note: wrote an object file for `{}` to `{}` without linking it; pass --cc to \
+             link it with a linker for that target",
+            target_triple, output
+        );
+        return;
+    };
+    let cache_key = cache_key.map(|key| {
+        key.with(&triple_name)
+            .with(&target_machine.get_cpu().to_string())
+            .with(&target_machine.get_feature_string().to_string_lossy())
+    });
+    let _temporary = file_io::TempPath::new(objects_dir());
+    let mut objects = build_objects(
+        renderer,
+        args,
+        program,
+        files,
+        cache_key,
+        intermediates.as_deref(),
+        |unit, path| {
+            write_object(&target_machine, &generate(unit), path)
+                .unwrap_or_else(|error| write_failed(renderer, path, error));
+        },
+    );
+    objects.extend_from_slice(link_inputs);
+    link(&linker, &objects);
+}
+
+/// Generates code for the host with Cranelift, an object file per source file, and links them.
+/// Options that only LLVM implements are rejected rather than ignored.
+#[cfg(feature = "cranelift")]
+fn compile_with_cranelift(
+    renderer: &Renderer,
+    args: &Arguments,
+    program: &hir::Program,
+    files: &[SourceFile],
+    output: &str,
+    cache_key: Option<cache::Key>,
+    link_inputs: &[String],
+) {
+    let fail = |message: &str, status: i32| -> ! {
+        eprintln!("{}", renderer.header(Severity::Error, None, message));
+        file_io::remove_temporaries();
+        process::exit(status);
+    };
+    let unsupported = if args.debug_info {
+        Some("-g")
+    } else if args.target.triple.is_some() {
+        Some("--target")
+    } else if args.target.mcpu != "generic" {
+        Some("--mcpu")
+    } else if !args.target.mattr.is_empty() {
+        Some("--mattr")
+    } else if let Emit::LlvmIr = args.emit {
+        Some("--emit llvm-ir")
+    } else if args.jit {
+        Some("--jit")
+    } else {
+        None
+    };
+    if let Some(option) = unsupported {
+        let message = format!("`{}` is not supported by the cranelift backend", option);
+        fail(&message, EXIT_USAGE);
+    }
+    reject_unsupported_builtins(renderer, program, "cranelift");
+    if LogLevel::of(args) >= LogLevel::Info {
+        eprintln!("backend: cranelift, for the host");
+    }
+    let options = cranelift_codegen::Options {
+        opt_level: OptLevel::of(args).cranelift_level(),
+        pic: matches!(args.reloc, Relocation::Pic),
+        c_main: emits_c_main(args),
+    };
+    if let Emit::Obj = args.emit {
+        let object = cranelift_codegen::generate_object(program, &Unit::whole(), &options)
+            .unwrap_or_else(|message| fail(&message, EXIT_TOOLCHAIN));
+        write_output(renderer, output, |path| {
+            std::fs::write(path, object).map_err(|error| error.to_string())
+        });
+        return;
+    }
+    let cache_key = cache_key.map(|key| key.with(std::env::consts::ARCH));
+    let intermediates = intermediates_dir(args, output);
+    let _temporary = file_io::TempPath::new(objects_dir());
+    let mut objects = build_objects(
+        renderer,
+        args,
+        program,
+        files,
+        cache_key,
+        intermediates.as_deref(),
+        |unit, path| {
+            let object = cranelift_codegen::generate_object(program, unit, &options)
+                .unwrap_or_else(|message| fail(&message, EXIT_TOOLCHAIN));
+            if let Err(error) = std::fs::write(path, object) {
+                write_failed(renderer, path, error);
+            }
+        },
+    );
+    objects.extend_from_slice(link_inputs);
+    let linker = host_linker(args);
+    let mut link = process::Command::new(&linker);
+    link.args(c_library_args(args, &linker));
+    run_linker(renderer, args, program, link, &objects, output);
+}
+
+/// Exits if the program calls a builtin that `backend` cannot generate code for yet, which is
+/// only `input`.
+fn reject_unsupported_builtins(renderer: &Renderer, program: &hir::Program, backend: &str) {
+    if let Some(span) = program.first_call_to(Builtin::Input) {
+        let message = format!("`input` is not supported by the {} backend", backend);
+        report_errors(renderer, vec![CompileError::new(message, span)]);
+        process::exit(EXIT_ERRORS);
+    }
+}
+
+/// Whether the object for the entry point has a C main calling it. It has unless the entry point
+/// is not `main` and the object is written with `--emit obj`, to link into a C program with a
+/// main of its own.
+fn emits_c_main(args: &Arguments) -> bool {
+    args.entry == "main" || !matches!(args.emit, Emit::Obj)
+}
+
+/// Produces an object file for each source file that defines functions, and returns their paths
+/// in order. An object in the cache is reused; otherwise `generate` writes the object for a unit
+/// to the path it is given. Each object's key is `cache_key` with its source file's text and the
+/// program's interface added, so an edit only regenerates the objects it can change. Objects
+/// are written to `intermediates` when it is given, to be kept, and otherwise to `objects_dir`,
+/// which the caller removes once they are linked.
+fn build_objects(
+    renderer: &Renderer,
+    args: &Arguments,
+    program: &hir::Program,
+    files: &[SourceFile],
+    cache_key: Option<cache::Key>,
+    intermediates: Option<&std::path::Path>,
+    mut generate: impl FnMut(&Unit, &str),
+) -> Vec<String> {
+    let temporary_dir = objects_dir();
+    let dir = intermediates.unwrap_or(&temporary_dir);
+    if !dir.as_os_str().is_empty()
+        && let Err(error) = std::fs::create_dir_all(dir)
+    {
+        let message = format!("cannot create the directory `{}`: {}", dir.display(), error);
+        eprintln!("{}", renderer.header(Severity::Error, None, &message));
+        process::exit(EXIT_USAGE);
+    }
+    let interface = hir::units::interface(program);
+    hir::units::files(program)
+        .into_iter()
+        .map(|file| {
+            let cached = cache_key
+                .clone()
+                .and_then(|key| key.with(&files[file.0].text).with(&interface).object_path());
+            if let Some(path) = &cached
+                && path.exists()
+            {
+                if LogLevel::of(args) >= LogLevel::Info {
+                    eprintln!("cache: reusing `{}`", path.display());
+                }
+                return path.display().to_string();
+            }
+            let Some(dir) = intermediates else {
+                let temporary = temporary_dir.join(format!("{}.o", file.0));
+                let temporary = temporary.display().to_string();
+                return store_object(renderer, args, cached.as_deref(), &temporary, |path| {
+                    generate(&Unit::file(program, file), path)
+                });
+            };
+            let path = intermediate_path(dir, files, file, "o")
+                .display()
+                .to_string();
+            generate(&Unit::file(program, file), &path);
+            if LogLevel::of(args) >= LogLevel::Info {
+                eprintln!("kept `{}`", path);
+            }
+            path
+        })
+        .collect()
+}
+
+/// Where `--keep-intermediates` keeps the files generated on the way to the output: the
+/// directory it names, or else the output's.
+fn intermediates_dir(args: &Arguments, output: &str) -> Option<std::path::PathBuf> {
+    let dir = match args.keep_intermediates.as_ref()? {
+        Some(dir) => dir.clone(),
+        None => std::path::Path::new(output)
+            .parent()
+            .map(std::path::Path::to_path_buf)
+            .unwrap_or_default(),
+    };
+    Some(dir)
+}
+
+/// Where a file generated from the source file `file` is kept in `dir`: named after the source
+/// file, with `extension`. Source files of the same name in different directories are told
+/// apart by adding their `FileId`.
+fn intermediate_path(
+    dir: &std::path::Path,
+    files: &[SourceFile],
+    file: FileId,
+    extension: &str,
+) -> std::path::PathBuf {
+    let stem = |name: &str| {
+        std::path::Path::new(name)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    let name = stem(&files[file.0].name);
+    let shared = files
+        .iter()
+        .filter(|other| stem(&other.name) == name)
+        .count()
+        > 1;
+    let name = if shared {
+        format!("{}-{}", name, file.0)
+    } else {
+        name
+    };
+    dir.join(name).with_extension(extension)
+}
+
+/// Writes the object file with `write` and returns its path: `cached` when the object is to be
+/// cached, and `temporary` otherwise. A cached object is written under another name first and
+/// then renamed, so that an interrupted build never leaves a partial object to be reused.
+fn store_object(
+    renderer: &Renderer,
+    args: &Arguments,
+    cached: Option<&std::path::Path>,
+    temporary: &str,
+    write: impl FnOnce(&str),
+) -> String {
+    let Some(cached) = cached else {
+        write(temporary);
+        return temporary.to_string();
+    };
+    let partial = cached.with_extension("partial");
+    write(&partial.display().to_string());
+    if let Err(error) = std::fs::rename(&partial, cached) {
+        write_failed(renderer, &cached.display().to_string(), error);
+    }
+    if LogLevel::of(args) >= LogLevel::Info {
+        eprintln!("cache: stored `{}`", cached.display());
+    }
+    cached.display().to_string()
+}
+
+/// The directory for this compile's temporary object files. It is named after the process, so
+/// that compiles running at the same time each have their own, and is in the temporary directory
+/// that `TMPDIR` names, if set.
+fn objects_dir() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("ratio-build-{}", process::id()))
+}
+
+/// Whether the executable's symbols are stripped: with `--release`, unless `-g` asks for debug
+/// information, which a debugger needs the symbols for.
+fn strips(args: &Arguments) -> bool {
+    args.release && !args.debug_info
+}
+
+/// Whether `linker` is an MSVC-style linker, `link.exe` or LLVM's `lld-link`, which takes
+/// `/OUT:` and the like rather than a C compiler's flags.
+fn msvc_linker(linker: &std::ffi::OsStr) -> bool {
+    std::path::Path::new(linker)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|stem| stem == "link" || stem == "lld-link")
+}
+
+/// The flags that choose how `linker` links the C library into an executable for the target.
+fn c_library_args(args: &Arguments, linker: &str) -> &'static [&'static str] {
+    let linking = Linking::of(args);
+    if msvc_linker(linker.as_ref()) {
+        linking.msvc_libraries()
+    } else {
+        linking.link_args(args.reloc, Platform::of(args))
+    }
+}
+
+/// Exits if the executable cannot be linked as `--link` asks: Apple's systems have no static C
+/// library to link.
+fn check_linking(args: &Arguments) {
+    let linking_static = matches!(Linking::of(args), Linking::Static);
+    if matches!(args.emit, Emit::Link) && linking_static && Platform::of(args) == Platform::Apple {
+        let renderer = Renderer::new(&[], args.color.enabled());
+        let message = "executables for Apple's systems cannot link the C library statically";
+        eprintln!("{}", renderer.header(Severity::Error, None, message));
+        eprintln!("help: leave out `--link static` to link it dynamically");
+        process::exit(EXIT_USAGE);
+    }
+}
+
+/// The linker named with `--linker` or `--cc`, or else by RATIO_CC.
+fn configured_linker(args: &Arguments) -> Option<String> {
+    args.linker.clone().or_else(|| {
+        std::env::var("RATIO_CC")
+            .ok()
+            .filter(|linker| !linker.is_empty())
+    })
+}
+
+/// The C compilers looked for on PATH to link executables for the host, in order of preference.
+const HOST_LINKERS: [&str; 3] = ["cc", "gcc", "clang"];
+
+/// The C compiler that links executables for the host: the configured one, or else the first of
+/// `HOST_LINKERS` on PATH. When there is none, the first is tried anyway, and `run_linker`
+/// reports that none was found.
+fn host_linker(args: &Arguments) -> String {
+    configured_linker(args)
+        .or_else(|| find_on_path(&HOST_LINKERS))
+        .unwrap_or_else(|| HOST_LINKERS[0].to_string())
+}
+
+/// The first of `programs` that is on PATH.
+fn find_on_path(programs: &[&str]) -> Option<String> {
+    let on_path = |program: &str| {
+        let file = format!("{}{}", program, std::env::consts::EXE_SUFFIX);
+        std::env::var_os("PATH")
+            .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(&file).is_file()))
+    };
+    programs
+        .iter()
+        .find(|program| on_path(program))
+        .map(|program| program.to_string())
+}
+
+/// Runs `link`, with the object files, libraries, output and `--link-arg`s appended, and exits
+/// if it fails. The libraries come after the objects, since a static library is only searched
+/// for the symbols that the files before it leave undefined. The output is only replaced once
+/// linking has succeeded. With `--verbose`, the command is printed so that the link can be
+/// repeated by hand.
+///
+/// The linker's errors are captured, so that a failure can be reported with the command that
+/// was run and what it printed, and any symbol it could not find that the program declared as
+/// an extern is pointed out in the source.
+fn run_linker(
+    renderer: &Renderer,
+    args: &Arguments,
+    program: &hir::Program,
+    mut link: process::Command,
+    objects: &[String],
+    output: &str,
+) {
+    write_output(renderer, output, |path| {
+        link.args(objects);
+        if msvc_linker(link.get_program()) {
+            // A PE executable has no symbol table to strip; debug information only goes in a
+            // separate PDB.
+            link.arg("/NOLOGO");
+            for dir in &args.library_dirs {
+                link.arg(format!("/LIBPATH:{}", dir.display()));
+            }
+            for library in &args.libraries {
+                link.arg(format!("{}.lib", library));
+            }
+            link.arg(format!("/OUT:{}", path));
+        } else {
+            for dir in &args.library_dirs {
+                link.arg("-L").arg(dir);
+            }
+            for library in &args.libraries {
+                link.arg(format!("-l{}", library));
+            }
+            // Apple's linker has no `-s`; `-S` and `-x` drop the debug and local symbols, leaving
+            // the ones the dynamic loader needs.
+            if strips(args) && Platform::of(args) == Platform::Apple {
+                link.arg("-Wl,-S,-x");
+            } else if strips(args) {
+                link.arg("-s");
+            }
+            link.args(["-o", path]);
+        }
+        link.args(&args.link_args);
+        let command: Vec<String> = std::iter::once(link.get_program())
+            .chain(link.get_args())
+            .map(|part| part.to_string_lossy().into_owned())
+            .collect();
+        let command = command.join(" ");
+        if LogLevel::of(args) >= LogLevel::Info {
+            eprintln!("linking: {}", command);
+        }
+        link.stdout(process::Stdio::inherit());
+        let link_output = match link.output() {
+            Ok(link_output) => link_output,
+            Err(error) => {
+                let program = link.get_program().to_string_lossy();
+                let not_found = error.kind() == std::io::ErrorKind::NotFound;
+                let message =
+                    if not_found && configured_linker(args).is_none() && program == HOST_LINKERS[0]
+                    {
+                        format!(
+                            "no C compiler found to link with; looked for {} on PATH",
+                            HOST_LINKERS.join(", ")
+                        )
+                    } else {
+                        format!("cannot run the linker `{}`: {}", program, error)
+                    };
+                eprintln!("{}", renderer.header(Severity::Error, None, &message));
+                if not_found {
+                    eprintln!(
+                        "help: install a C compiler such as gcc or clang, or name one with --cc \
+                         or RATIO_CC"
+                    );
+                }
+                file_io::remove_temporaries();
+                process::exit(EXIT_TOOLCHAIN);
+            }
+        };
+        let stderr = String::from_utf8_lossy(&link_output.stderr);
+        if link_output.status.success() {
+            // Warnings, such as glibc's about static linking, are still worth seeing.
+            eprint!("{}", stderr);
+            return Ok(());
+        }
+        eprintln!(
+            "{}",
+            renderer.header(Severity::Error, None, "linking failed")
+        );
+        eprintln!("note: `{}` failed with {}", command, link_output.status);
+        for line in stderr.lines() {
+            eprintln!("    {}", line);
+        }
+        for symbol in undefined_symbols(&stderr) {
+            if let Some(ext) = program.externs.iter().find(|ext| ext.name == symbol) {
+                let error = CompileError::new(
+                    format!("no library defines the extern function `{}`", ext.name),
+                    ext.span,
+                )
+                .with_help("check the name, or link the library that defines it with `-l`");
+                eprintln!("{}", renderer.error(&error));
+            }
+        }
+        file_io::remove_temporaries();
+        process::exit(EXIT_TOOLCHAIN);
+    });
+}
+
+/// The symbols a linker reported as undefined in `stderr`, each once, in the order reported.
+/// GNU ld's `undefined reference to `name'`, lld's `undefined symbol: name` and link.exe's
+/// `unresolved external symbol name` are recognised.
+fn undefined_symbols(stderr: &str) -> Vec<&str> {
+    let mut symbols: Vec<&str> = Vec::new();
+    for line in stderr.lines() {
+        let symbol = if let Some((_, rest)) = line.split_once("undefined reference to `") {
+            rest.split('\'').next()
+        } else if let Some((_, rest)) = line.split_once("unresolved external symbol ") {
+            rest.split_whitespace().next()
+        } else {
+            line.split_once("undefined symbol: ")
+                .map(|(_, rest)| rest.trim())
+        };
+        if let Some(symbol) = symbol
+            && !symbols.contains(&symbol)
+        {
+            symbols.push(symbol);
+        }
+    }
+    symbols
+}
+
+#[cfg(feature = "llvm")]
+fn write_object(
+    target_machine: &TargetMachine,
+    module: &inkwell::module::Module,
+    path: &str,
+) -> Result<(), String> {
     target_machine
         .write_to_file(
-            &module,
+            module,
             inkwell::targets::FileType::Object,
-            std::path::Path::new(obj_path),
+            std::path::Path::new(path),
         )
-        .expect("Failed to write object file");
-
-    let gcc_status = process::Command::new("gcc")
-        .args(["-static", obj_path, "-o", &args.output])
-        .status()
-        .expect("Failed to execute gcc");
-    if !gcc_status.success() {
-        eprintln!("gcc failed");
-        std::process::exit(1);
+        .map_err(|error| error.to_string_lossy().into_owned())
+}
+
+/// The target given with `--target`, or the host, in LLVM's normal form.
+#[cfg(feature = "llvm")]
+fn target_triple(options: &TargetOptions) -> TargetTriple {
+    TargetMachine::normalize_triple(&match &options.triple {
+        Some(triple) => TargetTriple::create(triple),
+        None => TargetMachine::get_default_triple(),
+    })
+}
+
+/// The linker for executables on `triple`: the configured one, or else the host's C compiler for
+/// the host and clang for WebAssembly, which it links for any host. Windows is linked with
+/// MinGW's gcc for the GNU ABI and with clang, lld-link or link.exe for MSVC's, whichever is
+/// found first; from another host only MinGW's cross gcc and lld-link can. Other targets have
+/// none, since the host's C compiler cannot link for them, and their object file is the output
+/// instead.
+#[cfg(feature = "llvm")]
+fn target_linker(args: &Arguments, triple: &TargetTriple) -> Option<String> {
+    let cross_compiling =
+        *triple != TargetMachine::normalize_triple(&TargetMachine::get_default_triple());
+    let name = triple.as_str().to_string_lossy();
+    let arch = name.split('-').next().unwrap_or_default();
+    if let Some(linker) = configured_linker(args) {
+        Some(linker)
+    } else if name.starts_with("wasm") {
+        Some("clang".to_string())
+    } else if name.contains("windows-msvc") && cross_compiling {
+        find_on_path(&["lld-link"])
+    } else if name.contains("windows-msvc") {
+        find_on_path(&["clang", "lld-link", "link"])
+    } else if name.contains("windows-gnu") && cross_compiling {
+        find_on_path(&[&format!("{}-w64-mingw32-gcc", arch)])
+    } else if cross_compiling {
+        None
+    } else {
+        Some(host_linker(args))
+    }
+}
+
+/// Creates the machine that code is generated for, reporting an unusable target as an error.
+#[cfg(feature = "llvm")]
+fn create_target_machine(
+    renderer: &Renderer,
+    triple: &TargetTriple,
+    options: &TargetOptions,
+    opt_level: OptLevel,
+    reloc: Relocation,
+) -> TargetMachine {
+    let fail = |message: String| -> ! {
+        eprintln!("{}", renderer.header(Severity::Error, None, &message));
+        process::exit(EXIT_USAGE);
+    };
+    for feature in options
+        .mattr
+        .split(',')
+        .filter(|feature| !feature.is_empty())
+    {
+        if !feature.starts_with(['+', '-']) {
+            fail(format!(
+                "target feature `{}` must start with `+` to enable it or `-` to disable it",
+                feature
+            ));
+        }
+    }
+    // `native` stands for both the host CPU and the features it supports. Features given with
+    // --mattr come last, so they override the detected ones.
+    let (cpu, features) = if options.mcpu == "native" {
+        let host_features = TargetMachine::get_host_cpu_features().to_string();
+        let features = [host_features.as_str(), options.mattr.as_str()]
+            .into_iter()
+            .filter(|features| !features.is_empty())
+            .collect::<Vec<_>>()
+            .join(",");
+        (TargetMachine::get_host_cpu_name().to_string(), features)
+    } else {
+        (options.mcpu.clone(), options.mattr.clone())
+    };
+
+    let target = Target::from_triple(triple).unwrap_or_else(|message| {
+        eprintln!(
+            "{}",
+            renderer.header(
+                Severity::Error,
+                None,
+                &format!("unsupported target `{}`: {}", triple, message)
+            )
+        );
+        let architectures: Vec<String> =
+            std::iter::successors(Target::get_first(), Target::get_next)
+                .map(|target| target.get_name().to_string_lossy().into_owned())
+                .collect();
+        eprintln!(
+            "note: the architectures this compiler supports are: {}",
+            architectures.join(", ")
+        );
+        process::exit(EXIT_USAGE);
+    });
+    target
+        .create_target_machine(
+            triple,
+            &cpu,
+            &features,
+            opt_level.codegen_level(),
+            reloc.mode(),
+            CodeModel::Default,
+        )
+        .unwrap_or_else(|| {
+            fail(format!(
+                "cannot generate code for cpu `{}` with features `{}` on `{}`",
+                cpu, features, triple
+            ))
+        })
+}
+
+/// Prints the explanation for an error code and exits. Unknown codes list the known ones.
+fn explain(renderer: &Renderer, code: &str) -> ! {
+    match ErrorCode::from_code(code) {
+        Some(error) => {
+            print_stdout(renderer, &format!("{}\n", error.explanation()));
+            process::exit(0);
+        }
+        None => {
+            let known: Vec<&str> = ErrorCode::ALL.iter().map(|error| error.code()).collect();
+            let message = format!("unknown error code `{}`", code);
+            eprintln!("{}", renderer.header(Severity::Error, None, &message));
+            eprintln!("note: the known error codes are {}", known.join(", "));
+            process::exit(EXIT_USAGE);
+        }
+    }
+}
+
+fn report_errors(renderer: &Renderer, mut errors: Vec<CompileError>) {
+    errors.sort_by_key(|e| e.span);
+    for e in errors.iter().take(MAX_REPORTED_ERRORS) {
+        eprintln!("{}\n", renderer.error(e));
+    }
+    if errors.len() > MAX_REPORTED_ERRORS {
+        let message = format!(
+            "{} additional errors suppressed",
+            errors.len() - MAX_REPORTED_ERRORS
+        );
+        eprintln!("{}", renderer.header(Severity::Error, None, &message));
     }
 }
@@ -0,0 +1,125 @@
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::common::{CompileError, FileId, Position, Span};
+
+/// The file `ratio build` reads the project from, in the directory it is run in.
+pub const MANIFEST_NAME: &str = "ratio.toml";
+
+/// The contents of `ratio.toml`, whose paths are relative to the directory it is in. Each field
+/// other than `name` is optional, and the README describes them.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Manifest {
+    name: String,
+    source: Option<Spanned<PathBuf>>,
+    output: Option<PathBuf>,
+    opt_level: Option<Spanned<u8>>,
+    #[serde(default)]
+    libraries: Vec<String>,
+    #[serde(default)]
+    library_dirs: Vec<PathBuf>,
+    #[serde(default)]
+    link_args: Vec<String>,
+}
+
+/// A project read from its manifest, with every path made relative to the current directory
+/// rather than the manifest's.
+pub struct Project {
+    pub name: String,
+    /// The source files to compile: the one the manifest names, or every `.ratio` file in the
+    /// directory it names, in order.
+    pub source_paths: Vec<PathBuf>,
+    /// Where to write the executable, if the manifest says.
+    pub output: Option<PathBuf>,
+    /// The optimization level, from 0 to 3.
+    pub opt_level: Option<u8>,
+    pub libraries: Vec<String>,
+    pub library_dirs: Vec<PathBuf>,
+    pub link_args: Vec<String>,
+}
+
+/// Reads the project from `text`, the manifest in `dir`. An error points into the manifest, whose
+/// diagnostics use `file`.
+pub fn parse(text: &str, file: FileId, dir: &Path) -> Result<Project, CompileError> {
+    let error_at = |range: Range<usize>, message: String| {
+        let span = Span::new(file, position(text, range.start), position(text, range.end));
+        CompileError::new(message, span)
+    };
+    let manifest: Manifest = toml::from_str(text).map_err(|error| {
+        // The parser explains some errors on a second line, such as what it expected.
+        let message = error.message().trim_end().replace('\n', ": ");
+        error_at(error.span().unwrap_or(0..0), message)
+    })?;
+
+    let opt_level = match manifest.opt_level {
+        Some(level) if *level.get_ref() > 3 => {
+            let message = format!("there is no optimization level {}", level.get_ref());
+            return Err(error_at(level.span(), message).with_help("choose a level from 0 to 3"));
+        }
+        level => level.map(Spanned::into_inner),
+    };
+
+    let (source, source_span) = match manifest.source {
+        Some(source) => (source.get_ref().clone(), Some(source.span())),
+        None => (PathBuf::from("src"), None),
+    };
+    let source_error = |message: String| match &source_span {
+        Some(span) => error_at(span.clone(), message),
+        None => error_at(0..0, message)
+            .with_help("name the program's source file or directory with `source = \"...\"`"),
+    };
+    let source = dir.join(source);
+    let source_paths = if source.is_dir() {
+        let mut paths = Vec::new();
+        let entries = std::fs::read_dir(&source).map_err(|error| {
+            source_error(format!("cannot read `{}`: {}", source.display(), error))
+        })?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "ratio")
+            {
+                paths.push(path);
+            }
+        }
+        if paths.is_empty() {
+            let message = format!("`{}` has no `.ratio` files", source.display());
+            return Err(source_error(message));
+        }
+        paths.sort();
+        paths
+    } else if source.is_file() {
+        vec![source]
+    } else {
+        return Err(source_error(format!("cannot find `{}`", source.display())));
+    };
+
+    Ok(Project {
+        name: manifest.name,
+        source_paths,
+        output: manifest.output.map(|output| dir.join(output)),
+        opt_level,
+        libraries: manifest.libraries,
+        library_dirs: manifest
+            .library_dirs
+            .iter()
+            .map(|library_dir| dir.join(library_dir))
+            .collect(),
+        link_args: manifest.link_args,
+    })
+}
+
+/// The line and column of the byte `offset` in `text`, counting characters as the lexer does.
+fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    Position::new(
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
@@ -4,6 +4,7 @@ use crate::{
 };
 
 pub fn parse_if_statement(parser: &mut Parser) -> Result<Stmt, CompileError> {
+    let start = parser.current_token.1;
     parser.expect_keyword(Keyword::If)?;
     let condition = expressions::parse_expression_until(
         parser,
@@ -22,26 +23,25 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Stmt, CompileError> {
         parser.advance();
         if matches!(parser.current_token.0, Token::Keyword(Keyword::If)) {
             // Recursively parse chained else if
-            let else_if_stmt = parse_if_statement(parser)?;
+            let else_if_stmt = parser.nested(parse_if_statement)?;
             else_body = Some(vec![else_if_stmt]);
         } else if matches!(parser.current_token.0, Token::LeftBrace) {
             // Only parse a block after else, not an expression
             else_body = Some(parser.parse_block()?);
         } else {
-            return Err(CompileError::new(
-                "Expected '{' after 'else'",
-                parser.current_token.1.clone(),
-            ));
+            return Err(parser.expected_one_of(&[Token::LeftBrace, Token::Keyword(Keyword::If)]));
         }
     }
     Ok(Stmt::IfStatement {
         condition,
         body,
         else_body,
+        span: parser.span_from(start),
     })
 }
 
 pub fn parse_while_statement(parser: &mut Parser) -> Result<Stmt, CompileError> {
+    let start = parser.current_token.1;
     parser.expect_keyword(Keyword::While)?;
     let condition = expressions::parse_expression_until(
         parser,
@@ -55,5 +55,9 @@ pub fn parse_while_statement(parser: &mut Parser) -> Result<Stmt, CompileError>
         ],
     )?;
     let body = parser.parse_block()?;
-    Ok(Stmt::While { condition, body })
+    Ok(Stmt::While {
+        condition,
+        body,
+        span: parser.span_from(start),
+    })
 }
@@ -1,8 +1,18 @@
 use crate::{
-    common::{Builtin, CompileError, Expr, Keyword, Position, Program, Stmt, Token},
+    common::{CompileError, Expr, Keyword, Token},
+    diagnostics::ErrorCode,
     parser::{Parser, functions},
 };
 
+const COMPARISON_OPERATORS: [Token; 6] = [
+    Token::Equality,
+    Token::NotEqual,
+    Token::LessThan,
+    Token::LessThanOrEqual,
+    Token::GreaterThan,
+    Token::GreaterThanOrEqual,
+];
+
 pub fn parse_expression(parser: &mut Parser) -> Result<Expr, CompileError> {
     parse_expression_until(
         parser,
@@ -17,129 +27,282 @@ pub fn parse_expression(parser: &mut Parser) -> Result<Expr, CompileError> {
     )
 }
 
+/// Parses an expression that ends at one of `stop_tokens`, or at anything else that cannot
+/// continue it. `*` and `/` bind more tightly than `+` and `-`, which bind more tightly than the
+/// comparisons, and operators of the same precedence group from the left, so `a - b * c < d`
+/// is `(a - (b * c)) < d`.
 pub fn parse_expression_until(
     parser: &mut Parser,
     stop_tokens: &[Token],
 ) -> Result<Expr, CompileError> {
-    let mut left = match &parser.current_token.0 {
+    let outermost = parser.operators.is_none();
+    if outermost {
+        parser.operators = Some(0);
+    }
+    let expr = parser.nested(|parser| parse_comparison(parser, stop_tokens));
+    if outermost {
+        parser.operators = None;
+    }
+    expr
+}
+
+/// Parses `a < b`, or a single sum if there is no comparison.
+fn parse_comparison(parser: &mut Parser, stop_tokens: &[Token]) -> Result<Expr, CompileError> {
+    let mut left = parse_sum(parser, stop_tokens)?;
+    if COMPARISON_OPERATORS.contains(&parser.current_token.0) {
+        let op = parser.current_token.0.clone();
+        let op_span = parser.current_token.1;
+        parser.count_operator()?;
+        parser.advance();
+        expect_operand(parser, &op, stop_tokens)?;
+        let right = parse_sum(parser, stop_tokens)?;
+        // `a < b < c` is rejected instead of silently comparing the result of `a < b` with `c`.
+        if COMPARISON_OPERATORS.contains(&parser.current_token.0) {
+            return Err(CompileError::new(
+                "chained comparisons are not supported",
+                parser.current_token.1,
+            )
+            .with_code(ErrorCode::ChainedComparison)
+            .with_label(op_span, "the first comparison is here")
+            .with_help("compare each pair separately, such as `a < b` and `b < c`"));
+        }
+        let span = left.span().to(right.span());
+        left = Expr::BooleanComparison {
+            lvalue: Box::new(left),
+            operator: op,
+            operator_span: op_span,
+            rvalue: Box::new(right),
+            span,
+        };
+    }
+    if parser.current_token.0 == Token::Equals {
+        return Err(
+            CompileError::new("assignment is not an expression", parser.current_token.1)
+                .with_code(ErrorCode::MisusedAssignment)
+                .with_help("did you mean '=='?"),
+        );
+    }
+    Ok(left)
+}
+
+/// Parses `a + b - c`, whose operands are products.
+fn parse_sum(parser: &mut Parser, stop_tokens: &[Token]) -> Result<Expr, CompileError> {
+    parse_left_associative(
+        parser,
+        stop_tokens,
+        &[Token::Plus, Token::Minus],
+        parse_product,
+    )
+}
+
+/// Parses `a * b / c`, whose operands are single values.
+fn parse_product(parser: &mut Parser, stop_tokens: &[Token]) -> Result<Expr, CompileError> {
+    parse_left_associative(
+        parser,
+        stop_tokens,
+        &[Token::Asterisk, Token::Slash],
+        |parser, _| parse_primary(parser),
+    )
+}
+
+/// Parses operands joined by any of `operators`, grouping them from the left. Each operand is
+/// parsed by `parse_operand`, which handles the operators that bind more tightly.
+fn parse_left_associative(
+    parser: &mut Parser,
+    stop_tokens: &[Token],
+    operators: &[Token],
+    parse_operand: fn(&mut Parser, &[Token]) -> Result<Expr, CompileError>,
+) -> Result<Expr, CompileError> {
+    let mut left = parse_operand(parser, stop_tokens)?;
+    while operators.contains(&parser.current_token.0) {
+        let op = parser.current_token.0.clone();
+        parser.count_operator()?;
+        parser.advance();
+        expect_operand(parser, &op, stop_tokens)?;
+        let right = parse_operand(parser, stop_tokens)?;
+        let span = left.span().to(right.span());
+        left = Expr::BinaryOperator {
+            operator: parser.get_operator(op),
+            left: Box::new(left),
+            right: Box::new(right),
+            span,
+        };
+    }
+    Ok(left)
+}
+
+/// Fails if the expression ends right after the operator `op`, before its right operand.
+fn expect_operand(parser: &Parser, op: &Token, stop_tokens: &[Token]) -> Result<(), CompileError> {
+    if stop_tokens.contains(&parser.current_token.0) {
+        return Err(parser.unexpected(format!("an expression after {}", op)));
+    }
+    Ok(())
+}
+
+/// Parses a value that operators can apply to: a literal, a variable or a call.
+fn parse_primary(parser: &mut Parser) -> Result<Expr, CompileError> {
+    let start = parser.current_token.1;
+    let primary = match &parser.current_token.0 {
         Token::Builtin(builtin) => {
-            let callee = match builtin {
-                Builtin::Print => "print",
-                Builtin::Input => "input",
-            }
-            .to_string();
+            let callee = builtin.name().to_string();
             parser.advance();
-            return functions::parse_call(parser, callee);
+            functions::parse_call(parser, callee, start)?
         }
         Token::Keyword(Keyword::True) => {
             parser.advance();
-            Expr::BooleanLiteral(true)
+            Expr::BooleanLiteral {
+                value: true,
+                span: start,
+            }
         }
         Token::Keyword(Keyword::False) => {
             parser.advance();
-            Expr::BooleanLiteral(false)
+            Expr::BooleanLiteral {
+                value: false,
+                span: start,
+            }
         }
         Token::Identifier(name) => {
             let name = name.clone();
             parser.advance();
             if matches!(parser.current_token.0, Token::LeftParen) {
-                return functions::parse_call(parser, name);
+                functions::parse_call(parser, name, start)?
+            } else {
+                Expr::Variable { name, span: start }
             }
-            Expr::Variable(name)
         }
         Token::NumberLiteral(n) => {
             let value = *n;
             parser.advance();
-            Expr::IntegerLiteral(value)
+            Expr::IntegerLiteral { value, span: start }
         }
         Token::StringLiteral(s) => {
-            let s = s.clone();
+            let value = s.clone();
             parser.advance();
-            Expr::StringLiteral(s)
+            Expr::StringLiteral { value, span: start }
         }
-        _ => {
-            eprintln!(
-                "DEBUG: Unexpected token in expression: {:?} at {:?}",
-                parser.current_token.0, parser.current_token.1
-            );
-            return Err(CompileError::new(
-                format!(
-                    "Unexpected token in expression: {:?}",
-                    parser.current_token.0
-                ),
-                parser.current_token.1.clone(),
-            ));
+        // A `)` with no `(` open is a stray closer rather than a missing expression.
+        Token::RightParen
+            if !parser
+                .open_delimiters
+                .iter()
+                .any(|(opener, _)| *opener == Token::LeftParen) =>
+        {
+            return Err(parser.unmatched());
         }
+        _ => return Err(parser.unexpected("an expression")),
     };
-    loop {
-        match &parser.current_token.0 {
-            Token::LeftBrace
-            | Token::RightBrace
-            | Token::Semicolon
-            | Token::Comma
-            | Token::RightParen
-            | Token::EOF => {
-                break;
-            }
-            Token::Plus | Token::Minus | Token::Asterisk | Token::Slash => {
-                let op = parser.current_token.0.clone();
-                parser.advance();
-                if stop_tokens
-                    .iter()
-                    .any(|stop| parser.current_token.0 == *stop)
-                {
-                    eprintln!(
-                        "DEBUG: Operator {:?} followed by stop token {:?} at {:?}",
-                        op, parser.current_token.0, parser.current_token.1
-                    );
-                    return Err(CompileError::new(
-                        format!(
-                            "Expected expression after operator {:?}, found block/statement delimiter",
-                            op
-                        ),
-                        parser.current_token.1.clone(),
-                    ));
-                }
-                let right = parse_expression_until(parser, stop_tokens)?;
-                left = Expr::BinaryOperator {
-                    operator: parser.get_operator(op),
-                    left: Box::new(left),
-                    right: Box::new(right),
-                };
+    Ok(primary)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{Expr, FileId, Stmt, Token};
+    use crate::loader;
+    use crate::parser::Parser;
+
+    /// Parses `expr` as the value of a `return` and writes it back with every operator's operands
+    /// in parentheses, showing how it was grouped.
+    fn grouping(expr: &str) -> String {
+        let source = format!("fn main() {{ return {}; }}", expr);
+        let tokens = loader::lex(&source, FileId(0)).expect("lexes");
+        let program = match Parser::new(tokens, FileId(0)).parse() {
+            Ok(program) => program,
+            Err(errors) => panic!("`{}` does not parse: {}", expr, errors[0].message),
+        };
+        let Stmt::Function { body, .. } = &program.functions[0] else {
+            panic!("expected a function");
+        };
+        let Stmt::Return { value, .. } = &body[0] else {
+            panic!("expected a return");
+        };
+        parenthesize(value)
+    }
+
+    fn parenthesize(expr: &Expr) -> String {
+        match expr {
+            Expr::Call { callee, args, .. } => {
+                let args: Vec<String> = args.iter().map(parenthesize).collect();
+                format!("{}({})", callee, args.join(", "))
             }
-            Token::Equality
-            | Token::GreaterThan
-            | Token::LessThan
-            | Token::GreaterThanOrEqual
-            | Token::LessThanOrEqual
-            | Token::NotEqual => {
-                let op = parser.current_token.0.clone();
-                parser.advance();
-                if stop_tokens
-                    .iter()
-                    .any(|stop| parser.current_token.0 == *stop)
-                {
-                    eprintln!(
-                        "DEBUG: Comparison operator {:?} followed by stop token {:?} at {:?}",
-                        op, parser.current_token.0, parser.current_token.1
-                    );
-                    return Err(CompileError::new(
-                        format!(
-                            "Expected expression after operator {:?}, found block/statement delimiter",
-                            op
-                        ),
-                        parser.current_token.1.clone(),
-                    ));
-                }
-                let right = parse_expression_until(parser, stop_tokens)?;
-                left = Expr::BooleanComparison {
-                    lvalue: Box::new(left),
-                    operator: op,
-                    rvalue: Box::new(right),
+            Expr::Variable { name, .. } => name.clone(),
+            Expr::StringLiteral { value, .. } => format!("\"{}\"", value),
+            Expr::IntegerLiteral { value, .. } => value.to_string(),
+            Expr::BooleanLiteral { value, .. } => value.to_string(),
+            Expr::BinaryOperator {
+                operator,
+                left,
+                right,
+                ..
+            } => format!(
+                "({} {} {})",
+                parenthesize(left),
+                operator,
+                parenthesize(right)
+            ),
+            Expr::BooleanComparison {
+                lvalue,
+                operator,
+                rvalue,
+                ..
+            } => {
+                let operator = match operator {
+                    Token::Equality => "==",
+                    Token::NotEqual => "!=",
+                    Token::LessThan => "<",
+                    Token::LessThanOrEqual => "<=",
+                    Token::GreaterThan => ">",
+                    Token::GreaterThanOrEqual => ">=",
+                    _ => unreachable!(),
                 };
+                format!(
+                    "({} {} {})",
+                    parenthesize(lvalue),
+                    operator,
+                    parenthesize(rvalue)
+                )
             }
-            _ => break,
         }
     }
-    Ok(left)
+
+    #[test]
+    fn arithmetic_binds_more_tightly_than_comparisons() {
+        assert_eq!(grouping("i * i >= n"), "((i * i) >= n)");
+        assert_eq!(grouping("n <= i * i"), "(n <= (i * i))");
+        assert_eq!(grouping("a + 1 == b - 1"), "((a + 1) == (b - 1))");
+        assert_eq!(grouping("a / 2 != 0"), "((a / 2) != 0)");
+    }
+
+    #[test]
+    fn multiplication_binds_more_tightly_than_addition() {
+        assert_eq!(grouping("2 * 3 + 1"), "((2 * 3) + 1)");
+        assert_eq!(grouping("1 + 2 * 3"), "(1 + (2 * 3))");
+        assert_eq!(grouping("a - b * c / d + e"), "((a - ((b * c) / d)) + e)");
+    }
+
+    #[test]
+    fn operators_of_equal_precedence_group_from_the_left() {
+        assert_eq!(grouping("10 - 2 - 3"), "((10 - 2) - 3)");
+        assert_eq!(grouping("12 / 2 / 3"), "((12 / 2) / 3)");
+        assert_eq!(grouping("a - b + c"), "((a - b) + c)");
+    }
+
+    #[test]
+    fn calls_are_operands_on_either_side_of_an_operator() {
+        assert_eq!(grouping("k() + 1 < g(1)"), "((k() + 1) < g(1))");
+        assert_eq!(grouping("g(h(1) * 2) - k()"), "(g((h(1) * 2)) - k())");
+        assert_eq!(
+            grouping("f(a) * f(b) == input()"),
+            "((f(a) * f(b)) == input())"
+        );
+        assert_eq!(grouping("f(g(a, b + 1), c)"), "f(g(a, (b + 1)), c)");
+    }
+
+    #[test]
+    fn chained_comparisons_are_rejected() {
+        let source = "fn main() { return a < b < c; }";
+        let tokens = loader::lex(source, FileId(0)).expect("lexes");
+        let errors = Parser::new(tokens, FileId(0)).parse().unwrap_err();
+        assert_eq!(errors[0].message, "chained comparisons are not supported");
+    }
 }
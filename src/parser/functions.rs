@@ -1,126 +1,207 @@
 use super::Parser;
 use crate::{
-    common::{CompileError, Expr, ExternFunction, Keyword, Stmt, Token, Type},
+    common::{CompileError, Expr, ExternFunction, Inline, Keyword, Span, Stmt, Token, Type},
+    diagnostics::ErrorCode,
     parser::expressions,
     type_system::keyword_to_type,
 };
 
 pub fn parse_extern_function(parser: &mut Parser) -> Result<ExternFunction, CompileError> {
+    let start = parser.current_token.1;
     parser.expect_keyword(Keyword::Extern)?;
+    parse_extern_declaration(parser, start)
+}
+
+/// Parses `extern { fn a(); fn b(); }`, which declares each of the functions as an extern.
+pub fn parse_extern_block(parser: &mut Parser) -> Result<Vec<ExternFunction>, CompileError> {
+    parser.expect_keyword(Keyword::Extern)?;
+    parser.open(Token::LeftBrace)?;
+    let depth = parser.open_delimiters.len();
+    let mut externs = Vec::new();
+    while !matches!(parser.current_token.0, Token::RightBrace | Token::EOF) {
+        let start = parser.current_token.1;
+        let index = parser.index;
+        match parse_extern_declaration(parser, start) {
+            Ok(ext) => externs.push(ext),
+            Err(e) => {
+                parser.record_error(e);
+                parser.open_delimiters.truncate(depth);
+                if parser.index == index {
+                    parser.advance();
+                }
+                parser.skip_statement();
+            }
+        }
+    }
+    parser.close(Token::RightBrace)?;
+    Ok(externs)
+}
+
+/// Parses `fn name(args) type;`, the part of an extern declaration that follows `extern`.
+fn parse_extern_declaration(
+    parser: &mut Parser,
+    start: Span,
+) -> Result<ExternFunction, CompileError> {
     parser.expect_keyword(Keyword::Fn)?;
     let name = parser.parse_identifier()?;
     let args = parse_extern_function_args(parser)?;
-    let return_type = if let Token::Identifier(ref t) = parser.current_token.0 {
-        let t = t.clone();
+    let mut noreturn = false;
+    let return_type = if let Token::Keyword(Keyword::Noreturn) = parser.current_token.0 {
+        // `noreturn` takes the place of the return type, since such a function returns nothing.
+        parser.advance();
+        noreturn = true;
+        Type::Void
+    } else if let Token::Keyword(kw) = &parser.current_token.0 {
+        let t = keyword_to_type(kw, &parser.current_token.1)?;
         parser.advance();
         t
     } else if matches!(parser.current_token.0, Token::Semicolon) {
         // No return type specified, treat as void
-        String::new()
+        Type::Void
     } else {
-        return Err(CompileError::new(
-            "Expected return type after extern fn args or ';'",
-            parser.current_token.1.clone(),
-        ));
+        return Err(parser.unexpected("a return type, keyword 'noreturn' or ';'"));
     };
     parser.expect(Token::Semicolon)?;
     Ok(ExternFunction {
         name,
         args,
         return_type,
+        noreturn,
+        span: parser.span_from(start),
     })
 }
 
 pub fn parse_extern_function_args(
     parser: &mut Parser,
 ) -> Result<Vec<(String, Type)>, CompileError> {
-    parser.expect(Token::LeftParen)?;
+    parser.open(Token::LeftParen)?;
     let mut args = Vec::new();
-    while !matches!(parser.current_token.0, Token::RightParen) {
+    while !matches!(
+        parser.current_token.0,
+        Token::RightParen | Token::RightBrace | Token::EOF
+    ) {
         let name = parser.parse_identifier()?;
         parser.expect(Token::Colon)?;
         let t = if let Token::Keyword(kw) = &parser.current_token.0 {
             keyword_to_type(kw, &parser.current_token.1)?
         } else {
-            return Err(CompileError::new(
-                "Expected type in extern fn arg",
-                parser.current_token.1.clone(),
-            ));
+            return Err(parser.unexpected("a type"));
         };
         parser.advance();
         args.push((name, t));
-        if matches!(parser.current_token.0, Token::Comma) {
-            parser.advance();
+        match parser.current_token.0 {
+            Token::Comma => parser.advance(),
+            Token::RightParen => {}
+            Token::RightBrace | Token::Semicolon | Token::EOF => return Err(parser.unclosed()),
+            _ => return Err(parser.expected_one_of(&[Token::Comma, Token::RightParen])),
         }
     }
-    parser.expect(Token::RightParen)?;
+    parser.close(Token::RightParen)?;
     Ok(args)
 }
 
 pub fn parse_function(parser: &mut Parser) -> Result<Stmt, CompileError> {
+    let start = parser.current_token.1;
+    let public = matches!(parser.current_token.0, Token::Keyword(Keyword::Pub));
+    if public {
+        parser.advance();
+    }
+    let inline = match parser.current_token.0 {
+        Token::Keyword(Keyword::Inline) => Inline::Always,
+        Token::Keyword(Keyword::Noinline) => Inline::Never,
+        _ => Inline::Default,
+    };
+    if inline != Inline::Default {
+        parser.advance();
+    }
     parser.expect_keyword(Keyword::Fn)?;
     let name = parser.parse_identifier()?;
     let args = parse_function_declaration_arguments_with_types(parser)?;
+    let span = parser.span_from(start);
     let body = parser.parse_block()?;
+    let body_end = parser.previous_span;
 
-    let mut return_expr = None;
+    // Returns used to be written after the body, as `fn f() { ... } return x;`.
     if matches!(parser.current_token.0, Token::Keyword(Keyword::Return)) {
+        let start = parser.current_token.1;
         parser.advance();
-        return_expr = Some(expressions::parse_expression(parser)?);
+        expressions::parse_expression(parser)?;
+        let return_span = parser.span_from(start);
         if matches!(parser.current_token.0, Token::Semicolon) {
             parser.advance();
         }
+        parser.record_error(
+            CompileError::new("`return` must be inside the function body", return_span)
+                .with_code(ErrorCode::ReturnAfterBody)
+                .with_label(body_end, format!("the body of `{}` ends here", name))
+                .with_help("move the `return` before the closing '}'"),
+        );
     }
     Ok(Stmt::Function {
         name,
         args,
         body,
-        return_expr,
+        public,
+        inline,
+        span,
     })
 }
 
 pub fn parse_function_declaration_arguments_with_types(
     parser: &mut Parser,
 ) -> Result<Vec<(String, Type)>, CompileError> {
-    parser.expect(Token::LeftParen)?;
+    parser.open(Token::LeftParen)?;
     let mut args = Vec::new();
     if matches!(parser.current_token.0, Token::RightParen) {
-        parser.expect(Token::RightParen)?;
+        parser.close(Token::RightParen)?;
         return Ok(args);
     }
-    while !matches!(parser.current_token.0, Token::RightParen) {
+    while !matches!(
+        parser.current_token.0,
+        Token::RightParen | Token::RightBrace | Token::EOF
+    ) {
         let name = parser.parse_identifier()?;
         parser.expect(Token::Colon)?;
 
         let t = if let Token::Keyword(kw) = &parser.current_token.0 {
             keyword_to_type(kw, &parser.current_token.1)?
         } else {
-            return Err(CompileError::new(
-                "Expected type in fn arg",
-                parser.current_token.1.clone(),
-            ));
+            return Err(parser.unexpected("a type"));
         };
         parser.advance();
         args.push((name, t));
-        if matches!(parser.current_token.0, Token::Comma) {
-            parser.advance();
+        match parser.current_token.0 {
+            Token::Comma => parser.advance(),
+            Token::RightParen => {}
+            Token::RightBrace | Token::Semicolon | Token::EOF => return Err(parser.unclosed()),
+            _ => return Err(parser.expected_one_of(&[Token::Comma, Token::RightParen])),
         }
     }
-    parser.expect(Token::RightParen)?;
+    parser.close(Token::RightParen)?;
     Ok(args)
 }
 
-pub fn parse_call(parser: &mut Parser, callee: String) -> Result<Expr, CompileError> {
-    parser.expect(Token::LeftParen)?;
+pub fn parse_call(parser: &mut Parser, callee: String, start: Span) -> Result<Expr, CompileError> {
+    parser.open(Token::LeftParen)?;
     let mut args = Vec::new();
 
-    while !matches!(parser.current_token.0, Token::RightParen) {
+    while !matches!(
+        parser.current_token.0,
+        Token::RightParen | Token::RightBrace | Token::EOF
+    ) {
         args.push(expressions::parse_expression(parser)?);
-        if matches!(parser.current_token.0, Token::Comma) {
-            parser.advance();
+        match parser.current_token.0 {
+            Token::Comma => parser.advance(),
+            Token::RightParen => {}
+            Token::RightBrace | Token::Semicolon | Token::EOF => return Err(parser.unclosed()),
+            _ => return Err(parser.expected_one_of(&[Token::Comma, Token::RightParen])),
         }
     }
 
-    parser.expect(Token::RightParen)?;
-    Ok(Expr::Call { callee, args })
+    parser.close(Token::RightParen)?;
+    Ok(Expr::Call {
+        callee,
+        args,
+        span: parser.span_from(start),
+    })
 }
@@ -0,0 +1,18 @@
+use crate::{
+    common::{CompileError, Import, Keyword, Token},
+    parser::Parser,
+};
+
+/// Parses `import "path";` at the top level of the file.
+pub fn parse_import(parser: &mut Parser) -> Result<Import, CompileError> {
+    let start = parser.current_token.1;
+    parser.expect_keyword(Keyword::Import)?;
+    let Token::StringLiteral(path) = &parser.current_token.0 else {
+        return Err(parser.unexpected("the path of a file to import, in quotes"));
+    };
+    let path = path.clone();
+    parser.advance();
+    let span = parser.span_from(start);
+    parser.expect_semicolon()?;
+    Ok(Import { path, span })
+}
@@ -1,64 +1,322 @@
-use crate::common::{Builtin, CompileError, Expr, Keyword, Position, Program, Stmt, Token};
+use std::fmt::Display;
+
+use crate::common::{CompileError, Expr, FileId, Keyword, Position, Program, Span, Stmt, Token};
+use crate::diagnostics::ErrorCode;
 
 pub mod control_flow;
 pub mod expressions;
 pub mod functions;
+pub mod imports;
 pub mod variables;
 
+/// After this many syntax errors in a row within one block, the rest of the block is skipped.
+/// Past that point further errors are usually knock-on effects of the first.
+const MAX_CONSECUTIVE_ERRORS: usize = 3;
+
+/// How deeply blocks, `else if` branches and expressions may nest. The parser and every later
+/// pass recurse into nested code, so without a limit a pathological program would overflow the
+/// stack instead of getting an error.
+pub const MAX_NESTING: usize = 256;
+
+/// How many binary operators one expression may have, counting those in the arguments of calls
+/// inside it. Operators of the same precedence are parsed in a loop, but the tree they build is
+/// as deep as the chain is long, and later passes recurse into it.
+pub const MAX_OPERATORS: usize = 1000;
+
 pub struct Parser {
-    tokens: Vec<(Token, Position)>,
-    current_token: (Token, Position),
+    /// The file the tokens were lexed from.
+    file: FileId,
+    tokens: Vec<(Token, Span)>,
+    current_token: (Token, Span),
+    /// The span of the last token consumed, which is where a construct being parsed ends.
+    previous_span: Span,
     index: usize,
+    /// Syntax errors that parsing has recovered from.
+    errors: Vec<CompileError>,
+    /// The token index the last recorded error was found at.
+    last_error_index: Option<usize>,
+    /// Every `{` and `(` that has been opened but not yet closed, innermost last.
+    open_delimiters: Vec<(Token, Span)>,
+    /// How many levels of nesting the code being parsed is inside, up to `MAX_NESTING`.
+    nesting: usize,
+    /// How many operators the outermost expression being parsed has so far, up to
+    /// `MAX_OPERATORS`, or `None` outside expressions.
+    operators: Option<usize>,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<(Token, Position)>) -> Self {
-        let current_token = tokens[0].clone();
+    pub fn new(tokens: Vec<(Token, Span)>, file: FileId) -> Self {
+        // An empty file may not even have an EOF token.
+        let current_token = tokens
+            .first()
+            .cloned()
+            .unwrap_or((Token::EOF, Span::at(file, Position::new(1, 1))));
+        let previous_span = current_token.1;
         Self {
+            file,
             tokens,
             current_token,
+            previous_span,
             index: 0,
+            errors: Vec::new(),
+            last_error_index: None,
+            open_delimiters: Vec::new(),
+            nesting: 0,
+            operators: None,
         }
     }
 
-    pub fn parse(&mut self) -> Result<Program, CompileError> {
+    /// Parses the whole program. Syntax errors are recovered from where possible, so that every
+    /// one of them can be reported at once; if there were any, they are returned instead.
+    pub fn parse(&mut self) -> Result<Program, Vec<CompileError>> {
+        let mut imports = Vec::new();
         let mut functions = Vec::new();
         let mut externs = Vec::new();
+        let mut consts = Vec::new();
         while self.current_token.0 != Token::EOF {
-            match &self.current_token.0 {
+            let index = self.index;
+            let result = match &self.current_token.0 {
+                Token::Keyword(Keyword::Extern) if self.peek().0 == Token::LeftBrace => {
+                    functions::parse_extern_block(self).map(|block| externs.extend(block))
+                }
                 Token::Keyword(Keyword::Extern) => {
-                    externs.push(functions::parse_extern_function(self)?);
+                    functions::parse_extern_function(self).map(|ext| externs.push(ext))
+                }
+                Token::Keyword(
+                    Keyword::Fn | Keyword::Pub | Keyword::Inline | Keyword::Noinline,
+                ) => functions::parse_function(self).map(|func| functions.push(func)),
+                Token::Keyword(Keyword::Const) => {
+                    variables::parse_const_decl(self).map(|decl| consts.push(decl))
                 }
-                Token::Keyword(Keyword::Fn) => {
-                    functions.push(functions::parse_function(self)?);
+                Token::Keyword(Keyword::Import) => {
+                    imports::parse_import(self).map(|import| imports.push(import))
                 }
-                Token::EOF => break,
-                _ => {
-                    return Err(CompileError::new(
-                        format!("Unexpected token at top level: {:?}", self.current_token.0),
-                        self.current_token.1.clone(),
-                    ));
+                Token::RightBrace | Token::RightParen => Err(self.unmatched()),
+                _ => Err(self.expected_one_of(&[
+                    Token::Keyword(Keyword::Fn),
+                    Token::Keyword(Keyword::Extern),
+                    Token::Keyword(Keyword::Const),
+                    Token::Keyword(Keyword::Import),
+                ])),
+            };
+            if let Err(e) = result {
+                self.record_error(e);
+                self.open_delimiters.clear();
+                if self.index == index {
+                    self.advance();
                 }
+                self.skip_to_item();
             }
         }
-        Ok(Program { functions, externs })
+        if self.errors.is_empty() {
+            Ok(Program {
+                imports,
+                functions,
+                externs,
+                consts,
+            })
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
     }
 
     fn parse_block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        self.nested(Self::parse_block_contents)
+    }
+
+    fn parse_block_contents(&mut self) -> Result<Vec<Stmt>, CompileError> {
         let mut body = Vec::new();
-        self.expect(Token::LeftBrace)?;
-        while !matches!(self.current_token.0, Token::RightBrace | Token::EOF) {
-            let stmt = self.parse_statement()?;
-            if matches!(self.current_token.0, Token::Semicolon) {
+        self.open(Token::LeftBrace)?;
+        let depth = self.open_delimiters.len();
+        let mut consecutive_errors = 0;
+        while !self.at_block_end() {
+            // A lone `;` is an empty statement.
+            if self.current_token.0 == Token::Semicolon {
                 self.advance();
+                continue;
+            }
+            match self.parse_statement() {
+                Ok(stmt) => {
+                    consecutive_errors = 0;
+                    body.push(stmt);
+                }
+                Err(e) => {
+                    self.record_error(e);
+                    self.open_delimiters.truncate(depth);
+                    consecutive_errors += 1;
+                    if consecutive_errors == MAX_CONSECUTIVE_ERRORS {
+                        self.skip_to_block_end();
+                    } else {
+                        self.skip_statement();
+                    }
+                }
             }
-            body.push(stmt);
         }
-        self.expect(Token::RightBrace)?;
+        self.close(Token::RightBrace)?;
         Ok(body)
     }
 
+    /// Parses code that nests one level inside the code being parsed, failing instead if that
+    /// would nest it more than `MAX_NESTING` levels deep.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, CompileError>,
+    ) -> Result<T, CompileError> {
+        if self.nesting == MAX_NESTING {
+            return Err(CompileError::new(
+                format!("nesting too deep (limit {})", MAX_NESTING),
+                self.current_token.1,
+            )
+            .with_help("move some of the nested code into a function of its own"));
+        }
+        self.nesting += 1;
+        let result = parse(self);
+        self.nesting -= 1;
+        result
+    }
+
+    /// Counts a binary operator in the expression being parsed, failing if it has more than
+    /// `MAX_OPERATORS` of them.
+    fn count_operator(&mut self) -> Result<(), CompileError> {
+        let operators = self.operators.get_or_insert(0);
+        if *operators == MAX_OPERATORS {
+            return Err(CompileError::new(
+                format!(
+                    "expression has too many operators (limit {})",
+                    MAX_OPERATORS
+                ),
+                self.current_token.1,
+            )
+            .with_help("split the expression up, keeping parts of it in variables"));
+        }
+        *operators += 1;
+        Ok(())
+    }
+
+    /// Whether the current token ends a block. A top-level keyword also does, since it means the
+    /// closing `}` is missing.
+    fn at_block_end(&self) -> bool {
+        matches!(self.current_token.0, Token::RightBrace | Token::EOF)
+            || self.current_token.0.starts_item()
+    }
+
+    fn record_error(&mut self, error: CompileError) {
+        // An error that propagates out of several nested constructs can be reported again by
+        // each of them without the parser moving on; only the first report is kept.
+        if self.last_error_index != Some(self.index) {
+            self.last_error_index = Some(self.index);
+            self.errors.push(error);
+        }
+    }
+
+    /// Consumes an opening delimiter and remembers where it is, so that it can be pointed at if
+    /// it is never closed.
+    fn open(&mut self, opener: Token) -> Result<(), CompileError> {
+        let span = self.current_token.1;
+        self.expect(opener.clone())?;
+        self.open_delimiters.push((opener, span));
+        Ok(())
+    }
+
+    /// Consumes the delimiter that closes the innermost open one.
+    fn close(&mut self, closer: Token) -> Result<(), CompileError> {
+        if self.current_token.0 == closer {
+            self.open_delimiters.pop();
+            self.advance();
+            return Ok(());
+        }
+        match self.current_token.0 {
+            Token::EOF | Token::RightBrace | Token::RightParen => Err(self.unclosed()),
+            _ if self.current_token.0.starts_item() => Err(self.unclosed()),
+            _ => Err(self.unexpected(closer)),
+        }
+    }
+
+    /// An error for the innermost open delimiter, which the current token shows is never closed.
+    fn unclosed(&self) -> CompileError {
+        let Some((opener, span)) = self.open_delimiters.last() else {
+            return self.unmatched();
+        };
+        let (found, label) = match &self.current_token.0 {
+            Token::EOF => (
+                self.current_token.1,
+                "the file ends before it is closed".to_string(),
+            ),
+            found => (
+                self.current_token.1,
+                format!("expected it to be closed before {}", found),
+            ),
+        };
+        CompileError::new(format!("unclosed {}", opener), *span)
+            .with_code(ErrorCode::UnclosedDelimiter)
+            .with_label(found, label)
+    }
+
+    /// An error for a closing delimiter that has nothing to close.
+    fn unmatched(&self) -> CompileError {
+        CompileError::new(
+            format!("unmatched {}", self.current_token.0),
+            self.current_token.1,
+        )
+        .with_code(ErrorCode::UnclosedDelimiter)
+    }
+
+    /// Skips the rest of a statement that failed to parse: up to and including its `;`, or
+    /// through a `{ ... }` it contains, stopping early at the end of the enclosing block.
+    fn skip_statement(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.current_token.0 {
+                Token::EOF => return,
+                _ if self.current_token.0.starts_item() => return,
+                Token::RightBrace if depth == 0 => return,
+                Token::Semicolon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                Token::LeftBrace => depth += 1,
+                Token::RightBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.advance();
+                        // An `else` belongs to the statement that was just skipped.
+                        if matches!(self.current_token.0, Token::Keyword(Keyword::Else)) {
+                            continue;
+                        }
+                        return;
+                    }
+                }
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Skips to the `}` that closes the current block, without consuming it.
+    fn skip_to_block_end(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.current_token.0 {
+                Token::EOF => return,
+                _ if self.current_token.0.starts_item() => return,
+                Token::RightBrace if depth == 0 => return,
+                Token::LeftBrace => depth += 1,
+                Token::RightBrace => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Skips to the next top-level `fn` or `extern`.
+    fn skip_to_item(&mut self) {
+        while self.current_token.0 != Token::EOF && !self.current_token.0.starts_item() {
+            self.advance();
+        }
+    }
+
     fn parse_statement(&mut self) -> Result<Stmt, CompileError> {
+        let start = self.current_token.1;
         match &self.current_token.0 {
             Token::Keyword(Keyword::Var) => variables::parse_variable_decl(self),
             Token::Keyword(Keyword::If) => control_flow::parse_if_statement(self),
@@ -66,23 +324,20 @@ impl Parser {
             Token::LeftBrace => {
                 // Standalone blocks; is it supported in the AST?
                 self.parse_block()?;
+                let span = self.span_from(start);
                 // THIS SHOULD NOT BE RETURNED IF STANDALONE BLOCKS ARE TO WORK
-                Ok(Stmt::ExprStmt(Expr::BooleanLiteral(true)))
+                Ok(Stmt::ExprStmt {
+                    expr: Expr::BooleanLiteral { value: true, span },
+                    span,
+                })
             }
-            Token::RightBrace | Token::EOF => Err(CompileError::new(
-                format!(
-                    "Unexpected block delimiter or EOF in statement context: {:?}",
-                    self.current_token.0
-                ),
-                self.current_token.1.clone(),
-            )),
+            Token::RightBrace | Token::EOF => Err(self.unexpected("a statement")),
             Token::Keyword(Keyword::Return) => {
                 self.advance();
                 let expr = expressions::parse_expression(self)?;
-                if matches!(self.current_token.0, Token::Semicolon) {
-                    self.advance();
-                }
-                Ok(Stmt::Return(expr))
+                let span = self.span_from(start);
+                self.expect_semicolon()?;
+                Ok(Stmt::Return { value: expr, span })
             }
             _ => {
                 if self.peek().0 == Token::Equals {
@@ -94,21 +349,56 @@ impl Parser {
     }
 
     fn parse_expression_statement(&mut self) -> Result<Stmt, CompileError> {
+        let start = self.current_token.1;
         let expr = expressions::parse_expression(self)?;
-        Ok(Stmt::ExprStmt(expr))
+        let span = self.span_from(start);
+        self.expect_semicolon()?;
+        Ok(Stmt::ExprStmt { expr, span })
     }
 
-    fn expect_keyword(&mut self, keyword: Keyword) -> Result<(), CompileError> {
-        if let Token::Keyword(k) = &self.current_token.0 {
-            if k == &keyword {
-                self.advance();
-                return Ok(());
+    /// Consumes the `;` that ends a statement. If it is missing but the next token plainly starts
+    /// another statement, the error is recorded and parsing carries on as if it were there.
+    fn expect_semicolon(&mut self) -> Result<(), CompileError> {
+        if self.current_token.0 == Token::Semicolon {
+            self.advance();
+            return Ok(());
+        }
+        if !self.starts_new_statement() {
+            return Err(self.unexpected(Token::Semicolon));
+        }
+        let error = CompileError::new(
+            "missing ';' after this statement",
+            Span::at(self.previous_span.file, self.previous_span.end),
+        )
+        .with_code(ErrorCode::MissingSemicolon);
+        self.record_error(error);
+        Ok(())
+    }
+
+    /// Whether the current token is clearly the start of something after the statement that was
+    /// just parsed. A name only counts when it is on a later line, since `x y` on one line is
+    /// more likely a typo within the statement.
+    fn starts_new_statement(&self) -> bool {
+        match self.current_token.0 {
+            Token::Keyword(Keyword::Var | Keyword::If | Keyword::While | Keyword::Return)
+            | Token::RightBrace
+            | Token::EOF => true,
+            _ if self.current_token.0.starts_item() => true,
+            Token::Identifier(_) | Token::Builtin(_) => {
+                self.current_token.1.start.line > self.previous_span.end.line
             }
+            _ => false,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: Keyword) -> Result<(), CompileError> {
+        if let Token::Keyword(k) = &self.current_token.0
+            && k == &keyword
+        {
+            self.advance();
+            return Ok(());
         }
-        Err(CompileError::new(
-            format!("Expected keyword {:?}", keyword),
-            self.current_token.1.clone(),
-        ))
+        Err(self.unexpected(keyword))
     }
 
     fn expect(&mut self, expected: Token) -> Result<(), CompileError> {
@@ -116,61 +406,54 @@ impl Parser {
             self.advance();
             Ok(())
         } else {
-            Err(CompileError::new(
-                format!("Expected {:?}, found {:?}", expected, self.current_token.0),
-                self.current_token.1.clone(),
-            ))
+            Err(self.unexpected(expected))
         }
     }
 
+    /// An error saying what was expected in place of the current token.
+    fn unexpected(&self, expected: impl Display) -> CompileError {
+        CompileError::new(
+            format!("expected {}, found {}", expected, self.current_token.0),
+            self.current_token.1,
+        )
+        .with_code(ErrorCode::UnexpectedToken)
+    }
+
+    /// An error for when any of several tokens could have come next.
+    fn expected_one_of(&self, expected: &[Token]) -> CompileError {
+        let mut names: Vec<String> = expected.iter().map(Token::to_string).collect();
+        let last = names.pop().unwrap_or_default();
+        let list = if names.is_empty() {
+            last
+        } else {
+            format!("{} or {}", names.join(", "), last)
+        };
+        self.unexpected(format!("one of {}", list))
+    }
+
     fn parse_identifier(&mut self) -> Result<String, CompileError> {
         if let Token::Identifier(name) = &self.current_token.0 {
             let name = name.clone();
             self.advance();
             Ok(name)
         } else {
-            Err(CompileError::new(
-                "Expected identifier",
-                self.current_token.1.clone(),
-            ))
+            Err(self.unexpected("an identifier"))
         }
     }
 
     fn advance(&mut self) {
+        self.previous_span = self.current_token.1;
         self.index += 1;
         if self.index >= self.tokens.len() {
-            self.current_token = (Token::EOF, Position::new(0, 0));
+            self.current_token = self.end_of_input();
             return;
         }
         self.current_token = self.tokens[self.index].clone();
     }
 
-    fn parse_binary_operator(&mut self, token: Token, lvalue: i64) -> Result<Expr, CompileError> {
-        // Note that this expects that the next token is a binary operator, and that the current
-        // token is a number literal.
-        self.advance();
-        self.expect(token.clone())?;
-        let rvalue = expressions::parse_expression(self)?;
-        Ok(Expr::BinaryOperator {
-            operator: self.get_operator(token),
-            left: Box::new(Expr::IntegerLiteral(lvalue)),
-            right: Box::new(rvalue),
-        })
-    }
-
-    fn parse_boolean_expression(
-        &mut self,
-        token: Token,
-        lvalue: i64,
-    ) -> Result<Expr, CompileError> {
-        self.advance();
-        self.expect(token.clone())?;
-        let rvalue = expressions::parse_expression(self)?;
-        Ok(Expr::BooleanComparison {
-            lvalue: Box::new(Expr::IntegerLiteral(lvalue)),
-            operator: token,
-            rvalue: Box::new(rvalue),
-        })
+    /// The span from `start` to the end of the last token consumed.
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous_span)
     }
 
     fn get_operator(&self, token: Token) -> String {
@@ -183,11 +466,240 @@ impl Parser {
         }
     }
 
-    fn peek(&self) -> (Token, Position) {
+    fn peek(&self) -> (Token, Span) {
         if self.index + 1 < self.tokens.len() {
             self.tokens[self.index + 1].clone()
         } else {
-            (Token::EOF, Position::new(0, 0))
+            self.end_of_input()
+        }
+    }
+
+    /// An EOF token for reading past the last token, placed where the last token ends.
+    fn end_of_input(&self) -> (Token, Span) {
+        let end = self
+            .tokens
+            .last()
+            .map_or(Position::new(1, 1), |(_, span)| span.end);
+        (Token::EOF, Span::at(self.file, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_CONSECUTIVE_ERRORS, MAX_NESTING, MAX_OPERATORS, Parser};
+    use crate::common::{CompileError, FileId, Position, Program, Token};
+    use crate::lexer::Lexer;
+
+    /// Parses `source` on a thread with the 8 MiB stack the compiler's main thread usually has,
+    /// rather than the smaller one a test gets.
+    fn parse(source: &str) -> Result<Program, Vec<CompileError>> {
+        let mut lexer = Lexer::new(source, FileId(0));
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token().expect("lexes");
+            let end = token.0 == Token::EOF;
+            tokens.push(token);
+            if end {
+                break;
+            }
+        }
+        std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(move || Parser::new(tokens, FileId(0)).parse())
+            .expect("spawns the parser thread")
+            .join()
+            .expect("the parser does not panic")
+    }
+
+    /// A `main` whose `return` sits inside `depth` nested `if`s. The function body and the
+    /// returned expression are a level each, so this nests `depth + 2` levels deep.
+    fn nested_ifs(depth: usize) -> String {
+        format!(
+            "fn main() {{ {}return 0;{} }}",
+            "if true { ".repeat(depth),
+            " }".repeat(depth)
+        )
+    }
+
+    fn assert_too_deep(source: &str) {
+        let errors = parse(source).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+        let expected = format!("nesting too deep (limit {})", MAX_NESTING);
+        assert_eq!(messages, [expected.as_str()]);
+    }
+
+    #[test]
+    fn deeply_nested_ifs_are_an_error() {
+        assert_too_deep(&nested_ifs(5000));
+    }
+
+    #[test]
+    fn each_if_nests_one_level() {
+        assert!(parse(&nested_ifs(MAX_NESTING - 2)).is_ok());
+        assert_too_deep(&nested_ifs(MAX_NESTING - 1));
+    }
+
+    #[test]
+    fn operator_chains_do_not_nest() {
+        let source = format!("fn main() {{ return 1{}; }}", " + 1".repeat(MAX_OPERATORS));
+        assert!(parse(&source).is_ok());
+    }
+
+    #[test]
+    fn expressions_with_too_many_operators_are_an_error() {
+        let expected = format!(
+            "expression has too many operators (limit {})",
+            MAX_OPERATORS
+        );
+        let chain = format!(
+            "fn main() {{ return 1{}; }}",
+            " * 1".repeat(MAX_OPERATORS + 1)
+        );
+        // The operators in a call's arguments count towards the expression the call is in.
+        let half = " - 1".repeat(MAX_OPERATORS / 2);
+        let calls = format!("fn main() {{ return f(1{half}) + f(1{half}); }}");
+        for source in [chain, calls] {
+            let errors = parse(&source).unwrap_err();
+            assert_eq!(errors[0].message, expected);
+        }
+    }
+
+    #[test]
+    fn deeply_nested_calls_are_an_error() {
+        let source = format!(
+            "fn main() {{ return {}1{}; }}",
+            "f(".repeat(5000),
+            ")".repeat(5000)
+        );
+        assert_too_deep(&source);
+    }
+
+    #[test]
+    fn long_else_if_chains_are_an_error() {
+        let source = format!(
+            "fn main() {{ if false {{ }}{} return 0; }}",
+            " else if false { }".repeat(5000)
+        );
+        assert_too_deep(&source);
+    }
+
+    #[test]
+    fn errors_name_tokens_as_they_are_written() {
+        let cases = [
+            (
+                "fn main( { return 0; }",
+                "expected an identifier, found '{'",
+            ),
+            (
+                "fn main() { var x int = 1; return 0; }",
+                "expected ':', found keyword 'int'",
+            ),
+            (
+                "fn main() { if 1 < 2 return 0; }",
+                "expected '{', found keyword 'return'",
+            ),
+            ("fn 5() {}", "expected an identifier, found a number"),
+            (
+                "fn main() { x = ; return 0; }",
+                "expected an expression, found ';'",
+            ),
+        ];
+        for (source, expected) in cases {
+            let errors = parse(source).unwrap_err();
+            assert_eq!(errors[0].message, expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn errors_list_every_token_that_could_come_next() {
+        let cases = [
+            (
+                "main() { return 0; }",
+                "expected one of keyword 'fn', keyword 'extern', keyword 'const' or keyword 'import', found an identifier",
+            ),
+            (
+                "fn main() { print(\"a\" \"b\"); return 0; }",
+                "expected one of ',' or ')', found a string",
+            ),
+        ];
+        for (source, expected) in cases {
+            let errors = parse(source).unwrap_err();
+            assert_eq!(errors[0].message, expected, "{}", source);
+        }
+    }
+
+    /// The line each error starts on, in the order they were found.
+    fn error_lines(source: &str) -> Vec<usize> {
+        let errors = parse(source).unwrap_err();
+        errors.iter().map(|error| error.span.start.line).collect()
+    }
+
+    #[test]
+    fn every_syntax_error_is_reported() {
+        let source = "fn main() {\n    var x int = 1;\n    var y: = 2;\n    print(;\n    return 0;\n}\n\
+                      fn f( {\n}\n";
+        assert_eq!(error_lines(source), [2, 3, 4, 7]);
+    }
+
+    #[test]
+    fn consecutive_errors_in_a_block_are_capped() {
+        let source = format!(
+            "fn main() {{\n{}    return 0;\n}}\nfn f() {{\n    var;\n    return 0;\n}}\n",
+            "    var;\n".repeat(MAX_CONSECUTIVE_ERRORS * 2)
+        );
+        // The rest of `main` is skipped after the cap, but `f` is still parsed.
+        let mut expected: Vec<usize> = (2..2 + MAX_CONSECUTIVE_ERRORS).collect();
+        expected.push(5 + 2 * MAX_CONSECUTIVE_ERRORS);
+        assert_eq!(error_lines(&source), expected);
+    }
+
+    #[test]
+    fn an_unclosed_paren_points_at_the_opener() {
+        let errors = parse("fn main() {\n    print(1;\n    return 0;\n}\n").unwrap_err();
+        assert_eq!(errors[0].message, "unclosed '('");
+        assert_eq!(errors[0].span.start, Position::new(2, 10));
+        assert_eq!(errors[0].labels[0].span.start, Position::new(2, 12));
+    }
+
+    #[test]
+    fn a_stray_closing_brace_is_unmatched() {
+        let errors = parse("fn main() {\n    return 0;\n}\n}\n").unwrap_err();
+        assert_eq!(errors[0].message, "unmatched '}'");
+        assert_eq!(errors[0].span.start, Position::new(4, 1));
+    }
+
+    #[test]
+    fn assignment_in_a_condition_suggests_equality() {
+        let source = "fn main() {\n    var x: int = 1;\n    if x = 5 {\n    }\n    return 0;\n}\n";
+        let errors = parse(source).unwrap_err();
+        assert_eq!(errors[0].message, "assignment is not an expression");
+        assert_eq!(errors[0].span.start, Position::new(3, 10));
+        assert_eq!(errors[0].notes[0].message, "did you mean '=='?");
+    }
+
+    #[test]
+    fn equality_in_a_declaration_suggests_assignment() {
+        let errors = parse("fn main() {\n    var x: int == 5;\n    return 0;\n}\n").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "`x` must be initialized with '=', not '=='"
+        );
+        assert_eq!(errors[0].span.start, Position::new(2, 16));
+    }
+
+    #[test]
+    fn a_file_ending_mid_function_points_at_the_end() {
+        // The end of the file is just past its last character, the newline if there is one.
+        let cases = [("\n", Position::new(4, 1)), ("", Position::new(3, 14))];
+        for (ending, end) in cases {
+            let source = format!("fn main() {{\n    var x: int = 1;\n    print(x);{}", ending);
+            let errors = parse(&source).unwrap_err();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].message, "unclosed '{'");
+            assert_eq!(errors[0].span.start, Position::new(1, 11));
+            let label = &errors[0].labels[0];
+            assert_eq!(label.message, "the file ends before it is closed");
+            assert_eq!(label.span.start, end);
         }
     }
 }
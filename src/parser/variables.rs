@@ -1,35 +1,71 @@
 use crate::{
-    common::{CompileError, Keyword, Stmt, Token},
+    common::{CompileError, ConstDecl, Keyword, Stmt, Token},
+    diagnostics::ErrorCode,
     parser::{Parser, expressions},
+    type_system::keyword_to_type,
 };
 
 pub fn parse_variable_decl(parser: &mut Parser) -> Result<Stmt, CompileError> {
+    let start = parser.current_token.1;
     parser.expect_keyword(Keyword::Var)?;
     let name = parser.parse_identifier()?;
     parser.expect(Token::Colon)?;
-    let type_name = match parser.current_token.0 {
-        Token::Keyword(Keyword::Int) => "int",
-        Token::Keyword(Keyword::Bool) => "bool",
-        _ => {
-            return Err(CompileError::new(
-                "Expected known type after variable declaration",
-                parser.current_token.1.clone(),
-            ));
-        }
+    let var_type = if let Token::Keyword(kw) = &parser.current_token.0 {
+        keyword_to_type(kw, &parser.current_token.1)?
+    } else {
+        return Err(parser.unexpected("a type"));
     };
     parser.advance();
+    if parser.current_token.0 == Token::Equality {
+        return Err(CompileError::new(
+            format!("`{}` must be initialized with '=', not '=='", name),
+            parser.current_token.1,
+        )
+        .with_code(ErrorCode::MisusedAssignment)
+        .with_help("'==' compares two values; use '=' to give the variable its value"));
+    }
     parser.expect(Token::Equals)?;
     let value = expressions::parse_expression(parser)?;
+    let span = parser.span_from(start);
+    parser.expect_semicolon()?;
     Ok(Stmt::VariableDecl {
         name,
-        type_name: type_name.to_string(),
+        var_type,
         value,
+        span,
+    })
+}
+
+/// Parses `const NAME: type = value;` at the top level of the file.
+pub fn parse_const_decl(parser: &mut Parser) -> Result<ConstDecl, CompileError> {
+    let start = parser.current_token.1;
+    parser.expect_keyword(Keyword::Const)?;
+    let name = parser.parse_identifier()?;
+    parser.expect(Token::Colon)?;
+    let ty = if let Token::Keyword(kw) = &parser.current_token.0 {
+        keyword_to_type(kw, &parser.current_token.1)?
+    } else {
+        return Err(parser.unexpected("a type"));
+    };
+    parser.advance();
+    parser.expect(Token::Equals)?;
+    let value = expressions::parse_expression(parser)?;
+    let span = parser.span_from(start);
+    parser.expect_semicolon()?;
+    Ok(ConstDecl {
+        name,
+        ty,
+        value,
+        span,
     })
 }
 
 pub fn parse_variable_assignment(parser: &mut Parser) -> Result<Stmt, CompileError> {
+    let start = parser.current_token.1;
     let name = parser.parse_identifier()?;
     parser.expect(Token::Equals)?;
     let value = expressions::parse_expression(parser)?;
-    Ok(Stmt::Assignment { name, value })
+    let span = parser.span_from(start);
+    parser.expect_semicolon()?;
+    Ok(Stmt::Assignment { name, value, span })
 }
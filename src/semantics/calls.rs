@@ -0,0 +1,181 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    common::{CompileWarning, Expr, Stmt},
+    lint::Lint,
+    semantics::Analyzer,
+};
+
+pub fn check_unused_functions(analyzer: &mut Analyzer) {
+    let program = analyzer.program;
+    let call_graph = build_call_graph(&program.functions);
+    if !call_graph.contains_key(analyzer.entry) {
+        // Without an entry point everything would be reported; the missing main is the real error.
+        return;
+    }
+
+    // Everything reachable from the entry point or from a `pub` function, which code outside the
+    // program may call, is used; anything only called by unused functions is not.
+    let mut reachable: HashSet<&str> = HashSet::new();
+    let mut pending: Vec<&str> = program
+        .functions
+        .iter()
+        .filter_map(|func| match func {
+            Stmt::Function {
+                name, public: true, ..
+            } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    pending.push(analyzer.entry);
+    while let Some(name) = pending.pop() {
+        if !reachable.insert(name) {
+            continue;
+        }
+        if let Some(callees) = call_graph.get(name) {
+            pending.extend(callees.iter().copied());
+        }
+    }
+
+    for func in &program.functions {
+        if let Stmt::Function { name, span, .. } = func
+            && name != analyzer.entry
+            && !reachable.contains(name.as_str())
+        {
+            analyzer.warn(CompileWarning::new(
+                Lint::DeadCode,
+                format!("function `{}` is never called", name),
+                *span,
+            ));
+        }
+    }
+    let mut reported = HashSet::new();
+    for ext in &program.externs {
+        if !reachable.contains(ext.name.as_str()) && reported.insert(ext.name.as_str()) {
+            analyzer.warn(CompileWarning::new(
+                Lint::UnusedExtern,
+                format!("extern `{}` is declared but never used", ext.name),
+                ext.span,
+            ));
+        }
+    }
+}
+
+/// Maps each function name to the names of everything it calls, builtins included.
+pub fn build_call_graph(functions: &[Stmt]) -> HashMap<&str, Vec<&str>> {
+    let mut graph = HashMap::new();
+    for func in functions {
+        if let Stmt::Function { name, body, .. } = func {
+            let mut callees = Vec::new();
+            collect_calls_in_block(body, &mut callees);
+            graph.insert(name.as_str(), callees);
+        }
+    }
+    graph
+}
+
+fn collect_calls_in_block<'a>(stmts: &'a [Stmt], callees: &mut Vec<&'a str>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Function { .. } => {}
+            Stmt::Return { value: expr, .. } | Stmt::ExprStmt { expr, .. } => {
+                collect_calls_in_expr(expr, callees)
+            }
+            Stmt::VariableDecl { value, .. } | Stmt::Assignment { value, .. } => {
+                collect_calls_in_expr(value, callees)
+            }
+            Stmt::IfStatement {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                collect_calls_in_expr(condition, callees);
+                collect_calls_in_block(body, callees);
+                if let Some(else_body) = else_body {
+                    collect_calls_in_block(else_body, callees);
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                collect_calls_in_expr(condition, callees);
+                collect_calls_in_block(body, callees);
+            }
+        }
+    }
+}
+
+fn collect_calls_in_expr<'a>(expr: &'a Expr, callees: &mut Vec<&'a str>) {
+    match expr {
+        Expr::Call { callee, args, .. } => {
+            callees.push(callee.as_str());
+            for arg in args {
+                collect_calls_in_expr(arg, callees);
+            }
+        }
+        Expr::BinaryOperator { left, right, .. } => {
+            collect_calls_in_expr(left, callees);
+            collect_calls_in_expr(right, callees);
+        }
+        Expr::BooleanComparison { lvalue, rvalue, .. } => {
+            collect_calls_in_expr(lvalue, callees);
+            collect_calls_in_expr(rvalue, callees);
+        }
+        Expr::Variable { .. }
+        | Expr::StringLiteral { .. }
+        | Expr::IntegerLiteral { .. }
+        | Expr::BooleanLiteral { .. } => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::semantics::analyze_source;
+
+    fn warnings(source: &str) -> Vec<String> {
+        let analysis = analyze_source(source);
+        assert!(analysis.errors.is_empty(), "{}", analysis.errors[0].message);
+        analysis
+            .warnings
+            .into_iter()
+            .map(|warning| warning.message)
+            .collect()
+    }
+
+    #[test]
+    fn functions_only_called_by_unused_functions_are_unused() {
+        let source = "fn helper() { return 1; }\n\
+                      fn unused() { return helper(); }\n\
+                      fn main() { return 0; }";
+        assert_eq!(
+            warnings(source),
+            [
+                "function `helper` is never called",
+                "function `unused` is never called"
+            ]
+        );
+    }
+
+    #[test]
+    fn externs_only_called_by_unused_functions_are_unused() {
+        let source = "extern fn abs(x: int) int;\n\
+                      fn unused() { return abs(1); }\n\
+                      fn main() { return 0; }";
+        assert_eq!(
+            warnings(source),
+            [
+                "function `unused` is never called",
+                "extern `abs` is declared but never used"
+            ]
+        );
+    }
+
+    #[test]
+    fn functions_called_from_pub_functions_are_used() {
+        let source = "fn helper() { return 1; }\n\
+                      pub fn api() { return helper(); }\n\
+                      fn main() { return 0; }";
+        assert_eq!(warnings(source), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{
+    common::{CompileWarning, Expr, Stmt, Token},
+    lint::Lint,
+    semantics::Analyzer,
+};
+
+pub fn check_constant_conditions(analyzer: &mut Analyzer) {
+    let program = analyzer.program;
+    for func in &program.functions {
+        if let Stmt::Function { body, .. } = func {
+            check_block(analyzer, body);
+        }
+    }
+}
+
+fn check_block(analyzer: &mut Analyzer, stmts: &[Stmt]) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::IfStatement {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                if let Some(value) = constant_value(condition) {
+                    let warning = CompileWarning::new(
+                        Lint::ConstantCondition,
+                        format!("condition is always {}", value),
+                        condition.span(),
+                    );
+                    analyzer.warn(match (value, else_body) {
+                        (true, Some(_)) => warning.with_note("the `else` branch is never executed"),
+                        (true, None) => warning,
+                        (false, _) => warning.with_note("the body of this `if` is never executed"),
+                    });
+                }
+                check_block(analyzer, body);
+                if let Some(else_body) = else_body {
+                    check_block(analyzer, else_body);
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                match constant_value(condition) {
+                    // `while true` is the idiomatic infinite loop, so only a derived constant is
+                    // suspicious.
+                    Some(true) if !matches!(condition, Expr::BooleanLiteral { .. }) => {
+                        analyzer.warn(
+                            CompileWarning::new(
+                                Lint::ConstantCondition,
+                                "condition is always true",
+                                condition.span(),
+                            )
+                            .with_note("this loop never terminates"),
+                        );
+                    }
+                    Some(false) => {
+                        analyzer.warn(
+                            CompileWarning::new(
+                                Lint::ConstantCondition,
+                                "condition is always false",
+                                condition.span(),
+                            )
+                            .with_note("the body of this loop is never executed"),
+                        );
+                    }
+                    _ => {}
+                }
+                check_block(analyzer, body);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Structurally evaluates a condition made of literals, or of a variable compared with itself.
+fn constant_value(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::BooleanLiteral { value, .. } => Some(*value),
+        Expr::BooleanComparison {
+            lvalue,
+            operator,
+            rvalue,
+            ..
+        } => match (lvalue.as_ref(), rvalue.as_ref()) {
+            (Expr::IntegerLiteral { value: l, .. }, Expr::IntegerLiteral { value: r, .. }) => {
+                Some(compare(operator, l, r))
+            }
+            (Expr::BooleanLiteral { value: l, .. }, Expr::BooleanLiteral { value: r, .. }) => {
+                Some(compare(operator, l, r))
+            }
+            (Expr::Variable { name: l, .. }, Expr::Variable { name: r, .. }) if l == r => {
+                Some(compare(operator, &0, &0))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+pub fn compare<T: PartialOrd>(operator: &Token, left: &T, right: &T) -> bool {
+    match operator {
+        Token::Equality => left == right,
+        Token::NotEqual => left != right,
+        Token::LessThan => left < right,
+        Token::LessThanOrEqual => left <= right,
+        Token::GreaterThan => left > right,
+        Token::GreaterThanOrEqual => left >= right,
+        _ => false,
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{
+    common::{CompileError, ExternFunction, RUNTIME_FUNCTIONS, Stmt, Type},
+    diagnostics::ErrorCode,
+    semantics::Analyzer,
+};
+
+pub fn check_declarations(analyzer: &mut Analyzer) {
+    let program = analyzer.program;
+    for (i, func) in program.functions.iter().enumerate() {
+        let Stmt::Function { name, span, .. } = func else {
+            continue;
+        };
+        let first = program.functions[..i].iter().find_map(|other| match other {
+            Stmt::Function {
+                name: other_name,
+                span,
+                ..
+            } if other_name == name => Some(*span),
+            _ => None,
+        });
+        if let Some(first) = first {
+            analyzer.error(
+                CompileError::new(format!("`{}` is defined more than once", name), *span)
+                    .with_code(ErrorCode::DuplicateDefinition)
+                    .with_label(first, "first defined here"),
+            );
+        }
+        if let Some(ext) = program.externs.iter().find(|ext| &ext.name == name) {
+            analyzer.error(
+                CompileError::new(
+                    format!(
+                        "`{}` is defined here but is also declared as an extern",
+                        name
+                    ),
+                    *span,
+                )
+                .with_code(ErrorCode::ConflictingDeclarations)
+                .with_label(ext.span, "the extern declaration is here"),
+            );
+        }
+    }
+
+    for (i, decl) in program.consts.iter().enumerate() {
+        if let Some(first) = program.consts[..i]
+            .iter()
+            .find(|other| other.name == decl.name)
+        {
+            analyzer.error(
+                CompileError::new(
+                    format!("constant `{}` is defined more than once", decl.name),
+                    decl.span,
+                )
+                .with_code(ErrorCode::DuplicateDefinition)
+                .with_label(first.span, "first defined here"),
+            );
+        }
+        // A constant and a function could be told apart by whether they are called, but one name
+        // for two things is almost certainly a mistake.
+        let function = program
+            .functions
+            .iter()
+            .find_map(|func| match func {
+                Stmt::Function { name, span, .. } if *name == decl.name => {
+                    Some((*span, "the function is defined here"))
+                }
+                _ => None,
+            })
+            .or_else(|| {
+                program
+                    .externs
+                    .iter()
+                    .find(|ext| ext.name == decl.name)
+                    .map(|ext| (ext.span, "the extern declaration is here"))
+            });
+        if let Some((span, label)) = function {
+            analyzer.error(
+                CompileError::new(
+                    format!("constant `{}` has the same name as a function", decl.name),
+                    decl.span,
+                )
+                .with_code(ErrorCode::ConflictingDeclarations)
+                .with_label(span, label),
+            );
+        }
+    }
+
+    for ext in &program.externs {
+        let Some(runtime) = RUNTIME_FUNCTIONS
+            .iter()
+            .find(|runtime| runtime.name == ext.name)
+        else {
+            continue;
+        };
+        if !runtime.accepts(ext) {
+            let mut params: Vec<String> = runtime.params.iter().map(|t| t.to_string()).collect();
+            if runtime.variadic {
+                params.push("...".to_string());
+            }
+            analyzer.error(
+                CompileError::new(
+                    format!(
+                        "extern `{}` conflicts with the declaration the compiler uses for `{}`",
+                        ext.name,
+                        runtime.builtin.name()
+                    ),
+                    ext.span,
+                )
+                .with_code(ErrorCode::ConflictingDeclarations)
+                .with_note(format!(
+                    "`{}` is implemented with the C function `{}({})`",
+                    runtime.builtin.name(),
+                    runtime.name,
+                    params.join(", ")
+                ))
+                .with_note(format!(
+                    "this extern declares it as `{}`",
+                    format_signature(ext)
+                )),
+            );
+        }
+    }
+
+    for (i, ext) in program.externs.iter().enumerate() {
+        let Some(first) = program.externs[..i]
+            .iter()
+            .find(|other| other.name == ext.name)
+        else {
+            continue;
+        };
+        // Identical redeclarations are harmless and are deduplicated by codegen.
+        if !same_signature(first, ext) {
+            analyzer.error(
+                CompileError::new(
+                    format!(
+                        "extern `{}` is redeclared with a different signature",
+                        ext.name
+                    ),
+                    ext.span,
+                )
+                .with_code(ErrorCode::ConflictingDeclarations)
+                .with_label(
+                    first.span,
+                    format!("first declared here as `{}`", format_signature(first)),
+                )
+                .with_note(format!("redeclared here as `{}`", format_signature(ext))),
+            );
+        }
+    }
+}
+
+fn same_signature(a: &ExternFunction, b: &ExternFunction) -> bool {
+    a.return_type == b.return_type
+        && a.is_noreturn() == b.is_noreturn()
+        && a.args.len() == b.args.len()
+        && a.args.iter().zip(&b.args).all(|((_, a), (_, b))| a == b)
+}
+
+fn format_signature(ext: &ExternFunction) -> String {
+    let args: Vec<String> = ext.args.iter().map(|(_, t)| t.to_string()).collect();
+    let mut signature = format!("fn {}({})", ext.name, args.join(", "));
+    if ext.is_noreturn() {
+        signature.push_str(" noreturn");
+    } else if ext.return_type != Type::Void {
+        signature.push_str(&format!(" {}", ext.return_type));
+    }
+    signature
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::semantics::analyze_source;
+
+    fn errors(source: &str) -> Vec<String> {
+        let analysis = analyze_source(source);
+        analysis
+            .errors
+            .into_iter()
+            .map(|error| error.message)
+            .collect()
+    }
+
+    #[test]
+    fn printf_can_be_declared_to_return_nothing_or_an_int() {
+        for return_type in ["", " int"] {
+            let source = format!(
+                "extern fn printf(fmt: str){};\nfn main() {{ printf(\"hi\"); return 0; }}",
+                return_type
+            );
+            assert_eq!(errors(&source), Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    fn printf_cannot_be_declared_to_return_a_str() {
+        let source = "extern fn printf(fmt: str) str;\nfn main() { printf(\"hi\"); return 0; }";
+        assert_eq!(
+            errors(source),
+            ["extern `printf` conflicts with the declaration the compiler uses for `print`"]
+        );
+    }
+
+    #[test]
+    fn a_function_cannot_share_a_name_with_an_extern() {
+        let analysis = analyze_source(
+            "extern fn helper(x: int) int;\nfn helper(x: int) { return x; }\n\
+             fn main() { return helper(1); }",
+        );
+        let error = &analysis.errors[0];
+        assert_eq!(analysis.errors.len(), 1);
+        assert_eq!(
+            error.message,
+            "`helper` is defined here but is also declared as an extern"
+        );
+        assert_eq!(error.span.start.line, 2);
+        assert_eq!(error.labels[0].span.start.line, 1);
+    }
+
+    #[test]
+    fn a_constant_cannot_share_a_name_with_a_function() {
+        assert_eq!(
+            errors(
+                "const helper: int = 1;\nfn helper() { return 2; }\nfn main() { return helper(); }"
+            ),
+            ["constant `helper` has the same name as a function"]
+        );
+        assert_eq!(
+            errors(
+                "const abs: int = 1;\nextern fn abs(x: int) int;\nfn main() { return abs(abs); }"
+            ),
+            ["constant `abs` has the same name as a function"]
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{
+    common::{CompileError, FileId, Position, Span, Stmt},
+    diagnostics::ErrorCode,
+    semantics::Analyzer,
+};
+
+/// Ensures the program has an entry function, `main` unless `--entry` names another, with a
+/// signature the C entry wrapper can call.
+pub fn check_main(analyzer: &mut Analyzer) {
+    let entry = analyzer.entry;
+    let main = analyzer
+        .program
+        .functions
+        .iter()
+        .find(|func| matches!(func, Stmt::Function { name, .. } if name == entry));
+    match main {
+        None => {
+            let mut error = CompileError::new(
+                format!("no `{}` function found in {}", entry, analyzer.source_name),
+                Span::at(FileId(0), Position::new(1, 1)),
+            )
+            .with_code(ErrorCode::MissingMain);
+            if analyzer.program.functions.is_empty() {
+                error = error.with_note("the source file contains no functions");
+            }
+            analyzer.error(error);
+        }
+        Some(Stmt::Function { args, span, .. }) if !args.is_empty() => analyzer.error(
+            CompileError::new(format!("`{}` cannot take parameters", entry), *span)
+                .with_code(ErrorCode::MainWithParameters)
+                .with_note(format!(
+                    "`{}` is called by the C runtime with no arguments",
+                    entry
+                )),
+        ),
+        Some(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::semantics::analyze_source;
+
+    fn errors(source: &str) -> Vec<String> {
+        let analysis = analyze_source(source);
+        analysis
+            .errors
+            .into_iter()
+            .map(|error| error.message)
+            .collect()
+    }
+
+    #[test]
+    fn main_without_parameters_is_accepted() {
+        assert!(errors("fn main() { return 0; }").is_empty());
+    }
+
+    #[test]
+    fn main_cannot_take_parameters() {
+        assert_eq!(
+            errors("fn main(argc: int) { return argc; }"),
+            ["`main` cannot take parameters"]
+        );
+    }
+
+    #[test]
+    fn a_program_needs_a_main() {
+        assert_eq!(
+            errors("fn start() { return 0; }"),
+            ["no `main` function found in test.ratio"]
+        );
+    }
+}
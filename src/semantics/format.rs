@@ -0,0 +1,233 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::common::{CompileError, CompileWarning, Expr, Span, Type};
+use crate::diagnostics::ErrorCode;
+use crate::lint::Lint;
+
+/// A conversion in a Ratio format string: `%d` takes an int (or bool), `%s` takes a str.
+/// `%%` is a literal percent sign and consumes no argument.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FormatSpec {
+    Int,
+    Str,
+}
+
+impl FormatSpec {
+    fn accepts(&self, t: Type) -> bool {
+        match self {
+            FormatSpec::Int => matches!(t, Type::Int | Type::Bool),
+            FormatSpec::Str => t == Type::Str,
+        }
+    }
+
+    fn expected_type(&self) -> Type {
+        match self {
+            FormatSpec::Int => Type::Int,
+            FormatSpec::Str => Type::Str,
+        }
+    }
+}
+
+impl Display for FormatSpec {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            FormatSpec::Int => write!(f, "%d"),
+            FormatSpec::Str => write!(f, "%s"),
+        }
+    }
+}
+
+/// Returns the conversions in the format string, in order.
+pub fn parse_format(format: &str) -> Result<Vec<FormatSpec>, String> {
+    let mut specs = Vec::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        match chars.next() {
+            Some('d') => specs.push(FormatSpec::Int),
+            Some('s') => specs.push(FormatSpec::Str),
+            Some('%') => {}
+            Some(other) => return Err(format!("unknown format specifier `%{}`", other)),
+            None => return Err("format string ends with an incomplete `%`".to_string()),
+        }
+    }
+    Ok(specs)
+}
+
+/// Translates a valid Ratio format string into the printf format codegen passes to libc,
+/// including the newline that `print` always ends with.
+pub fn to_printf_format(format: &str) -> String {
+    // Ratio ints are 64-bit, so `%d` must become `%lld` for printf. `long` is only 32-bit on some
+    // targets, such as WebAssembly.
+    let mut printf_format = String::with_capacity(format.len() + 2);
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        printf_format.push(c);
+        if c == '%' {
+            match chars.next() {
+                Some('d') => printf_format.push_str("lld"),
+                Some(other) => printf_format.push(other),
+                None => {}
+            }
+        }
+    }
+    printf_format.push('\n');
+    printf_format
+}
+
+/// Checks a printf-style call whose first argument is the format string.
+pub fn check_format_call(
+    callee: &str,
+    args: &[Expr],
+    arg_types: &[Option<Type>],
+    span: Span,
+    errors: &mut Vec<CompileError>,
+    warnings: &mut Vec<CompileWarning>,
+) {
+    let format = match &args[0] {
+        Expr::StringLiteral { value, .. } => value,
+        other => {
+            match arg_types[0] {
+                Some(Type::Str) | None => warnings.push(CompileWarning::new(
+                    Lint::NonLiteralFormat,
+                    "format string is not a literal, so its arguments cannot be checked",
+                    other.span(),
+                )),
+                Some(found) => errors.push(
+                    CompileError::new(
+                        format!(
+                            "the format string passed to `{}` must be str, found {}",
+                            callee, found
+                        ),
+                        other.span(),
+                    )
+                    .with_code(ErrorCode::InvalidFormat),
+                ),
+            }
+            return;
+        }
+    };
+
+    let specs = match parse_format(format) {
+        Ok(specs) => specs,
+        Err(message) => {
+            errors.push(
+                CompileError::new(message, args[0].span()).with_code(ErrorCode::InvalidFormat),
+            );
+            return;
+        }
+    };
+    let provided = args.len() - 1;
+    if specs.len() != provided {
+        errors.push(
+            CompileError::new(
+                format!(
+                    "format string expects {} argument{}, {} provided",
+                    specs.len(),
+                    if specs.len() == 1 { "" } else { "s" },
+                    provided
+                ),
+                span,
+            )
+            .with_code(ErrorCode::InvalidFormat),
+        );
+        return;
+    }
+    for (i, spec) in specs.iter().enumerate() {
+        // Argument numbers count the format string as argument 1.
+        let index = i + 1;
+        if let Some(found) = arg_types[index]
+            && !spec.accepts(found)
+        {
+            errors.push(
+                CompileError::new(
+                    format!(
+                        "{} expects {}, argument {} is {}",
+                        spec,
+                        spec.expected_type(),
+                        index + 1,
+                        found
+                    ),
+                    args[index].span(),
+                )
+                .with_code(ErrorCode::InvalidFormat),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FormatSpec, parse_format, to_printf_format};
+    use crate::semantics::analyze_source;
+
+    fn errors(source: &str) -> Vec<String> {
+        let analysis = analyze_source(source);
+        analysis
+            .errors
+            .into_iter()
+            .map(|error| error.message)
+            .collect()
+    }
+
+    #[test]
+    fn conversions_are_found_in_order() {
+        assert_eq!(
+            parse_format("%s is %d%% done"),
+            Ok(vec![FormatSpec::Str, FormatSpec::Int])
+        );
+        assert_eq!(parse_format("no conversions"), Ok(vec![]));
+    }
+
+    #[test]
+    fn bad_conversions_are_rejected() {
+        assert_eq!(
+            parse_format("%x"),
+            Err("unknown format specifier `%x`".to_string())
+        );
+        assert_eq!(
+            parse_format("100%"),
+            Err("format string ends with an incomplete `%`".to_string())
+        );
+    }
+
+    #[test]
+    fn ints_are_printed_as_64_bit() {
+        assert_eq!(to_printf_format("%s: %d%%"), "%s: %lld%%\n");
+    }
+
+    #[test]
+    fn matching_arguments_are_accepted() {
+        let source = "fn main() { print(\"%s is %d\", \"x\", 1); return 0; }";
+        assert!(errors(source).is_empty());
+    }
+
+    #[test]
+    fn the_argument_count_must_match() {
+        let source = "fn main() { print(\"%d and %d\", 1); return 0; }";
+        assert_eq!(
+            errors(source),
+            ["format string expects 2 arguments, 1 provided"]
+        );
+    }
+
+    #[test]
+    fn each_argument_must_match_its_conversion() {
+        let source = "fn main() { print(\"%d %s\", 1, 2); return 0; }";
+        assert_eq!(errors(source), ["%s expects str, argument 3 is int"]);
+    }
+
+    #[test]
+    fn a_format_string_that_is_not_a_literal_is_only_warned_about() {
+        let source = "fn main() { var f: str = \"%d\"; print(f, 1); return 0; }";
+        let analysis = analyze_source(source);
+        assert!(analysis.errors.is_empty());
+        assert_eq!(
+            analysis.warnings[0].message,
+            "format string is not a literal, so its arguments cannot be checked"
+        );
+    }
+}
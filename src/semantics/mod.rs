@@ -0,0 +1,72 @@
+use crate::common::{CompileError, CompileWarning, Program};
+
+pub mod calls;
+pub mod conditions;
+pub mod declarations;
+pub mod entry;
+pub mod format;
+pub mod reachability;
+pub mod types;
+
+/// Everything the semantic checks found. The program may only be compiled if `errors` is empty.
+pub struct Analysis {
+    pub errors: Vec<CompileError>,
+    pub warnings: Vec<CompileWarning>,
+}
+
+pub struct Analyzer<'a> {
+    program: &'a Program,
+    /// The names of the source files, for the error when none of them has a `main`.
+    source_name: &'a str,
+    /// The function execution starts in: `main`, unless `--entry` names another.
+    entry: &'a str,
+    errors: Vec<CompileError>,
+    warnings: Vec<CompileWarning>,
+}
+
+impl<'a> Analyzer<'a> {
+    pub fn new(program: &'a Program, source_name: &'a str, entry: &'a str) -> Self {
+        Self {
+            program,
+            source_name,
+            entry,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Runs every semantic check over the program, continuing past errors so that all of them
+    /// can be reported at once.
+    pub fn analyze(mut self) -> Analysis {
+        entry::check_main(&mut self);
+        declarations::check_declarations(&mut self);
+        calls::check_unused_functions(&mut self);
+        reachability::check_unreachable_code(&mut self);
+        conditions::check_constant_conditions(&mut self);
+        types::check_types(&mut self);
+        Analysis {
+            errors: self.errors,
+            warnings: self.warnings,
+        }
+    }
+
+    fn error(&mut self, error: CompileError) {
+        self.errors.push(error);
+    }
+
+    fn warn(&mut self, warning: CompileWarning) {
+        self.warnings.push(warning);
+    }
+}
+
+/// Analyzes `source` as the whole program, for testing the checks. It must parse.
+#[cfg(test)]
+pub fn analyze_source(source: &str) -> Analysis {
+    use crate::common::FileId;
+    use crate::loader;
+
+    let program = loader::lex(source, FileId(0))
+        .and_then(|tokens| loader::parse(tokens, FileId(0)))
+        .unwrap_or_else(|errors| panic!("the test program does not parse: {}", errors[0].message));
+    Analyzer::new(&program, "test.ratio", "main").analyze()
+}
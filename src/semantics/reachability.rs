@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+
+use crate::{
+    common::{CompileWarning, Expr, Span, Stmt},
+    lint::Lint,
+    semantics::Analyzer,
+};
+
+pub fn check_unreachable_code(analyzer: &mut Analyzer) {
+    let program = analyzer.program;
+    let noreturn: HashSet<&str> = program
+        .externs
+        .iter()
+        .filter(|ext| ext.is_noreturn())
+        .map(|ext| ext.name.as_str())
+        .collect();
+    for func in &program.functions {
+        if let Stmt::Function { body, .. } = func {
+            check_block(analyzer, &noreturn, body);
+        }
+    }
+}
+
+fn check_block(analyzer: &mut Analyzer, noreturn: &HashSet<&str>, stmts: &[Stmt]) {
+    let mut terminator: Option<(String, Span)> = None;
+    for stmt in stmts {
+        if let Some((kind, span)) = terminator {
+            analyzer.warn(
+                CompileWarning::new(Lint::UnreachableCode, "unreachable statement", stmt.span())
+                    .with_label(
+                        span,
+                        format!("execution never continues past this {}", kind),
+                    ),
+            );
+            // One warning per block is enough; the rest of the block is dead for the same reason.
+            return;
+        }
+
+        match stmt {
+            Stmt::IfStatement {
+                body, else_body, ..
+            } => {
+                check_block(analyzer, noreturn, body);
+                if let Some(else_body) = else_body {
+                    check_block(analyzer, noreturn, else_body);
+                }
+            }
+            Stmt::While { body, .. } => check_block(analyzer, noreturn, body),
+            _ => {}
+        }
+        terminator = terminating_kind(noreturn, stmt).map(|kind| (kind, stmt.span()));
+    }
+}
+
+/// Describes the statement if control flow can never continue past it.
+fn terminating_kind(noreturn: &HashSet<&str>, stmt: &Stmt) -> Option<String> {
+    match stmt {
+        Stmt::Return { .. } => Some("`return`".to_string()),
+        Stmt::ExprStmt {
+            expr: Expr::Call { callee, .. },
+            ..
+        } if noreturn.contains(callee.as_str()) => Some(format!("call to `{}`", callee)),
+        _ => None,
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+
+use crate::{
+    common::{
+        Builtin, CompileError, CompileWarning, Expr, FUNCTION_RETURN_TYPE, Span, Stmt, Token, Type,
+    },
+    diagnostics::ErrorCode,
+    semantics::{Analyzer, format},
+};
+
+struct Signature<'a> {
+    params: &'a [(String, Type)],
+    return_type: Type,
+}
+
+pub fn check_types(analyzer: &mut Analyzer) {
+    let program = analyzer.program;
+    let mut signatures = HashMap::new();
+    for ext in &program.externs {
+        signatures.entry(ext.name.as_str()).or_insert(Signature {
+            params: &ext.args,
+            return_type: ext.return_type,
+        });
+    }
+    for func in &program.functions {
+        if let Stmt::Function { name, args, .. } = func {
+            signatures.insert(
+                name.as_str(),
+                Signature {
+                    params: args,
+                    return_type: FUNCTION_RETURN_TYPE,
+                },
+            );
+        }
+    }
+
+    // Each constant can use the ones declared before it, and functions can use all of them.
+    let mut consts: HashMap<String, Type> = HashMap::new();
+    for decl in &program.consts {
+        let mut checker = TypeChecker {
+            signatures: &signatures,
+            variables: consts.clone(),
+            errors: &mut analyzer.errors,
+            warnings: &mut analyzer.warnings,
+        };
+        if let Some(found) = checker.infer(&decl.value)
+            && found != decl.ty
+        {
+            analyzer.errors.push(
+                CompileError::new(
+                    format!(
+                        "constant `{}` is declared as {}, but its value is {}",
+                        decl.name, decl.ty, found
+                    ),
+                    decl.value.span(),
+                )
+                .with_code(ErrorCode::MismatchedTypes),
+            );
+        }
+        consts.entry(decl.name.clone()).or_insert(decl.ty);
+    }
+
+    for func in &program.functions {
+        if let Stmt::Function { args, body, .. } = func {
+            let mut variables = consts.clone();
+            variables.extend(args.iter().cloned());
+            let mut checker = TypeChecker {
+                signatures: &signatures,
+                variables,
+                errors: &mut analyzer.errors,
+                warnings: &mut analyzer.warnings,
+            };
+            checker.check_block(body);
+        }
+    }
+}
+
+struct TypeChecker<'a> {
+    signatures: &'a HashMap<&'a str, Signature<'a>>,
+    // Variables are function-scoped, matching how codegen allocates them.
+    variables: HashMap<String, Type>,
+    errors: &'a mut Vec<CompileError>,
+    warnings: &'a mut Vec<CompileWarning>,
+}
+
+impl TypeChecker<'_> {
+    fn check_block(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.check_stmt(stmt);
+        }
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Function { .. } => {}
+            Stmt::Return { value, span } => {
+                if let Some(found) = self.infer(value)
+                    && found != FUNCTION_RETURN_TYPE
+                {
+                    self.errors.push(
+                        CompileError::new(
+                            format!(
+                                "functions return {}, but this value is {}",
+                                FUNCTION_RETURN_TYPE, found
+                            ),
+                            *span,
+                        )
+                        .with_code(ErrorCode::MismatchedTypes),
+                    );
+                }
+            }
+            Stmt::ExprStmt { expr, .. } => {
+                self.infer(expr);
+            }
+            Stmt::VariableDecl {
+                name,
+                var_type,
+                value,
+                ..
+            } => {
+                if let Some(found) = self.infer(value)
+                    && found != *var_type
+                {
+                    self.errors.push(
+                        CompileError::new(
+                            format!(
+                                "`{}` is declared as {}, but its initializer is {}",
+                                name, var_type, found
+                            ),
+                            value.span(),
+                        )
+                        .with_code(ErrorCode::MismatchedTypes),
+                    );
+                }
+                self.variables.insert(name.clone(), *var_type);
+            }
+            Stmt::Assignment { name, value, .. } => {
+                let found = self.infer(value);
+                if let (Some(declared), Some(found)) = (self.variables.get(name).copied(), found)
+                    && found != declared
+                {
+                    self.errors.push(
+                        CompileError::new(
+                            format!(
+                                "cannot assign {} to `{}`, which is declared as {}",
+                                found, name, declared
+                            ),
+                            value.span(),
+                        )
+                        .with_code(ErrorCode::MismatchedTypes),
+                    );
+                }
+            }
+            Stmt::IfStatement {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                self.infer(condition);
+                self.check_block(body);
+                if let Some(else_body) = else_body {
+                    self.check_block(else_body);
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.infer(condition);
+                self.check_block(body);
+            }
+        }
+    }
+
+    /// Returns the type of the expression, or `None` when it cannot be determined (such as an
+    /// unknown variable or a call to an unknown function), in which case no further checks are
+    /// made on it.
+    /// Operators that fail to type-check still report their usual result type, so one mistake
+    /// does not cascade into errors on the surrounding expression.
+    fn infer(&mut self, expr: &Expr) -> Option<Type> {
+        match expr {
+            Expr::IntegerLiteral { .. } => Some(Type::Int),
+            Expr::BooleanLiteral { .. } => Some(Type::Bool),
+            Expr::StringLiteral { .. } => Some(Type::Str),
+            Expr::Variable { name, .. } => self.variables.get(name).copied(),
+            Expr::Call { callee, args, span } => {
+                let arg_types: Vec<Option<Type>> = args.iter().map(|arg| self.infer(arg)).collect();
+                if let Some(builtin) = Builtin::from_name(callee) {
+                    self.check_builtin_call(builtin, args, &arg_types, *span);
+                    Some(builtin.signature().return_type)
+                } else if let Some(signature) = self.signatures.get(callee.as_str()) {
+                    self.check_call(callee, signature.params, args, &arg_types, *span);
+                    Some(signature.return_type)
+                } else {
+                    None
+                }
+            }
+            Expr::BinaryOperator {
+                operator,
+                left,
+                right,
+                span,
+            } => {
+                let left = self.infer(left);
+                let right = self.infer(right);
+                if let Err(e) = check_arithmetic_operands(operator, left, right, *span) {
+                    self.errors.push(e);
+                }
+                Some(Type::Int)
+            }
+            Expr::BooleanComparison {
+                lvalue,
+                operator,
+                operator_span,
+                rvalue,
+                ..
+            } => {
+                let left = self.infer(lvalue);
+                let right = self.infer(rvalue);
+                if let (Some(left), Some(right)) = (left, right)
+                    && let Err(e) = check_comparison_operands(operator, left, right, *operator_span)
+                {
+                    self.errors.push(e);
+                }
+                Some(Type::Bool)
+            }
+        }
+    }
+
+    fn check_call(
+        &mut self,
+        callee: &str,
+        params: &[(String, Type)],
+        args: &[Expr],
+        arg_types: &[Option<Type>],
+        span: Span,
+    ) {
+        if args.len() != params.len() {
+            self.errors.push(
+                CompileError::new(
+                    format!(
+                        "`{}` expects {}, but {}",
+                        callee,
+                        count_arguments(params.len()),
+                        count_provided(args.len())
+                    ),
+                    span,
+                )
+                .with_code(ErrorCode::WrongArgumentCount),
+            );
+            return;
+        }
+        for (i, ((_, expected), (arg, found))) in
+            params.iter().zip(args.iter().zip(arg_types)).enumerate()
+        {
+            if let Some(found) = found
+                && found != expected
+            {
+                self.errors.push(
+                    CompileError::new(
+                        format!(
+                            "argument {} of `{}` expects {}, found {}",
+                            i + 1,
+                            callee,
+                            expected,
+                            found
+                        ),
+                        arg.span(),
+                    )
+                    .with_code(ErrorCode::MismatchedArgument),
+                );
+            }
+        }
+    }
+
+    fn check_builtin_call(
+        &mut self,
+        builtin: Builtin,
+        args: &[Expr],
+        arg_types: &[Option<Type>],
+        span: Span,
+    ) {
+        let signature = builtin.signature();
+        let count = args.len();
+        let arity_ok =
+            count >= signature.min_args && signature.max_args.is_none_or(|max| count <= max);
+        if !arity_ok {
+            let expected = match signature.max_args {
+                Some(max) if max == signature.min_args => count_arguments(max),
+                Some(max) => format!(
+                    "between {} and {}",
+                    signature.min_args,
+                    count_arguments(max)
+                ),
+                None => format!("at least {}", count_arguments(signature.min_args)),
+            };
+            self.errors.push(
+                CompileError::new(
+                    format!(
+                        "`{}` expects {}, but {}",
+                        builtin.name(),
+                        expected,
+                        count_provided(count)
+                    ),
+                    span,
+                )
+                .with_code(ErrorCode::WrongArgumentCount),
+            );
+            return;
+        }
+        for (i, (arg, found)) in args.iter().zip(arg_types).enumerate() {
+            if let Some(found) = found
+                && !signature.accepted_types.contains(found)
+            {
+                let accepted: Vec<String> = signature
+                    .accepted_types
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect();
+                self.errors.push(
+                    CompileError::new(
+                        format!(
+                            "argument {} of `{}` expects {}, found {}",
+                            i + 1,
+                            builtin.name(),
+                            accepted.join(" or "),
+                            found
+                        ),
+                        arg.span(),
+                    )
+                    .with_code(ErrorCode::MismatchedArgument),
+                );
+            }
+        }
+        if builtin == Builtin::Print && count > 1 {
+            format::check_format_call(
+                builtin.name(),
+                args,
+                arg_types,
+                span,
+                self.errors,
+                self.warnings,
+            );
+        }
+    }
+}
+
+fn count_arguments(count: usize) -> String {
+    match count {
+        1 => "1 argument".to_string(),
+        _ => format!("{} arguments", count),
+    }
+}
+
+fn count_provided(count: usize) -> String {
+    match count {
+        1 => "1 was provided".to_string(),
+        _ => format!("{} were provided", count),
+    }
+}
+
+fn check_comparison_operands(
+    operator: &Token,
+    left: Type,
+    right: Type,
+    span: Span,
+) -> Result<(), CompileError> {
+    match (left, right) {
+        (Type::Int, Type::Int) => Ok(()),
+        (Type::Bool, Type::Bool) if matches!(operator, Token::Equality | Token::NotEqual) => Ok(()),
+        (Type::Bool, Type::Bool) => Err(CompileError::new(
+            format!(
+                "operator `{}` cannot be applied to bool and bool",
+                comparison_symbol(operator)
+            ),
+            span,
+        )
+        .with_code(ErrorCode::InvalidOperands)),
+        (Type::Str, Type::Str) => Err(CompileError::new(
+            "cannot compare str with str: string comparison is not supported yet",
+            span,
+        )
+        .with_code(ErrorCode::InvalidOperands)),
+        (left, right) => Err(CompileError::new(
+            format!("cannot compare {} with {}", left, right),
+            span,
+        )
+        .with_code(ErrorCode::InvalidOperands)),
+    }
+}
+
+/// Arithmetic is only defined on ints. Bools are stored as integers by codegen, but `true + 1` is
+/// deliberately rejected rather than silently treating `true` as 1.
+fn check_arithmetic_operands(
+    operator: &str,
+    left: Option<Type>,
+    right: Option<Type>,
+    span: Span,
+) -> Result<(), CompileError> {
+    match (left, right) {
+        (Some(Type::Int), Some(Type::Int)) | (None, _) | (_, None) => Ok(()),
+        (Some(Type::Str), Some(Type::Str)) if operator == "+" => Err(CompileError::new(
+            "operator `+` cannot be applied to str and str: string concatenation is not \
+             supported yet",
+            span,
+        )
+        .with_code(ErrorCode::InvalidOperands)),
+        (Some(left), Some(right)) => Err(CompileError::new(
+            format!(
+                "operator `{}` cannot be applied to {} and {}",
+                operator, left, right
+            ),
+            span,
+        )
+        .with_code(ErrorCode::InvalidOperands)),
+    }
+}
+
+fn comparison_symbol(operator: &Token) -> &'static str {
+    match operator {
+        Token::Equality => "==",
+        Token::NotEqual => "!=",
+        Token::LessThan => "<",
+        Token::LessThanOrEqual => "<=",
+        Token::GreaterThan => ">",
+        Token::GreaterThanOrEqual => ">=",
+        _ => "?",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::semantics::analyze_source;
+
+    fn errors(source: &str) -> Vec<String> {
+        let analysis = analyze_source(source);
+        analysis
+            .errors
+            .into_iter()
+            .map(|error| error.message)
+            .collect()
+    }
+
+    #[test]
+    fn a_function_can_return_an_int() {
+        assert!(errors("fn main() { return 1 + 2; }").is_empty());
+    }
+
+    #[test]
+    fn returning_a_str_or_bool_is_an_error() {
+        assert_eq!(
+            errors("fn main() { return \"no\"; }"),
+            ["functions return int, but this value is str"]
+        );
+        assert_eq!(
+            errors("fn main() { return 1 < 2; }"),
+            ["functions return int, but this value is bool"]
+        );
+    }
+}
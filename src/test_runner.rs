@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::file_io;
+use crate::lexer;
+
+/// What a test file expects, from its `// expect:` and `// compile-fail:` comments.
+struct Expectations {
+    /// Each line the program should print, in order.
+    output: Vec<String>,
+    /// Text that should appear in the compiler's errors, if the file should not compile.
+    compile_fail: Option<String>,
+}
+
+impl Expectations {
+    fn parse(input: &str) -> Self {
+        let mut expectations = Expectations {
+            output: Vec::new(),
+            compile_fail: None,
+        };
+        for comment in lexer::scan_comments(input) {
+            let text = comment.trim_start();
+            if let Some(line) = text.strip_prefix("expect:") {
+                // One space after the colon is part of the syntax, so that a line can still
+                // start with spaces of its own.
+                let line = line.strip_prefix(' ').unwrap_or(line);
+                expectations.output.push(line.to_string());
+            } else if let Some(message) = text.strip_prefix("compile-fail:") {
+                expectations.compile_fail = Some(message.trim().to_string());
+            }
+        }
+        expectations
+    }
+}
+
+/// Compiles and runs every `.ratio` file under `dir`, checking each against the directives in
+/// its comments, and prints a line per test followed by the failures and a summary. Returns
+/// whether every test passed.
+///
+/// Each file is compiled by running `compiler` with `compiler_args`, so that a file that does
+/// not compile fails its test instead of ending the run. A program's output must match its
+/// `// expect: <line>` comments line for line, and a program with none must print nothing. A
+/// file with `// compile-fail: <message>` must instead fail to compile with an error containing
+/// that message.
+pub fn run_tests(dir: &Path, compiler: &Path, compiler_args: &[String]) -> Result<bool, String> {
+    let mut files = Vec::new();
+    find_tests(dir, &mut files)
+        .map_err(|error| format!("cannot read `{}`: {}", dir.display(), error))?;
+    files.sort();
+
+    let build_dir = std::env::temp_dir().join(format!("ratio-test-{}", std::process::id()));
+    std::fs::create_dir_all(&build_dir)
+        .map_err(|error| format!("cannot create a temporary directory: {}", error))?;
+    let mut failures = Vec::new();
+    for (i, file) in files.iter().enumerate() {
+        let executable = build_dir.join(i.to_string());
+        let result = run_test(file, &executable, compiler, compiler_args);
+        let status = if result.is_ok() { "ok" } else { "FAILED" };
+        println!("test {} ... {}", file.display(), status);
+        if let Err(message) = result {
+            failures.push((file, message));
+        }
+    }
+    let _ = std::fs::remove_dir_all(&build_dir);
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (file, message) in &failures {
+            println!("\n---- {} ----\n{}", file.display(), message.trim_end());
+        }
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        files.len() - failures.len(),
+        failures.len()
+    );
+    Ok(failures.is_empty())
+}
+
+/// Adds every `.ratio` file in `dir` and its subdirectories to `files`.
+fn find_tests(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_tests(&path, files)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "ratio")
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Runs one test, returning why it failed.
+fn run_test(
+    file: &Path,
+    executable: &Path,
+    compiler: &Path,
+    compiler_args: &[String],
+) -> Result<(), String> {
+    let input = file_io::read_file(file).map_err(|error| format!("cannot read it: {}", error))?;
+    let expectations = Expectations::parse(&input);
+
+    let compile = Command::new(compiler)
+        .arg(file)
+        .arg("-o")
+        .arg(executable)
+        .args(compiler_args)
+        .output()
+        .map_err(|error| format!("cannot run the compiler: {}", error))?;
+    let errors = String::from_utf8_lossy(&compile.stderr);
+    match (&expectations.compile_fail, compile.status.success()) {
+        (None, true) => {}
+        (None, false) => return Err(format!("it did not compile:\n{}", errors)),
+        (Some(message), true) => {
+            return Err(format!(
+                "it compiled, but was expected to fail with `{}`",
+                message
+            ));
+        }
+        (Some(message), false) if errors.contains(message.as_str()) => return Ok(()),
+        (Some(message), false) => {
+            return Err(format!(
+                "it failed to compile without mentioning `{}`:\n{}",
+                message, errors
+            ));
+        }
+    }
+
+    let run = Command::new(executable)
+        .output()
+        .map_err(|error| format!("cannot run it: {}", error))?;
+    let stdout = String::from_utf8_lossy(&run.stdout);
+    let output: Vec<&str> = stdout.lines().collect();
+    if output == expectations.output {
+        return Ok(());
+    }
+    let mut message = String::from("its output did not match:\n");
+    for (i, expected) in expectations.output.iter().enumerate() {
+        match output.get(i) {
+            Some(line) if line == expected => {}
+            Some(line) => {
+                message.push_str(&format!(
+                    "line {}: expected `{}`, found `{}`\n",
+                    i + 1,
+                    expected,
+                    line
+                ));
+            }
+            None => message.push_str(&format!(
+                "line {}: expected `{}`, found nothing\n",
+                i + 1,
+                expected
+            )),
+        }
+    }
+    for (i, line) in output.iter().enumerate().skip(expectations.output.len()) {
+        message.push_str(&format!("line {}: unexpected `{}`\n", i + 1, line));
+    }
+    Err(message)
+}
@@ -1,10 +1,15 @@
-use crate::common::{CompileError, Keyword, Position, Type};
+use crate::common::{CompileError, Keyword, Span, Type};
+use crate::diagnostics::ErrorCode;
 
-pub fn keyword_to_type(kw: &Keyword, pos: &Position) -> Result<Type, CompileError> {
+pub fn keyword_to_type(kw: &Keyword, span: &Span) -> Result<Type, CompileError> {
     match kw {
         Keyword::Int => Ok(Type::Int),
         Keyword::Bool => Ok(Type::Bool),
+        Keyword::Str => Ok(Type::Str),
 
-        _ => return Err(CompileError::new("Unknown type found", *pos)),
+        _ => Err(
+            CompileError::new(format!("expected a type, found {}", kw), *span)
+                .with_code(ErrorCode::UnexpectedToken),
+        ),
     }
 }
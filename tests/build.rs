@@ -0,0 +1,64 @@
+mod common;
+
+use std::process::Command;
+
+use common::{ratio, stderr, stdout, test_dir, write};
+
+const MAIN: &str = "fn main() {\n    print(\"built\");\n    return 0;\n}\n";
+
+#[test]
+fn build_compiles_the_project_in_the_current_directory() {
+    let dir = test_dir("build_compiles_the_project_in_the_current_directory");
+    std::fs::create_dir(dir.join("src")).unwrap();
+    write(&dir, "src/main.ratio", MAIN);
+    write(&dir, "ratio.toml", "name = \"hello\"\n");
+    let output = ratio(&dir).arg("build").output().unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let output = Command::new(dir.join("hello")).output().unwrap();
+    assert_eq!(stdout(&output), "built\n");
+}
+
+#[test]
+fn the_manifest_names_the_source_and_output() {
+    let dir = test_dir("the_manifest_names_the_source_and_output");
+    write(&dir, "app.ratio", MAIN);
+    let manifest = "name = \"hello\"\nsource = \"app.ratio\"\noutput = \"bin/app\"\n";
+    write(&dir, "ratio.toml", manifest);
+    std::fs::create_dir(dir.join("bin")).unwrap();
+    let output = ratio(&dir).args(["build", "--release"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let output = Command::new(dir.join("bin/app")).output().unwrap();
+    assert_eq!(stdout(&output), "built\n");
+}
+
+#[test]
+fn manifest_errors_point_into_the_manifest() {
+    let dir = test_dir("manifest_errors_point_into_the_manifest");
+    write(&dir, "ratio.toml", "name = \"hello\"\nopt-level = 7\n");
+    let output = ratio(&dir)
+        .args(["build", "--color", "never"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = stderr(&output);
+    assert!(
+        stderr.starts_with("error: there is no optimization level 7\n --> ratio.toml:2:13\n"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn build_needs_a_manifest() {
+    let dir = test_dir("build_needs_a_manifest");
+    let output = ratio(&dir)
+        .args(["build", "--color", "never"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).starts_with("error: no `ratio.toml` in the current directory\n"),
+        "{}",
+        stderr(&output)
+    );
+}
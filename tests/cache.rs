@@ -0,0 +1,87 @@
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use common::{ratio, stderr, stdout, test_dir, write};
+
+const MAIN: &str = "fn main() {\n    print(\"cached\");\n    return 0;\n}\n";
+
+/// Compiles `main.ratio` in `dir` with a cache of its own, so that no other test's objects are
+/// found in it, and returns what -v printed.
+fn build(dir: &Path, options: &[&str]) -> String {
+    let output = ratio(dir)
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .args(["-v", "-o", "main"])
+        .args(options)
+        .arg("main.ratio")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let run = Command::new(dir.join("main")).output().unwrap();
+    assert_eq!(stdout(&run), "cached\n");
+    stderr(&output)
+}
+
+fn reused(log: &str) -> bool {
+    log.contains("cache: reusing")
+}
+
+fn stored(log: &str) -> bool {
+    log.contains("cache: stored")
+}
+
+#[test]
+fn an_unchanged_file_reuses_its_object() {
+    let dir = test_dir("an_unchanged_file_reuses_its_object");
+    write(&dir, "main.ratio", MAIN);
+    let log = build(&dir, &[]);
+    assert!(stored(&log) && !reused(&log), "{}", log);
+    // Writing the same text again changes the file's modification time, but not its object.
+    write(&dir, "main.ratio", MAIN);
+    let log = build(&dir, &[]);
+    assert!(reused(&log) && !stored(&log), "{}", log);
+}
+
+#[test]
+fn changing_the_source_or_options_generates_a_new_object() {
+    let dir = test_dir("changing_the_source_or_options_generates_a_new_object");
+    write(&dir, "main.ratio", MAIN);
+    build(&dir, &[]);
+    write(
+        &dir,
+        "main.ratio",
+        &MAIN.replace("return 0;", "return 1 - 1;"),
+    );
+    let log = build(&dir, &[]);
+    assert!(stored(&log) && !reused(&log), "{}", log);
+    let log = build(&dir, &["-O0"]);
+    assert!(stored(&log) && !reused(&log), "{}", log);
+    let log = build(&dir, &["-O0"]);
+    assert!(reused(&log), "{}", log);
+}
+
+#[test]
+fn no_cache_neither_reads_nor_writes_the_cache() {
+    let dir = test_dir("no_cache_neither_reads_nor_writes_the_cache");
+    write(&dir, "main.ratio", MAIN);
+    build(&dir, &[]);
+    let log = build(&dir, &["--no-cache"]);
+    assert!(!reused(&log) && !stored(&log), "{}", log);
+}
+
+#[test]
+fn clean_empties_the_cache() {
+    let dir = test_dir("clean_empties_the_cache");
+    write(&dir, "main.ratio", MAIN);
+    build(&dir, &[]);
+    let output = ratio(&dir)
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .arg("clean")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(!dir.join("cache/ratio").exists());
+    let log = build(&dir, &[]);
+    assert!(stored(&log) && !reused(&log), "{}", log);
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use common::{ratio, stderr, stdout, test_dir, write};
+
+#[test]
+fn check_exits_with_0_for_a_correct_program() {
+    let dir = test_dir("check_exits_with_0_for_a_correct_program");
+    write(&dir, "ok.ratio", "fn main() {\n    return 0;\n}\n");
+    let output = ratio(&dir).args(["check", "ok.ratio"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn check_reports_every_error_and_exits_with_1() {
+    let dir = test_dir("check_reports_every_error_and_exits_with_1");
+    write(
+        &dir,
+        "bad.ratio",
+        "fn main() {\n    print(x);\n    print(y);\n    return 0;\n}\n",
+    );
+    let output = ratio(&dir)
+        .args(["--color", "never", "check", "bad.ratio"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("undeclared variable `x`"), "{}", stderr);
+    assert!(stderr.contains("undeclared variable `y`"), "{}", stderr);
+}
+
+#[test]
+fn check_neither_links_nor_writes_an_executable() {
+    let dir = test_dir("check_neither_links_nor_writes_an_executable");
+    write(&dir, "ok.ratio", "fn main() {\n    return 0;\n}\n");
+    let output = ratio(&dir)
+        .args(["check", "ok.ratio"])
+        .env("RATIO_CC", "/nonexistent/cc")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let entries: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(entries.len(), 1);
+}
+
+#[test]
+fn global_options_can_come_before_check() {
+    let dir = test_dir("global_options_can_come_before_check");
+    write(&dir, "ok.ratio", "fn main() {\n    return 0;\n}\n");
+    for options in [&["-v"][..], &["-O", "0"], &["--color", "never"]] {
+        let output = ratio(&dir)
+            .args(options)
+            .args(["check", "ok.ratio"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    }
+}
+
+#[test]
+fn optimization_level_can_come_before_run() {
+    let dir = test_dir("optimization_level_can_come_before_run");
+    write(&dir, "five.ratio", "fn main() {\n    return 2 + 3;\n}\n");
+    for options in [&["-O", "0"][..], &["-O3"], &["--release"]] {
+        let output = ratio(&dir)
+            .args(options)
+            .args(["run", "five.ratio"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    }
+}
+
+/// Checks a `main` that prints `expr`, expecting it to fail, and returns what was reported.
+fn check_failing_print(name: &str, expr: &str) -> String {
+    let dir = test_dir(name);
+    let source = format!("fn main() {{\n    print({});\n    return 0;\n}}\n", expr);
+    write(&dir, "bad.ratio", &source);
+    let output = ratio(&dir)
+        .args(["--color", "never", "check", "bad.ratio"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    stderr(&output)
+}
+
+#[test]
+fn comparing_mismatched_types_points_at_the_operator() {
+    let cases = [
+        ("1 == \"a\"", "cannot compare int with str", 13),
+        ("true < 2", "cannot compare bool with int", 16),
+    ];
+    for (i, (expr, message, column)) in cases.into_iter().enumerate() {
+        let stderr = check_failing_print(&format!("comparing_mismatched_types_{}", i), expr);
+        assert!(stderr.contains(message), "{}", stderr);
+        let location = format!("bad.ratio:2:{}\n", column);
+        assert!(stderr.contains(&location), "{}", stderr);
+    }
+}
+
+#[test]
+fn arithmetic_on_bools_and_strs_is_rejected() {
+    let cases = [
+        ("true + 1", "operator `+` cannot be applied to bool and int"),
+        ("\"a\" * 3", "operator `*` cannot be applied to str and int"),
+    ];
+    for (i, (expr, message)) in cases.into_iter().enumerate() {
+        let stderr = check_failing_print(&format!("arithmetic_on_bools_and_strs_{}", i), expr);
+        assert!(stderr.contains(message), "{}", stderr);
+    }
+}
+
+#[test]
+fn a_file_without_functions_is_missing_main() {
+    let sources = [
+        ("empty", ""),
+        ("whitespace", "   \n\n\t\n"),
+        ("extern_only", "extern fn abs(x: int) int;\n"),
+    ];
+    for (name, source) in sources {
+        let dir = test_dir(&format!("a_file_without_functions_{}", name));
+        write(&dir, "main.ratio", source);
+        let output = ratio(&dir)
+            .args(["--color", "never", "check", "main.ratio"])
+            .output()
+            .unwrap();
+        let stderr = stderr(&output);
+        assert_eq!(output.status.code(), Some(1), "{}", stderr);
+        assert!(
+            stderr.starts_with("error[R0010]: no `main` function found in main.ratio\n"),
+            "{}",
+            stderr
+        );
+        assert!(
+            stderr.contains("= note: the source file contains no functions"),
+            "{}",
+            stderr
+        );
+        assert!(!stderr.contains("warning"), "{}", stderr);
+    }
+}
+
+#[test]
+fn calls_with_the_wrong_number_of_arguments_are_reported() {
+    let dir = test_dir("calls_with_the_wrong_number_of_arguments_are_reported");
+    let source = "extern fn abs(x: int) int;\n\
+                  \n\
+                  fn add(a: int, b: int) {\n    return a + b;\n}\n\
+                  \n\
+                  fn main() {\n    print(add(1));\n    print(abs(1, 2));\n    print();\n    return 0;\n}\n";
+    write(&dir, "arity.ratio", source);
+    let output = ratio(&dir)
+        .args(["--color", "never", "check", "arity.ratio"])
+        .output()
+        .unwrap();
+    let stderr = stderr(&output);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    for (message, location) in [
+        (
+            "`add` expects 2 arguments, but 1 was provided",
+            "arity.ratio:8:11",
+        ),
+        (
+            "`abs` expects 1 argument, but 2 were provided",
+            "arity.ratio:9:11",
+        ),
+        (
+            "`print` expects at least 1 argument, but 0 were provided",
+            "arity.ratio:10:5",
+        ),
+    ] {
+        assert!(stderr.contains(message), "{}", stderr);
+        assert!(
+            stderr.contains(&format!("--> {}\n", location)),
+            "{}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn only_the_first_20_errors_are_shown() {
+    let dir = test_dir("only_the_first_20_errors_are_shown");
+    let prints: String = (0..25).map(|i| format!("    print(x{});\n", i)).collect();
+    write(
+        &dir,
+        "many.ratio",
+        &format!("fn main() {{\n{}    return 0;\n}}\n", prints),
+    );
+    let output = ratio(&dir)
+        .args(["--color", "never", "check", "many.ratio"])
+        .output()
+        .unwrap();
+    let stderr = stderr(&output);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert_eq!(stderr.matches("error[").count(), 20, "{}", stderr);
+    assert!(stderr.contains("undeclared variable `x19`"), "{}", stderr);
+    assert!(!stderr.contains("undeclared variable `x20`"), "{}", stderr);
+    assert!(
+        stderr.ends_with("error: 5 additional errors suppressed\n"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,247 @@
+mod common;
+
+use object::{Object, ObjectSymbol};
+
+use common::{ratio, stderr, stdout, test_dir, write};
+
+/// Runs `source` as a program of its own, returning its exit status and what it printed.
+fn run(test: &str, source: &str) -> (Option<i32>, String) {
+    let dir = test_dir(test);
+    write(&dir, "main.ratio", source);
+    let output = ratio(&dir).args(["run", "main.ratio"]).output().unwrap();
+    assert!(!stderr(&output).contains("error"), "{}", stderr(&output));
+    (output.status.code(), stdout(&output))
+}
+
+/// Compiles `source` to an object file, returning its bytes.
+fn object(test: &str, source: &str) -> Vec<u8> {
+    emit(test, source, "obj", &[])
+}
+
+/// Compiles `source` with `options` to the output `--emit` names, returning its bytes.
+fn emit(test: &str, source: &str, kind: &str, options: &[&str]) -> Vec<u8> {
+    let dir = test_dir(test);
+    write(&dir, "main.ratio", source);
+    let output = ratio(&dir)
+        .args(options)
+        .args(["--emit", kind, "main.ratio", "-o", "main.out"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    std::fs::read(dir.join("main.out")).unwrap()
+}
+
+/// The functions an object defines, split into its global and local symbols. Mach-O's leading
+/// underscore is dropped.
+fn defined_functions(object: &[u8]) -> (Vec<String>, Vec<String>) {
+    let file = object::File::parse(object).expect("the output is an object file");
+    let mut global = Vec::new();
+    let mut local = Vec::new();
+    for symbol in file.symbols() {
+        if symbol.is_undefined() || symbol.kind() != object::SymbolKind::Text {
+            continue;
+        }
+        let name = symbol.name().unwrap().trim_start_matches('_').to_string();
+        if symbol.is_global() {
+            global.push(name);
+        } else {
+            local.push(name);
+        }
+    }
+    global.sort();
+    local.sort();
+    (global, local)
+}
+
+/// How many times `needle` appears in `haystack`.
+fn occurrences(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .filter(|window| *window == needle)
+        .count()
+}
+
+/// A program of `count` functions, each made of `body`, all called from `main`.
+fn functions(count: usize, body: &str) -> String {
+    let mut source = String::new();
+    for i in 0..count {
+        source.push_str(&format!("fn f{}() {{\n{}    return 0;\n}}\n\n", i, body));
+    }
+    source.push_str("fn main() {\n");
+    for i in 0..count {
+        source.push_str(&format!("    f{}();\n", i));
+    }
+    source.push_str("    return 0;\n}\n");
+    source
+}
+
+#[test]
+fn return_in_the_then_branch() {
+    let source = "fn f(x: int) {\n    if x > 0 {\n        return 1;\n    }\n    return 2;\n}\n\nfn main() {\n    return f(1) * 10 + f(0);\n}\n";
+    assert_eq!(run("return_in_the_then_branch", source).0, Some(12));
+}
+
+#[test]
+fn return_in_the_else_branch() {
+    let source = "fn f(x: int) {\n    if x > 0 {\n        print(\"then\");\n    } else {\n        return 3;\n    }\n    return 4;\n}\n\nfn main() {\n    return f(1) * 10 + f(0);\n}\n";
+    assert_eq!(
+        run("return_in_the_else_branch", source),
+        (Some(43), "then\n".to_string())
+    );
+}
+
+#[test]
+fn return_in_both_branches() {
+    let source = "fn f(x: int) {\n    if x > 0 {\n        return 5;\n    } else {\n        return 6;\n    }\n}\n\nfn main() {\n    return f(1) * 10 + f(0);\n}\n";
+    assert_eq!(run("return_in_both_branches", source).0, Some(56));
+}
+
+#[test]
+fn return_in_nested_ifs() {
+    let source = "fn f(x: int) {\n    if x > 0 {\n        if x > 1 {\n            return 7;\n        }\n        print(\"one\");\n    } else {\n        if x < 0 {\n            return 8;\n        } else {\n            return 9;\n        }\n    }\n    return 1;\n}\n\nfn main() {\n    return f(2) + f(1) * 10 + f(0) - f(0 - 1);\n}\n";
+    assert_eq!(
+        run("return_in_nested_ifs", source),
+        (Some(7 + 10 + 9 - 8), "one\n".to_string())
+    );
+}
+
+#[test]
+fn return_in_a_loop() {
+    let source = "fn f() {\n    var i: int = 0;\n    while i < 10 {\n        if i == 4 {\n            return i;\n        }\n        i = i + 1;\n    }\n    return 0;\n}\n\nfn main() {\n    return f();\n}\n";
+    assert_eq!(run("return_in_a_loop", source).0, Some(4));
+}
+
+#[test]
+fn code_after_a_void_extern_call_runs() {
+    let source = "extern fn srand(seed: int);\n\nfn main() {\n    srand(1);\n    print(\"after\");\n    return 2;\n}\n";
+    assert_eq!(
+        run("code_after_a_void_extern_call_runs", source),
+        (Some(2), "after\n".to_string())
+    );
+}
+
+#[test]
+fn exit_ends_the_program() {
+    let source = "extern fn exit(code: int);\n\nfn main() {\n    print(\"before\");\n    exit(6);\n    return 0;\n}\n";
+    assert_eq!(
+        run("exit_ends_the_program", source),
+        (Some(6), "before\n".to_string())
+    );
+}
+
+#[test]
+fn exit_in_a_guard_clause() {
+    let source = "extern fn exit(code: int);\n\nfn check(x: int) {\n    if x > 5 {\n        print(\"bad\");\n        exit(1);\n    }\n    print(\"ok\");\n    return x;\n}\n\nfn main() {\n    print(check(3));\n    return check(9);\n}\n";
+    assert_eq!(
+        run("exit_in_a_guard_clause", source),
+        (Some(1), "ok\n3\nbad\n".to_string())
+    );
+}
+
+#[test]
+fn print_formats_are_emitted_once() {
+    let source = functions(20, "    print(1);\n    print(\"text\");\n");
+    let object = object("print_formats_are_emitted_once", &source);
+    assert_eq!(occurrences(&object, b"%lld\n\0"), 1);
+    assert_eq!(occurrences(&object, b"%s\n\0"), 1);
+}
+
+#[test]
+fn identical_string_literals_are_shared() {
+    let source = functions(3, "    print(\"hello\");\n    print(\"hello\");\n");
+    let object = object("identical_string_literals_are_shared", &source);
+    assert_eq!(occurrences(&object, b"hello\0"), 1);
+}
+
+#[test]
+fn compiling_twice_gives_identical_objects() {
+    let source = functions(5, "    print(\"a\");\n    print(\"%d items\", 2);\n");
+    let first = object("compiling_twice_gives_identical_objects", &source);
+    let second = object("compiling_twice_gives_identical_objects", &source);
+    assert!(first == second, "the two objects differ");
+}
+
+#[test]
+fn only_main_is_exported() {
+    let mut source = String::new();
+    for i in 0..10 {
+        source.push_str(&format!("fn helper{}() {{\n    return {};\n}}\n\n", i, i));
+    }
+    source.push_str("fn main() {\n    return helper3();\n}\n");
+    let object = object("only_main_is_exported", &source);
+    let (global, _) = defined_functions(&object);
+    assert_eq!(global, ["main"]);
+}
+
+#[test]
+fn pub_functions_are_exported_and_others_are_not() {
+    let source = "pub fn api_entry(x: int) {\n    return helper(x) + 1;\n}\n\nfn helper(x: int) {\n    return x * 2;\n}\n\npub fn main() {\n    return api_entry(1);\n}\n";
+    let object = object("pub_functions_are_exported_and_others_are_not", source);
+    let (global, local) = defined_functions(&object);
+    assert_eq!(global, ["api_entry", "main"]);
+    assert!(local.contains(&"helper".to_string()), "{:?}", local);
+}
+
+#[test]
+fn optimization_changes_the_code_and_o2_is_the_default() {
+    let source = "fn sum() {\n    var total: int = 0;\n    var i: int = 0;\n    while i < 10 {\n        total = total + i * 3;\n        i = i + 1;\n    }\n    return total;\n}\n\nfn main() {\n    return sum();\n}\n";
+    let test = "optimization_changes_the_code_and_o2_is_the_default";
+    let o0 = emit(test, source, "obj", &["-O0"]);
+    let o2 = emit(test, source, "obj", &["-O2"]);
+    let default = emit(test, source, "obj", &[]);
+    assert!(o0 != o2, "-O0 and -O2 give the same object");
+    assert!(default == o2, "the default is not -O2");
+}
+
+/// LLVM's IR shows whether locals were promoted to registers; Cranelift has no IR output.
+#[cfg(feature = "llvm")]
+#[test]
+fn locals_are_promoted_out_of_stack_slots() {
+    let source = "fn main() {\n    var sum: int = 0;\n    var i: int = 0;\n    while i < 1000000 {\n        sum = sum + i;\n        i = i + 1;\n    }\n    print(sum);\n    return 0;\n}\n";
+    let ir = emit(
+        "locals_are_promoted_out_of_stack_slots",
+        source,
+        "llvm-ir",
+        &[],
+    );
+    let ir = String::from_utf8(ir).unwrap();
+    assert!(!ir.contains("alloca"), "{}", ir);
+}
+
+/// Only LLVM can generate code for a target other than the host.
+#[cfg(feature = "llvm")]
+#[test]
+fn cross_compiling_gives_an_object_for_the_target() {
+    let object = emit(
+        "cross_compiling_gives_an_object_for_the_target",
+        "fn main() {\n    print(\"hi\");\n    return 0;\n}\n",
+        "obj",
+        &["--target", "aarch64-unknown-linux-gnu"],
+    );
+    let file = object::File::parse(&*object).expect("the output is an object file");
+    assert_eq!(file.format(), object::BinaryFormat::Elf);
+    assert_eq!(file.architecture(), object::Architecture::Aarch64);
+    assert!(file.is_little_endian());
+}
+
+/// Windows' C library takes `long` as 32 bits, so an int is printed with `%lld` there too.
+#[cfg(feature = "llvm")]
+#[test]
+fn windows_objects_are_coff_and_print_with_lld() {
+    let source = "fn main() {\n    print(1);\n    return 0;\n}\n";
+    let options = ["--target", "x86_64-pc-windows-gnu"];
+    let object = emit(
+        "windows_objects_are_coff_and_print_with_lld",
+        source,
+        "obj",
+        &options,
+    );
+    let file = object::File::parse(&*object).expect("the output is an object file");
+    assert_eq!(file.format(), object::BinaryFormat::Coff);
+    assert_eq!(file.architecture(), object::Architecture::X86_64);
+    assert_eq!(occurrences(&object, b"%lld\n\0"), 1);
+    let ir = emit("windows_ir_prints_with_lld", source, "llvm-ir", &options);
+    let ir = String::from_utf8(ir).unwrap();
+    assert!(ir.contains("c\"%lld\\0A\\00\""), "{}", ir);
+    assert!(!ir.contains("%ld"), "{}", ir);
+}
@@ -0,0 +1,41 @@
+//! Helpers shared by the tests that run the `ratio` executable.
+
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A command running the compiler built for these tests, in `dir`. Its cache is kept with the
+/// other test files rather than in the user's.
+pub fn ratio(dir: &Path) -> Command {
+    let cache = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cache");
+    let mut command = Command::new(env!("CARGO_BIN_EXE_ratio"));
+    command
+        .current_dir(dir)
+        .env_remove("RATIO_LOG")
+        .env("XDG_CACHE_HOME", cache);
+    command
+}
+
+/// An empty directory for the test `name`, removing what an earlier run left in it.
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("creates the test directory");
+    dir
+}
+
+/// Writes `text` to the file `name` in `dir`, returning its path.
+pub fn write(dir: &Path, name: &str, text: &str) -> PathBuf {
+    let path = dir.join(name);
+    std::fs::write(&path, text).expect("writes the test file");
+    path
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
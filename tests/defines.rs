@@ -0,0 +1,77 @@
+mod common;
+
+use common::{ratio, stderr, stdout, test_dir, write};
+
+const SOURCE: &str = "fn main() {\n    if VERBOSE {\n        print(\"limit is %d\", LIMIT);\n    }\n    return LIMIT;\n}\n";
+
+#[test]
+fn defines_change_what_the_program_does() {
+    let dir = test_dir("defines_change_what_the_program_does");
+    write(&dir, "defines.ratio", SOURCE);
+    let verbose = ratio(&dir)
+        .args(["--define", "VERBOSE=true", "--define", "LIMIT=100"])
+        .args(["run", "defines.ratio"])
+        .output()
+        .unwrap();
+    assert_eq!(verbose.status.code(), Some(100), "{}", stderr(&verbose));
+    assert_eq!(stdout(&verbose), "limit is 100\n");
+    let quiet = ratio(&dir)
+        .args(["--define", "VERBOSE=false", "--define", "LIMIT=7"])
+        .args(["run", "defines.ratio"])
+        .output()
+        .unwrap();
+    assert_eq!(quiet.status.code(), Some(7), "{}", stderr(&quiet));
+    assert_eq!(stdout(&quiet), "");
+}
+
+#[test]
+fn a_define_must_be_a_name_and_a_value() {
+    let dir = test_dir("a_define_must_be_a_name_and_a_value");
+    write(&dir, "defines.ratio", SOURCE);
+    for (define, problem) in [
+        ("LIMIT", "there is no `=` between the name and the value"),
+        (
+            "VERBOSE=yes",
+            "the value must be an integer, `true` or `false`",
+        ),
+    ] {
+        let output = ratio(&dir)
+            .args(["check", "--color", "never", "--define", define])
+            .arg("defines.ratio")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2));
+        let expected = format!("error: invalid `--define {}`: {}\n", define, problem);
+        assert!(
+            stderr(&output).starts_with(&expected),
+            "{}",
+            stderr(&output)
+        );
+    }
+}
+
+#[test]
+fn a_define_conflicts_with_a_constant_of_the_same_name() {
+    let dir = test_dir("a_define_conflicts_with_a_constant_of_the_same_name");
+    let source = "const LIMIT: int = 3;\n\nfn main() {\n    return LIMIT;\n}\n";
+    write(&dir, "limit.ratio", source);
+    let output = ratio(&dir)
+        .args([
+            "check",
+            "--color",
+            "never",
+            "--define",
+            "LIMIT=5",
+            "limit.ratio",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(
+        stderr.starts_with("error[R0013]: constant `LIMIT` is defined more than once\n"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("<command line>:1:1"), "{}", stderr);
+}
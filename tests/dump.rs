@@ -0,0 +1,60 @@
+mod common;
+
+use std::process::Stdio;
+
+use common::{ratio, stderr, stdout, test_dir, write};
+
+/// A program whose dumps are far larger than a pipe's buffer.
+fn long_program() -> String {
+    let mut text = String::from("fn main() {\n");
+    for i in 0..5000 {
+        text.push_str(&format!("    var x{}: int = 1 + 2 * 3;\n", i));
+    }
+    text.push_str("}\n");
+    text
+}
+
+#[test]
+fn dumps_stop_quietly_when_the_reader_goes_away() {
+    let dir = test_dir("dumps_stop_quietly_when_the_reader_goes_away");
+    write(&dir, "long.ratio", &long_program());
+    for option in ["--dump-tokens", "--dump-ast", "--dump-ast=json"] {
+        let mut child = ratio(&dir)
+            .args([option, "long.ratio"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // Closing the reading end before anything is read, as `head` does once it has enough.
+        drop(child.stdout.take());
+        let output = child.wait_with_output().unwrap();
+        assert_eq!(
+            output.status.code(),
+            Some(0),
+            "{}: {}",
+            option,
+            stderr(&output)
+        );
+        assert_eq!(stderr(&output), "", "{}", option);
+    }
+}
+
+/// The JSON dump is read by other tools, so any change to its schema must show up here. If a
+/// change is intended, regenerate `tests/snapshots/ast.json` from `ast.ratio`.
+#[test]
+fn the_json_dump_matches_its_snapshot() {
+    let dir = test_dir("the_json_dump_matches_its_snapshot");
+    let snapshots = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let output = ratio(&dir)
+        .arg("--dump-ast=json")
+        .arg(snapshots.join("ast.ratio"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let expected = std::fs::read_to_string(snapshots.join("ast.json")).unwrap();
+    assert!(
+        stdout(&output) == expected,
+        "the JSON dump no longer matches tests/snapshots/ast.json:\n{}",
+        stdout(&output)
+    );
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{ratio, stderr, stdout, test_dir, write};
+
+const HELLO: &str = "fn main() {\n    print(\"hello\");\n    return 0;\n}\n";
+
+#[test]
+fn a_syntax_error_exits_with_1() {
+    let dir = test_dir("a_syntax_error_exits_with_1");
+    write(&dir, "broken.ratio", "fn main() {\n    return 0\n}\n");
+    let output = ratio(&dir).arg("broken.ratio").output().unwrap();
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("missing ';'"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn an_unknown_character_exits_with_1_instead_of_panicking() {
+    let dir = test_dir("an_unknown_character_exits_with_1_instead_of_panicking");
+    write(&dir, "stray.ratio", "fn main() {\n    return 0 @;\n}\n");
+    let output = ratio(&dir).arg("stray.ratio").output().unwrap();
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("unknown character '@'"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn a_missing_source_file_exits_with_2() {
+    let dir = test_dir("a_missing_source_file_exits_with_2");
+    let output = ratio(&dir).arg("missing.ratio").output().unwrap();
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert!(
+        stderr(&output).starts_with("error: cannot read `missing.ratio`"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn a_missing_linker_exits_with_3() {
+    let dir = test_dir("a_missing_linker_exits_with_3");
+    write(&dir, "hello.ratio", HELLO);
+    let output = ratio(&dir)
+        .args(["--no-cache", "hello.ratio"])
+        .env("RATIO_CC", "/nonexistent/cc")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("panicked"), "{}", stderr(&output));
+    assert!(!dir.join("hello").exists());
+}
+
+#[test]
+fn help_lists_the_exit_statuses() {
+    let dir = test_dir("help_lists_the_exit_statuses");
+    let output = ratio(&dir).arg("--help").output().unwrap();
+    let help = stdout(&output);
+    assert!(help.contains("Exit status:\n  0   success\n"), "{}", help);
+    assert!(help.contains("  70  internal compiler error\n"), "{}", help);
+}
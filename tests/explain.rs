@@ -0,0 +1,43 @@
+mod common;
+
+use common::{ratio, stderr, stdout, test_dir};
+
+#[test]
+fn explain_prints_the_explanation() {
+    let dir = test_dir("explain_prints_the_explanation");
+    let output = ratio(&dir).args(["--explain", "R0003"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("never declared"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn explain_lists_the_known_codes_for_an_unknown_one() {
+    let dir = test_dir("explain_lists_the_known_codes_for_an_unknown_one");
+    let output = ratio(&dir)
+        .args(["--color", "never", "--explain", "R9999"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stdout(&output), "");
+    let stderr = stderr(&output);
+    assert!(
+        stderr.starts_with("error: unknown error code `R9999`\n"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("R0001, R0002, R0003"), "{}", stderr);
+}
+
+#[test]
+fn explain_colors_its_error_like_the_others() {
+    let dir = test_dir("explain_colors_its_error_like_the_others");
+    let output = ratio(&dir)
+        .args(["--color", "always", "--explain", "R9999"])
+        .output()
+        .unwrap();
+    assert!(stderr(&output).starts_with('\u{1b}'), "{}", stderr(&output));
+}
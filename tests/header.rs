@@ -0,0 +1,61 @@
+mod common;
+
+use std::process::Command;
+
+use common::{ratio, stderr, stdout, test_dir, write};
+
+const LIBRARY: &str = "extern fn abs(x: int) int;\n\npub fn twice(x: int) {\n    return x * 2;\n}\n\npub fn greet(name: str) {\n    print(\"hello %s\", name);\n    return 0;\n}\n\nfn helper() {\n    return abs(0 - 21);\n}\n\npub fn answer() {\n    return twice(helper());\n}\n";
+
+const C_MAIN: &str = "#include <stdio.h>\n#include \"library.h\"\n\nint main(void) {\n    greet(\"c\");\n    printf(\"%lld %lld\\n\", (long long)twice(4), (long long)answer());\n    return 0;\n}\n";
+
+/// Compiles the library to an object, without a C main, along with its header.
+fn compile_library(test: &str) -> std::path::PathBuf {
+    let dir = test_dir(test);
+    write(&dir, "library.ratio", LIBRARY);
+    let output = ratio(&dir)
+        .args(["--emit", "obj", "--entry", "answer", "--reloc", "pic"])
+        .args([
+            "--emit-header",
+            "library.h",
+            "library.ratio",
+            "-o",
+            "library.o",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    dir
+}
+
+#[test]
+fn the_header_declares_only_pub_functions() {
+    let dir = compile_library("the_header_declares_only_pub_functions");
+    let header = std::fs::read_to_string(dir.join("library.h")).unwrap();
+    assert!(header.contains("#include <stdint.h>\n"), "{}", header);
+    assert!(header.contains("#ifndef RATIO_LIBRARY_H\n"), "{}", header);
+    assert!(header.contains("extern \"C\" {\n"), "{}", header);
+    let prototypes: Vec<&str> = header.lines().filter(|line| line.ends_with(");")).collect();
+    assert_eq!(
+        prototypes,
+        [
+            "int64_t twice(int64_t x);",
+            "int64_t greet(const char *name);",
+            "int64_t answer(void);",
+        ]
+    );
+}
+
+#[test]
+fn c_can_call_ratio_through_the_header() {
+    let dir = compile_library("c_can_call_ratio_through_the_header");
+    write(&dir, "main.c", C_MAIN);
+    let output = Command::new("cc")
+        .current_dir(&dir)
+        .args(["main.c", "library.o", "-o", "program"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = Command::new(dir.join("program")).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "hello c\n8 42\n");
+}
@@ -0,0 +1,254 @@
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use common::{ratio, stderr, stdout, test_dir, write};
+
+/// A C function for Ratio programs to link with.
+const HELPER_C: &str = "long triple(long x) {\n    return 3 * x;\n}\n";
+
+/// A program calling `triple` from `HELPER_C`.
+const CALLS_TRIPLE: &str =
+    "extern fn triple(x: int) int;\n\nfn main() {\n    return triple(4);\n}\n";
+
+/// Runs the system's C compiler in `dir`, which the compiler links with as well.
+fn cc(dir: &Path, args: &[&str]) {
+    let output = Command::new("cc")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+/// Compiles `HELPER_C` into the static library `lib/libhelpers.a` in `dir`.
+fn helper_library(dir: &Path) {
+    write(dir, "helper.c", HELPER_C);
+    cc(dir, &["-c", "helper.c", "-o", "helper.o"]);
+    std::fs::create_dir(dir.join("lib")).unwrap();
+    let output = Command::new("ar")
+        .current_dir(dir)
+        .args(["rcs", "lib/libhelpers.a", "helper.o"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn libraries_named_with_l_are_linked() {
+    let dir = test_dir("libraries_named_with_l_are_linked");
+    helper_library(&dir);
+    write(&dir, "main.ratio", CALLS_TRIPLE);
+    let output = ratio(&dir)
+        .args(["-L", "lib", "-l", "helpers", "main.ratio"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let output = Command::new(dir.join("main")).output().unwrap();
+    assert_eq!(output.status.code(), Some(12));
+}
+
+#[test]
+fn a_missing_library_points_at_the_extern() {
+    let dir = test_dir("a_missing_library_points_at_the_extern");
+    write(&dir, "main.ratio", CALLS_TRIPLE);
+    let output = ratio(&dir)
+        .args(["--color", "never", "main.ratio"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = stderr(&output);
+    assert!(stderr.starts_with("error: linking failed\n"), "{}", stderr);
+    assert!(
+        stderr.contains(
+            "error: no library defines the extern function `triple`\n --> main.ratio:1:1\n"
+        ),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("help: check the name, or link the library that defines it with `-l`"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn object_files_and_archives_are_linked_as_they_are() {
+    let dir = test_dir("object_files_and_archives_are_linked_as_they_are");
+    helper_library(&dir);
+    write(&dir, "main.ratio", CALLS_TRIPLE);
+    for input in ["helper.o", "lib/libhelpers.a"] {
+        let output = ratio(&dir)
+            .args(["main.ratio", input, "-o", "prog"])
+            .output()
+            .unwrap();
+        assert_eq!(
+            output.status.code(),
+            Some(0),
+            "{}: {}",
+            input,
+            stderr(&output)
+        );
+        let output = Command::new(dir.join("prog")).output().unwrap();
+        assert_eq!(output.status.code(), Some(12), "{}", input);
+    }
+}
+
+#[test]
+fn a_missing_input_to_link_is_reported_before_compiling() {
+    let dir = test_dir("a_missing_input_to_link_is_reported_before_compiling");
+    write(
+        &dir,
+        "main.ratio",
+        "fn main() {\n    return undefined;\n}\n",
+    );
+    let output = ratio(&dir)
+        .args(["--color", "never", "main.ratio", "extra.o"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        stderr(&output),
+        "error: cannot find `extra.o` to link\nhelp: inputs with an extension other than `.ratio` are passed on to the linker\n"
+    );
+}
+
+#[test]
+fn a_ratio_source_is_still_needed() {
+    let dir = test_dir("a_ratio_source_is_still_needed");
+    helper_library(&dir);
+    let output = ratio(&dir)
+        .args(["--color", "never", "helper.o"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).starts_with("error: no Ratio source file to compile\n"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+/// The dynamic loader an ELF executable names, which a statically linked one has none of.
+#[cfg(target_os = "linux")]
+fn interpreter(executable: &Path) -> Option<String> {
+    use object::{Object, ObjectSection};
+
+    let bytes = std::fs::read(executable).unwrap();
+    let file = object::File::parse(&*bytes).expect("the output is an executable");
+    let section = file.section_by_name(".interp")?;
+    let path = section.data().unwrap().strip_suffix(b"\0").unwrap();
+    Some(String::from_utf8(path.to_vec()).unwrap())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn only_dynamic_executables_have_an_interpreter() {
+    let dir = test_dir("only_dynamic_executables_have_an_interpreter");
+    write(
+        &dir,
+        "main.ratio",
+        "fn main() {\n    print(\"linked\");\n    return 0;\n}\n",
+    );
+    for (options, dynamic) in [
+        (&[][..], false),
+        (&["--link", "static"][..], false),
+        (&["--link", "dynamic"][..], true),
+        (&["--reloc", "pic"][..], true),
+        (&["--reloc", "pic", "--link", "static"][..], false),
+    ] {
+        let output = ratio(&dir)
+            .args(options)
+            .args(["main.ratio", "-o", "main"])
+            .output()
+            .unwrap();
+        assert_eq!(
+            output.status.code(),
+            Some(0),
+            "{:?}: {}",
+            options,
+            stderr(&output)
+        );
+        let interpreter = interpreter(&dir.join("main"));
+        assert_eq!(
+            interpreter.is_some(),
+            dynamic,
+            "{:?}: {:?}",
+            options,
+            interpreter
+        );
+        if let Some(path) = interpreter {
+            assert!(Path::new(&path).exists(), "{:?}: {}", options, path);
+        }
+        let output = Command::new(dir.join("main")).output().unwrap();
+        assert_eq!(stdout(&output), "linked\n", "{:?}", options);
+    }
+}
+
+#[test]
+fn a_c_program_can_call_a_custom_entry() {
+    let dir = test_dir("a_c_program_can_call_a_custom_entry");
+    write(
+        &dir,
+        "start.ratio",
+        "fn start() {\n    print(\"in start\");\n    return 5;\n}\n",
+    );
+    let harness = "#include <stdio.h>\n\nlong start(void);\n\nint main(void) {\n    printf(\"start returned %ld\\n\", start());\n    return 0;\n}\n";
+    write(&dir, "harness.c", harness);
+    let output = ratio(&dir)
+        .args([
+            "--entry",
+            "start",
+            "--emit",
+            "obj",
+            "start.ratio",
+            "-o",
+            "start.o",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    cc(&dir, &["harness.c", "start.o", "-o", "harness"]);
+    let output = Command::new(dir.join("harness")).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "in start\nstart returned 5\n");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn release_builds_are_stripped_unless_debugging() {
+    use object::Object;
+
+    let dir = test_dir("release_builds_are_stripped_unless_debugging");
+    write(
+        &dir,
+        "main.ratio",
+        "fn main() {\n    print(\"built\");\n    return 0;\n}\n",
+    );
+    let build = |name: &str, options: &[&str]| {
+        let output = ratio(&dir)
+            .args(options)
+            .args(["main.ratio", "-o", name])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+        let bytes = std::fs::read(dir.join(name)).unwrap();
+        let file = object::File::parse(&*bytes).expect("the output is an executable");
+        (bytes.len(), file.symbol_table().is_some())
+    };
+    let (default_size, default_symbols) = build("default", &[]);
+    let (release_size, release_symbols) = build("release", &["--release"]);
+    assert!(default_symbols);
+    assert!(!release_symbols);
+    assert!(
+        release_size < default_size,
+        "{} is not smaller than {}",
+        release_size,
+        default_size
+    );
+    // Only LLVM generates debug information, which needs the symbols kept.
+    #[cfg(feature = "llvm")]
+    assert!(build("debug", &["--release", "-g"]).1);
+}
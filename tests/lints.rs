@@ -0,0 +1,99 @@
+mod common;
+
+use common::{ratio, stderr, test_dir, write};
+
+/// A program with an unused function and a constant condition.
+const SOURCE: &str = "fn unused() {\n    return 1;\n}\n\nfn main() {\n    if 1 < 2 {\n        print(\"yes\");\n    }\n    return 0;\n}\n";
+
+const DEAD_CODE: &str = "function `unused` is never called";
+const CONSTANT_CONDITION: &str = "condition is always true";
+
+/// Checks the program with `options`, returning the exit status and the diagnostics' headers.
+fn check(test: &str, options: &[&str]) -> (Option<i32>, Vec<String>) {
+    let dir = test_dir(test);
+    write(&dir, "lints.ratio", SOURCE);
+    let output = ratio(&dir)
+        .args(["check", "--color", "never"])
+        .args(options)
+        .arg("lints.ratio")
+        .output()
+        .unwrap();
+    let headers = stderr(&output)
+        .lines()
+        .filter(|line| line.starts_with("warning") || line.starts_with("error"))
+        .map(str::to_string)
+        .collect();
+    (output.status.code(), headers)
+}
+
+#[test]
+fn lints_warn_by_default() {
+    let (status, headers) = check("lints_warn_by_default", &[]);
+    assert_eq!(status, Some(0));
+    assert_eq!(
+        headers,
+        [
+            format!("warning: {} [-Wdead-code]", DEAD_CODE),
+            format!("warning: {} [-Wconstant-condition]", CONSTANT_CONDITION),
+        ]
+    );
+}
+
+#[test]
+fn a_lint_can_be_turned_off() {
+    let (status, headers) = check("a_lint_can_be_turned_off", &["-Wno-dead-code"]);
+    assert_eq!(status, Some(0));
+    assert_eq!(
+        headers,
+        [format!(
+            "warning: {} [-Wconstant-condition]",
+            CONSTANT_CONDITION
+        )]
+    );
+    let (status, headers) = check("a_lint_can_be_turned_off", &["-W", "no-constant-condition"]);
+    assert_eq!(status, Some(0));
+    assert_eq!(headers, [format!("warning: {} [-Wdead-code]", DEAD_CODE)]);
+}
+
+#[test]
+fn one_lint_can_be_made_an_error() {
+    let (status, headers) = check(
+        "one_lint_can_be_made_an_error",
+        &["-Werror=constant-condition"],
+    );
+    assert_eq!(status, Some(1));
+    assert_eq!(
+        headers,
+        [
+            format!("warning: {} [-Wdead-code]", DEAD_CODE),
+            format!("error: {} [-Werror=constant-condition]", CONSTANT_CONDITION),
+        ]
+    );
+}
+
+#[test]
+fn every_lint_can_be_made_an_error() {
+    let (status, headers) = check("every_lint_can_be_made_an_error", &["-Werror"]);
+    assert_eq!(status, Some(1));
+    assert_eq!(
+        headers,
+        [
+            format!("error: {} [-Werror=dead-code]", DEAD_CODE),
+            format!("error: {} [-Werror=constant-condition]", CONSTANT_CONDITION),
+        ]
+    );
+}
+
+#[test]
+fn every_warning_can_be_silenced() {
+    let (status, headers) = check("every_warning_can_be_silenced", &["-w"]);
+    assert_eq!(status, Some(0));
+    assert!(headers.is_empty(), "{:?}", headers);
+}
+
+#[test]
+fn an_unknown_lint_is_a_usage_error() {
+    let (status, headers) = check("an_unknown_lint_is_a_usage_error", &["-Wdead-cod"]);
+    assert_eq!(status, Some(2));
+    assert_eq!(headers, ["error: unknown lint `dead-cod` in `-Wdead-cod`"]);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{ratio, stderr, test_dir, write};
+
+const BROKEN: &str = "fn main() {\n    return 1 +;\n}\n";
+const HELLO: &str = "fn main() {\n    print(\"hello\");\n    return 0;\n}\n";
+
+/// The lines of `stderr` that start a diagnostic.
+fn diagnostics(stderr: &str) -> Vec<&str> {
+    stderr
+        .lines()
+        .filter(|line| line.starts_with("error") || line.starts_with("warning"))
+        .collect()
+}
+
+#[test]
+fn a_syntax_error_is_reported_once_without_debug_output() {
+    let dir = test_dir("a_syntax_error_is_reported_once_without_debug_output");
+    write(&dir, "broken.ratio", BROKEN);
+    let output = ratio(&dir)
+        .args(["--color", "never", "broken.ratio"])
+        .output()
+        .unwrap();
+    let stderr = stderr(&output);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        diagnostics(&stderr),
+        ["error[R0001]: expected an expression after '+', found ';'"]
+    );
+    assert!(!stderr.contains("DEBUG"), "{}", stderr);
+    assert!(!stderr.contains("trace:"), "{}", stderr);
+}
+
+#[test]
+fn diagnostics_do_not_depend_on_the_log_level() {
+    let dir = test_dir("diagnostics_do_not_depend_on_the_log_level");
+    write(&dir, "broken.ratio", BROKEN);
+    let quiet = ratio(&dir)
+        .args(["--color", "never", "broken.ratio"])
+        .output()
+        .unwrap();
+    let verbose = ratio(&dir)
+        .args(["-vv", "--color", "never", "broken.ratio"])
+        .output()
+        .unwrap();
+    assert_eq!(verbose.status.code(), Some(1));
+    let verbose = stderr(&verbose);
+    let logged: Vec<&str> = verbose
+        .lines()
+        .filter(|line| !line.starts_with("trace:"))
+        .collect();
+    assert_eq!(logged.join("\n") + "\n", stderr(&quiet));
+}
+
+#[test]
+fn the_log_level_comes_from_the_options_or_ratio_log() {
+    let dir = test_dir("the_log_level_comes_from_the_options_or_ratio_log");
+    write(&dir, "hello.ratio", HELLO);
+    let compile = |options: &[&str], log: Option<&str>| {
+        let mut command = ratio(&dir);
+        command.args(["--emit", "obj", "hello.ratio", "-o", "hello.o"]);
+        command.args(options);
+        if let Some(log) = log {
+            command.env("RATIO_LOG", log);
+        }
+        let output = command.output().unwrap();
+        assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+        stderr(&output)
+    };
+
+    assert_eq!(compile(&[], None), "");
+    let info = compile(&["-v"], None);
+    assert!(info.contains("opt-level: 2\n"), "{}", info);
+    assert!(!info.contains("trace:"), "{}", info);
+    let trace = compile(&["-vv"], None);
+    assert!(
+        trace.contains("trace: generating the output took"),
+        "{}",
+        trace
+    );
+    assert_eq!(
+        compile(&[], Some("info")).lines().count(),
+        info.lines().count()
+    );
+    assert!(compile(&[], Some("trace")).contains("trace:"));
+}
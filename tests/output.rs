@@ -0,0 +1,68 @@
+mod common;
+
+use std::process::{Command, Stdio};
+
+use common::{ratio, stderr, stdout, test_dir, write};
+
+fn printing(text: &str) -> String {
+    format!(
+        "fn main() {{\n    print(\"{}\");\n    return 0;\n}}\n",
+        text
+    )
+}
+
+#[test]
+fn concurrent_compiles_keep_their_objects_apart() {
+    let dir = test_dir("concurrent_compiles_keep_their_objects_apart");
+    let names = ["first", "second", "third", "fourth"];
+    for name in names {
+        write(&dir, &format!("{}.ratio", name), &printing(name));
+    }
+    // The cache would let a compile skip generating its object altogether.
+    let children: Vec<_> = names
+        .iter()
+        .map(|name| {
+            ratio(&dir)
+                .args(["--no-cache", &format!("{}.ratio", name)])
+                .stderr(Stdio::piped())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for child in children {
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+    for name in names {
+        let output = Command::new(dir.join(name)).output().unwrap();
+        assert_eq!(stdout(&output), format!("{}\n", name));
+    }
+}
+
+#[test]
+fn a_failed_link_leaves_the_previous_output_alone() {
+    let dir = test_dir("a_failed_link_leaves_the_previous_output_alone");
+    write(&dir, "good.ratio", &printing("good"));
+    let source =
+        "extern fn no_such_function();\n\nfn main() {\n    no_such_function();\n    return 0;\n}\n";
+    write(&dir, "bad.ratio", source);
+    let output = ratio(&dir)
+        .args(["good.ratio", "-o", "program"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let before = std::fs::read(dir.join("program")).unwrap();
+
+    let output = ratio(&dir)
+        .args(["bad.ratio", "-o", "program"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(std::fs::read(dir.join("program")).unwrap() == before);
+    let mut entries: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    entries.sort();
+    assert_eq!(entries, ["bad.ratio", "good.ratio", "program"]);
+}
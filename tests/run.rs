@@ -0,0 +1,190 @@
+mod common;
+
+use common::{ratio, stderr, stdout, test_dir, write};
+
+const HELLO: &str = "fn main() {\n    print(\"hello\");\n    return 3;\n}\n";
+
+#[test]
+fn run_exits_with_the_programs_status() {
+    let dir = test_dir("run_exits_with_the_programs_status");
+    write(&dir, "hello.ratio", HELLO);
+    let output = ratio(&dir).args(["run", "hello.ratio"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "hello\n");
+}
+
+#[test]
+fn run_leaves_no_executable_behind() {
+    let dir = test_dir("run_leaves_no_executable_behind");
+    write(&dir, "hello.ratio", HELLO);
+    ratio(&dir).args(["run", "hello.ratio"]).output().unwrap();
+    let entries: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(entries.len(), 1);
+}
+
+#[test]
+fn run_cleans_up_after_a_failed_link() {
+    let dir = test_dir("run_cleans_up_after_a_failed_link");
+    let source = "extern fn nosuch(x: int) int;\n\nfn main() {\n    return nosuch(1);\n}\n";
+    write(&dir, "missing.ratio", source);
+    let tmp = dir.join("tmp");
+    std::fs::create_dir(&tmp).unwrap();
+    let output = ratio(&dir)
+        .env("TMPDIR", &tmp)
+        .args(["run", "missing.ratio"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    let entries: Vec<_> = std::fs::read_dir(&tmp).unwrap().collect();
+    assert!(entries.is_empty(), "{:?}", entries);
+}
+
+#[test]
+fn global_options_can_come_before_run() {
+    let dir = test_dir("global_options_can_come_before_run");
+    write(&dir, "hello.ratio", HELLO);
+    for options in [&["-v"][..], &["--color", "never"], &["-w"]] {
+        let output = ratio(&dir)
+            .args(options)
+            .args(["run", "hello.ratio"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+        assert_eq!(stdout(&output), "hello\n");
+    }
+}
+
+#[test]
+fn compile_options_are_rejected_before_run() {
+    let dir = test_dir("compile_options_are_rejected_before_run");
+    write(&dir, "hello.ratio", HELLO);
+    let output = ratio(&dir)
+        .args(["-o", "hello", "run", "hello.ratio"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("`-o` cannot be used with a subcommand"));
+}
+
+#[test]
+fn input_is_reported_as_unsupported() {
+    let dir = test_dir("input_is_reported_as_unsupported");
+    let source = "fn main() {\n    var s: str = input();\n    print(s);\n    return 0;\n}\n";
+    write(&dir, "input.ratio", source);
+    let output = ratio(&dir).args(["run", "input.ratio"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("`input` is not supported by the"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("input.ratio:2:5"), "{}", stderr);
+}
+
+#[test]
+fn printf_declared_as_returning_int_returns_the_bytes_written() {
+    let dir = test_dir("printf_declared_as_returning_int_returns_the_bytes_written");
+    let source = "extern fn printf(fmt: str) int;\n\nfn main() {\n    var n: int = printf(\"hello\");\n    print(\"\");\n    return n;\n}\n";
+    write(&dir, "printf.ratio", source);
+    let output = ratio(&dir).args(["run", "printf.ratio"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "hello\n");
+}
+
+#[test]
+fn mains_return_value_is_the_exit_status() {
+    let dir = test_dir("mains_return_value_is_the_exit_status");
+    for status in [0, 1, 42] {
+        let source = format!("fn main() {{\n    return {};\n}}\n", status);
+        write(&dir, "status.ratio", &source);
+        let output = ratio(&dir).args(["run", "status.ratio"]).output().unwrap();
+        assert_eq!(output.status.code(), Some(status), "{}", stderr(&output));
+    }
+}
+
+#[test]
+fn a_program_can_span_several_files() {
+    let dir = test_dir("a_program_can_span_several_files");
+    write(&dir, "main.ratio", "fn main() {\n    return twice(4);\n}\n");
+    write(
+        &dir,
+        "twice.ratio",
+        "\n\nfn twice(x: int) {\n    return x * 2;\n}\n",
+    );
+    let output = ratio(&dir)
+        .args(["run", "main.ratio", "twice.ratio"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(8), "{}", stderr(&output));
+}
+
+#[test]
+fn errors_name_the_file_they_are_in() {
+    let dir = test_dir("errors_name_the_file_they_are_in");
+    write(&dir, "main.ratio", "fn main() {\n    return twice(4);\n}\n");
+    write(
+        &dir,
+        "twice.ratio",
+        "\n\nfn twice(x: int) {\n    return y * 2;\n}\n",
+    );
+    let output = ratio(&dir)
+        .args(["run", "--color", "never", "main.ratio", "twice.ratio"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains(" --> twice.ratio:4:12\n"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn bools_can_be_stored_reassigned_and_passed() {
+    let dir = test_dir("bools_can_be_stored_reassigned_and_passed");
+    let source = "\
+fn pick(flag: bool, a: int, b: int) {
+    if flag {
+        return a;
+    }
+    return b;
+}
+
+fn main() {
+    var i: int = 3;
+    var done: bool = i >= 2;
+    print(done);
+    var other: bool = i < 2;
+    done = other;
+    print(done);
+    print(pick(i == 3, 10, 20));
+    print(pick(done, 10, 20));
+    return 0;
+}
+";
+    write(&dir, "bools.ratio", source);
+    let output = ratio(&dir).args(["run", "bools.ratio"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "1\n0\n10\n20\n");
+}
+
+#[test]
+fn declared_types_are_written_as_type_keywords() {
+    // Before types were parsed into `Type`, only `int` and `bool` variables were accepted, and an
+    // extern's return type had to be a plain identifier, so both of these were syntax errors.
+    let dir = test_dir("declared_types_are_written_as_type_keywords");
+    let source = "\
+extern fn abs(x: int) int;
+
+fn main() {
+    var greeting: str = \"hi\";
+    print(greeting);
+    return abs(0 - 7);
+}
+";
+    write(&dir, "types.ratio", source);
+    let output = ratio(&dir).args(["run", "types.ratio"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(7), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "hi\n");
+}